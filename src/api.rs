@@ -6,44 +6,172 @@
 // much about semantics, and thereby importantly means we do less error
 // handling during parsing and more during subsequent semantic analysis.
 use std;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use expr;
 use function;
+use fuzz;
 use stmt;
-use typ::{EnumValue, Native, Type};
+use typ::{EnumValue, Field, Name, Native, Qualifier, Type};
 use opcode::{BinOp, UOp};
+use util;
 use variable;
 use variable::Generator;
 
-#[derive(Clone, Debug)]
+// The libFuzzer harness spells out every state of every free variable, so it
+// is only made when no generator has more states than this.
+const MAX_HARNESS_STATES: usize = 1 << 16;
+// Generators with more states than this are sampled uniformly, as weights()
+// would give a weight for every one of them.
+const MAX_WEIGHTED_STATES: usize = 1 << 16;
+// GenerationStats counts distinct values exactly up to this many of them, and
+// approximately, in a bitmap of DISTINCT_BITS bits, after that.
+const MAX_EXACT_DISTINCT: usize = 1 << 16;
+const DISTINCT_BITS: usize = 1 << 20;
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum DeclType {
 	Basic(Type),
-	Struct(String, Vec<UDTDecl>),
+	// the name, the fields and whether it is packed, i.e. has no padding
+	// between its fields.
+	Struct(String, Vec<UDTDecl>, bool),
 	Enum(String, Vec<EnumValue>),
+	// An enum whose enumerators are bits that are OR'd together.
+	Flags(String, Vec<EnumValue>),
 	StructRef(String),
 	EnumRef(String),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct UDTDecl {
 	pub name: String,
 	pub ty: DeclType,
+	pub bits: Option<u32>, // width, for bitfields
+	pub offset: Option<u32>, // expected offsetof(), checked by generated code
 }
 
-#[derive(Clone, Debug)]
+impl UDTDecl {
+	// The field as it is written in a struct definition, without the ';'.
+	fn to_dsl(&self) -> String {
+		// the sizes of an array go after the name, as in C.
+		let (mut rv, dims) = match self.ty {
+			DeclType::Basic(ref ty) => {
+				let (base, dims) = ty.array_parts();
+				(type_dsl(base), dims)
+			},
+			_ => (self.ty.to_dsl(), String::new()),
+		};
+		if !self.name.is_empty() {
+			rv = rv + " " + &self.name + &dims;
+		}
+		if let Some(bits) = self.bits {
+			rv = rv + ":" + &bits.to_string();
+		}
+		if let Some(off) = self.offset {
+			rv = rv + " @ " + &off.to_string();
+		}
+		rv
+	}
+
+	// A plain field: not a bitfield, and without an expected offset.
+	#[allow(dead_code)]
+	pub fn new(name: &str, ty: DeclType) -> Self {
+		UDTDecl{name: name.to_string(), ty: ty, bits: None, offset: None}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct FreeVarDecl {
 	pub name: String,
 	pub genname: String,
 	pub ty: DeclType, // Struct(...) and Enum(...) are not valid, but *Refs are.
+	// Every declaration of a shared variable refers to the same variable,
+	// whereas each declaration of a plain free variable creates a new one.
+	pub shared: bool,
 }
 
-#[derive(Clone, Debug)]
+impl FreeVarDecl {
+	#[allow(dead_code)]
+	pub fn new(name: &str, genname: &str, ty: DeclType) -> Self {
+		FreeVarDecl{name: name.to_string(), genname: genname.to_string(), ty: ty,
+		            shared: false}
+	}
+}
+
+// A value that one call produces and a later call consumes, through the
+// variable 'var'; see Program::data_flow().
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataFlow {
+	pub var: String,
+	pub producer: String,
+	pub consumer: String,
+}
+
+// What a function does with the resources it is given.  Every resource that
+// a constructor creates should eventually be given to a destructor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FuncKind {
+	Constructor, // "function:new": creates a resource
+	Destructor, // "function:delete": releases a resource
+	Operation, // "function:decl" or "function:use": anything else
+}
+
+// How a function uses the value behind a parameter.  Outputs must be pointers;
+// the function writes its results through them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamMode {
+	In, // the default: the function only reads the argument
+	Out, // "out": the function writes a result through the pointer
+	InOut, // "inout": the function reads and updates what the pointer points to
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct FuncDecl {
 	pub name: String,
 	pub retval: DeclType,
 	pub parameters: Vec<DeclType>,
+	pub kind: FuncKind,
+	// C conditions that must hold before every call; see Stmt::Guarded.
+	pub preconditions: Vec<String>,
+	// the mode of each parameter, in the same order as 'parameters'.
+	pub modes: Vec<ParamMode>,
+	// declared with a trailing "...", as printf is.
+	pub variadic: bool,
+	// "returns:bool": the int it returns is a truth value, where any non-zero
+	// value means true.
+	pub bool_return: bool,
 }
 
-#[derive(Clone, Debug)]
+impl FuncDecl {
+	#[allow(dead_code)]
+	pub fn new(name: &str, retval: DeclType, parameters: Vec<DeclType>,
+	           kind: FuncKind) -> Self {
+		let modes = vec![ParamMode::In; parameters.len()];
+		FuncDecl{name: name.to_string(), retval: retval, parameters: parameters,
+		         kind: kind, preconditions: Vec::new(), modes: modes,
+		         variadic: false, bool_return: false}
+	}
+
+	// Resolves the return and parameter types against the given types, giving
+	// a Function that is not bound to any arguments yet.  Unlike the resolution
+	// during analysis, an unknown struct or enum is an error here.
+	#[allow(dead_code)]
+	pub fn to_function(&self, types: &Vec<Type>) ->
+		Result<function::Function, String> {
+		let rtype = try!(resolve_decl(&self.retval, types));
+		let mut params: Vec<function::Parameter> = Vec::new();
+		for pm in self.parameters.iter() {
+			params.push(try!(resolve_decl(pm, types)));
+		}
+		let mut func = function::Function::new(&self.name, &rtype, &params);
+		func.variadic = self.variadic;
+		func.bool_return = self.bool_return;
+		Ok(func)
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Declaration {
 	// "Constrained" variables are the opposite of "free" variables; they might
 	// vary at runtime of the generated program, but the initial value is not a
@@ -51,19 +179,320 @@ pub enum Declaration {
 	Constrained(String /* name */, DeclType),
 	Free(FreeVarDecl),
 	Function(FuncDecl),
-	UDT(DeclType), // Error if the DeclType is not a Struct || Enum!
+	UDT(DeclType), // Error if the DeclType is not a Struct, Enum or Flags!
+	// A named integer, like a #define in C.  Enumerators and expressions can
+	// refer to it by name.
+	Const(String /* name */, i64),
+	// A library to link the generated code with; see Program::emit_build().
+	Link(String),
 }
 
-#[derive(Clone, Debug)]
+impl Declaration {
+	// Prints the declaration in the DSL, such that parsing the text gives back
+	// an equal declaration.  Enumerators always get their values, and every
+	// function parameter is followed by a comma.
+	#[allow(dead_code)]
+	pub fn to_dsl(&self) -> String {
+		match *self {
+			Declaration::Constrained(ref nm, ref ty) =>
+				format!("var:constrained {} {}", nm, ty.to_dsl()),
+			Declaration::Free(ref fvd) => {
+				let kw = if fvd.shared { "var:shared" } else { "var:free" };
+				format!("{} {} gen:{} {}", kw, fvd.name, fvd.genname,
+				        fvd.ty.to_dsl())
+			},
+			Declaration::Function(ref f) => {
+				let kind = match f.kind {
+					FuncKind::Constructor => "function:new",
+					FuncKind::Destructor => "function:delete",
+					FuncKind::Operation => "function:decl",
+				};
+				let mut params: Vec<String> = f.parameters.iter().zip(f.modes.iter())
+					.map(|(p, mode)| match *mode {
+						ParamMode::In => p.to_dsl() + ",",
+						ParamMode::Out => "out ".to_string() + &p.to_dsl() + ",",
+						ParamMode::InOut => "inout ".to_string() + &p.to_dsl() + ",",
+					}).collect();
+				if f.variadic {
+					params.push("...".to_string());
+				}
+				let mut rv = format!("{} {} {} {{{}}}", kind, f.name,
+				                     f.retval.to_dsl(), params.join(" "));
+				if f.bool_return {
+					rv = rv + " returns:bool";
+				}
+				for cond in f.preconditions.iter() {
+					rv = rv + " precondition " + cond + ";";
+				}
+				rv
+			},
+			Declaration::UDT(ref udt) => udt.to_dsl(),
+			Declaration::Const(ref nm, value) => format!("const {} = {};", nm, value),
+			Declaration::Link(ref lib) => format!("link \"{}\";", lib),
+		}
+	}
+}
+
+impl DeclType {
+	// How the type is written in the DSL; see Declaration::to_dsl().  Structs
+	// and enums give their complete definition.
+	fn to_dsl(&self) -> String {
+		match *self {
+			DeclType::Basic(ref ty) => type_dsl(ty),
+			DeclType::StructRef(ref nm) => format!("struct {}", nm),
+			DeclType::EnumRef(ref nm) => format!("enum {}", nm),
+			DeclType::Struct(ref nm, ref fields, packed) => {
+				let mut rv = match packed {
+					true => String::from("packed struct "),
+					false => String::from("struct "),
+				};
+				if !nm.is_empty() {
+					rv = rv + nm + " ";
+				}
+				rv.push_str("{\n");
+				for fld in fields.iter() {
+					let decl = fld.to_dsl().replace("\n", "\n\t");
+					rv = rv + "\t" + &decl + ";\n";
+				}
+				rv + "}"
+			},
+			DeclType::Enum(ref nm, ref values) |
+			DeclType::Flags(ref nm, ref values) => {
+				let kw = match *self {
+					DeclType::Flags(_, _) => "enum:flags",
+					_ => "enum",
+				};
+				let mut rv = format!("{} {} {{\n", kw, nm);
+				for &(ref name, _, ref spelling) in values.iter() {
+					rv = rv + "\t" + name + " = " + spelling + ",\n";
+				}
+				rv + "}"
+			},
+		}
+	}
+}
+
+// How the given struct or enum is referred to in C, i.e. "struct foo" or the
+// name of the enum, which the header typedefs.
+fn udt_key(udt: &DeclType) -> Option<String> {
+	match *udt {
+		DeclType::Struct(ref nm, _, _) | DeclType::StructRef(ref nm) =>
+			Some(format!("struct {}", nm)),
+		DeclType::Enum(ref nm, _) | DeclType::Flags(ref nm, _) |
+		DeclType::EnumRef(ref nm) => Some(nm.clone()),
+		DeclType::Basic(_) => None,
+	}
+}
+
+// The structs and enums that the fields hold by value, and thus must be
+// defined before a struct with these fields.
+fn value_dependencies(fields: &Vec<UDTDecl>) -> Vec<String> {
+	let mut rv: Vec<String> = Vec::new();
+	for fld in fields.iter() {
+		match fld.ty {
+			DeclType::Struct(_, ref inner, _) => rv.extend(value_dependencies(inner)),
+			_ => rv.extend(udt_key(&fld.ty)),
+		};
+	}
+	rv
+}
+
+// A cycle of structs that hold each other by value, as the names of the
+// structs along it.  The first struct is repeated at the end.
+fn value_cycle(decls: &Vec<Declaration>) -> Option<Vec<String>> {
+	let structs: Vec<(String, Vec<String>)> = decls.iter().filter_map(
+		|decl| match *decl {
+			Declaration::UDT(DeclType::Struct(ref nm, ref flds, _)) =>
+				Some((format!("struct {}", nm), value_dependencies(flds))),
+			_ => None,
+		}
+	).collect();
+	// follows the fields from 'key'; 'path' is how we got there.
+	fn visit(key: &String, structs: &Vec<(String, Vec<String>)>,
+	         path: &mut Vec<String>) -> bool {
+		if let Some(i) = path.iter().position(|k| k == key) {
+			path.drain(..i);
+			path.push(key.clone());
+			return true;
+		}
+		let deps = match structs.iter().find(|s| s.0 == *key) {
+			Some(s) => &s.1,
+			None => return false, // enums, or structs that are not declared.
+		};
+		path.push(key.clone());
+		if deps.iter().any(|dep| visit(dep, structs, path)) {
+			return true;
+		}
+		path.pop();
+		false
+	}
+	for &(ref key, _) in structs.iter() {
+		let mut path: Vec<String> = Vec::new();
+		if visit(key, &structs, &mut path) {
+			return Some(path.iter().map(|k| k["struct ".len()..].to_string())
+			            .collect());
+		}
+	}
+	None
+}
+
+// The C definition of the fields, with their braces.
+fn c_fields(fields: &Vec<UDTDecl>) -> String {
+	let mut rv = String::from("{\n");
+	for fld in fields.iter() {
+		let dims = match fld.ty {
+			DeclType::Basic(ref ty) => ty.array_parts().1,
+			_ => String::new(),
+		};
+		let ty = match fld.ty {
+			DeclType::Basic(ref ty) => ty.array_parts().0.name(),
+			DeclType::Struct(ref nm, ref inner, _) if nm.is_empty() =>
+				format!("struct {}", c_fields(inner)),
+			DeclType::Struct(ref nm, ref inner, _) =>
+				format!("struct {} {}", nm, c_fields(inner)),
+			_ => udt_key(&fld.ty).unwrap(),
+		};
+		rv = rv + &ty;
+		if !fld.name.is_empty() {
+			rv = rv + " " + &fld.name + &dims;
+		}
+		if let Some(bits) = fld.bits {
+			rv = rv + &format!(" : {}", bits);
+		}
+		rv.push_str(";\n");
+	}
+	rv + "}"
+}
+
+// Writes the C definition of the given struct or enum, after the definitions
+// of the types it depends on.  'defined' lists the types that were written
+// already.
+fn define_type(udt: &DeclType, udts: &Vec<&DeclType>,
+               defined: &mut Vec<String>, strm: &mut std::io::Write)
+	-> std::io::Result<()> {
+	let key = match udt_key(udt) {
+		Some(key) => key,
+		None => return Ok(()),
+	};
+	if defined.contains(&key) {
+		return Ok(());
+	}
+	defined.push(key);
+	match *udt {
+		DeclType::Struct(_, ref fields, _) if fields.is_empty() => Ok(()),
+		DeclType::Struct(ref nm, ref fields, packed) => {
+			for dep in value_dependencies(fields).iter() {
+				let found = udts.iter().find(|u| udt_key(u).as_ref() == Some(dep));
+				if let Some(depudt) = found {
+					try!(define_type(depudt, udts, defined, strm));
+				}
+			}
+			let attr = if packed { " __attribute__((packed))" } else { "" };
+			writeln!(strm, "struct {} {}{};", nm, c_fields(fields), attr)
+		},
+		// the enum and its typedef have the same name, as the header would.
+		DeclType::Enum(ref nm, ref values) |
+		DeclType::Flags(ref nm, ref values) => {
+			try!(writeln!(strm, "typedef enum {} {{", nm));
+			for &(ref name, _, ref spelling) in values.iter() {
+				try!(writeln!(strm, "\t{} = {},", name, spelling));
+			}
+			writeln!(strm, "}} {};", nm)
+		},
+		_ => Ok(()),
+	}
+}
+
+// Whether a value of type 'arg' obviously cannot be passed for a parameter of
+// type 'param': a pointer for a number or the other way around, or a struct
+// for anything but the same struct.  Enums and integers mix freely, as in C.
+fn incompatible(arg: &Type, param: &Type) -> bool {
+	match (arg.unqualified(), param.unqualified()) {
+		(&Type::Pointer(_), &Type::Pointer(_)) => false,
+		(&Type::Struct(ref a, _, _), &Type::Struct(ref p, _, _)) => a != p,
+		(&Type::Pointer(_), _) | (_, &Type::Pointer(_)) => true,
+		(&Type::Struct(_, _, _), _) | (_, &Type::Struct(_, _, _)) => true,
+		_ => false,
+	}
+}
+
+// Whether 'ty' needs C's bool, for itself or for a part of it.
+fn mentions_bool(ty: &Type) -> bool {
+	match *ty {
+		Type::Builtin(nat) => nat == Native::Boolean,
+		Type::Pointer(ref inner) | Type::Qualified(ref inner, _) |
+		Type::Array(ref inner, _) => mentions_bool(inner),
+		Type::Struct(_, ref flds, _) => flds.iter().any(|f| mentions_bool(&f.1)),
+		Type::Enum(_, _) => false,
+		Type::Function(ref f) =>
+			mentions_bool(&f.retval) || f.parameters.iter().any(mentions_bool),
+	}
+}
+
+// The DSL spelling of a type that was resolved while parsing.
+fn type_dsl(ty: &Type) -> String {
+	match *ty {
+		Type::Builtin(ref nat) => match *nat {
+			Native::U8 => "u8", Native::U16 => "u16", Native::U32 => "u32",
+			Native::U64 => "u64", Native::U128 => "u128", Native::Usize => "usize",
+			Native::I8 => "i8", Native::I16 => "i16", Native::I32 => "i32",
+			Native::I64 => "i64", Native::I128 => "i128", Native::Integer => "int",
+			Native::Unsigned => "unsigned", Native::Void => "void",
+			Native::Character => "char", Native::WideCharacter => "wchar_t",
+			Native::Short => "short", Native::UShort => "unsigned short",
+			Native::Long => "long", Native::ULong => "unsigned long",
+			Native::LongLong => "long long",
+			Native::ULongLong => "unsigned long long", Native::Boolean => "bool",
+			Native::F32 | Native::F64 =>
+				panic!("the DSL has no spelling for {:?}", nat),
+		}.to_string(),
+		Type::Pointer(ref inner) => format!("pointer {}", type_dsl(inner)),
+		Type::Struct(ref nm, _, _) => format!("struct {}", nm),
+		Type::Enum(ref nm, _) => format!("enum {}", nm),
+		Type::Qualified(ref ptr, ref quals) => {
+			let mut rv = String::new();
+			for q in quals.iter() {
+				rv.push_str(&match *q {
+					Qualifier::Const => "const ".to_string(),
+					Qualifier::Restrict => "restrict ".to_string(),
+					Qualifier::Aligned(n) => format!("aligned({}) ", n),
+				});
+			}
+			rv + &type_dsl(ptr)
+		},
+		Type::Function(ref f) => panic!("the DSL has no function types ({})",
+		                                f.name),
+		// see UDTDecl::to_dsl().
+		Type::Array(_, _) => panic!("the sizes of an array follow its name"),
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Expr {
 	VarRef(UOp, String /* varname */),
+	// ops that do not combine into one, outermost first, as in "**pp".
+	VarOps(Vec<UOp>, String /* varname */),
 	IConst(String),
 	FConst(String),
 	Call(String /* funcname */, Box<Vec<Expr>> /* args */),
 	Compound(Box<Expr>, BinOp, Box<Expr>),
 	Field(String, String),
+	// a field of the struct that the variable points to, as in "p->x".
+	ArrowField(String, String),
 }
-#[derive(Clone, Debug)]
+
+impl Expr {
+	// The variable with the given ops applied to it, outermost first.
+	pub fn with_ops(ops: Vec<UOp>, name: String) -> Expr {
+		match ops.len() {
+			0 => Expr::VarRef(UOp::None, name),
+			1 => Expr::VarRef(ops[0], name),
+			_ => Expr::VarOps(ops, name),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Stmt {
 	Basic(Expr),
 	Declaration(Declaration),
@@ -72,6 +501,11 @@ pub enum Stmt {
 	Constraint(Expr),
 	If(Expr, Box<Vec<Stmt>>),
 	While(Expr, Box<Vec<Stmt>>),
+	// run the body N times; the loop variable counts the iterations.
+	Repeat(u64, Option<String> /* loop variable */, Box<Vec<Stmt>>),
+	// the statement is only run if the C conditions hold.  The conditions are
+	// not parsed, but the variables in them are renamed for the generated code.
+	Guarded(Vec<String> /* preconditions */, Box<Stmt>),
 }
 
 #[derive(Debug)]
@@ -79,6 +513,22 @@ pub struct Symbol {
 	pub name: String,
 	pub generator: Box<variable::Generator>, // actual, used generator.
 	pub typ: Type,
+	// the identifier we use for this symbol in generated code.  Assigned by the
+	// Program during analysis; until then, it's just the name.
+	cname: String,
+}
+impl Symbol {
+	pub fn new(nm: &str, gen: Box<variable::Generator>, ty: Type) -> Self {
+		Symbol{name: nm.to_string(), generator: gen, typ: ty,
+		       cname: nm.to_string()}
+	}
+
+	// The name as it appears in the generated C code.  This differs from the
+	// name() when the user's name is not a valid or unique C identifier.
+	pub fn codegen_name(&self) -> String { self.cname.clone() }
+}
+impl Name for Symbol {
+	fn name(&self) -> String { self.name.clone() }
 }
 impl PartialEq for Symbol {
 	fn eq(&self, other: &Symbol) -> bool {
@@ -89,7 +539,7 @@ impl Eq for Symbol {}
 impl Clone for Symbol {
 	fn clone(&self) -> Self {
 		Symbol{name: self.name.clone(), generator: self.generator.clone(),
-		       typ: self.typ.clone()}
+		       typ: self.typ.clone(), cname: self.cname.clone()}
 	}
 
 	#[allow(unused_variables)]
@@ -98,9 +548,179 @@ impl Clone for Symbol {
 	}
 }
 
+// Feedback on how much of a program's state space has been generated.
+#[derive(Clone, Debug)]
+pub struct GenerationStats {
+	pub emitted: usize, // number of programs generated, including repeats
+	pub unique: usize, // number of distinct program texts among those
+	pub total: usize, // number of states in the program
+	// Per free variable: its name and the lowest and highest generator state
+	// that we emitted a program for.
+	pub ranges: Vec<(String, usize, usize)>,
+	// true once a program was emitted for every state.
+	pub completed: bool,
+	cases: DistinctCount,
+	hashes: DistinctCount,
+}
+
+// Counts distinct values in bounded memory.  Up to MAX_EXACT_DISTINCT values
+// are kept in a set; past that, values are hashed into a bitmap and counted
+// by linear counting, which estimates the count from the fraction of bits
+// still clear.  A counter made with direct() instead indexes its bitmap by the
+// values themselves, and stays exact.
+#[derive(Clone, Debug)]
+struct DistinctCount {
+	exact: HashSet<u64>,
+	bitmap: Vec<u64>,
+	nset: usize, // number of bits set in 'bitmap'
+	hashed: bool,
+}
+
+impl DistinctCount {
+	fn new() -> Self {
+		DistinctCount{exact: HashSet::new(), bitmap: Vec::new(), nset: 0,
+		              hashed: false}
+	}
+
+	// A counter for values below 'n'.
+	fn direct(n: usize) -> Self {
+		DistinctCount{exact: HashSet::new(), bitmap: vec![0; (n+63)/64],
+		              nset: 0, hashed: false}
+	}
+
+	fn set(&mut self, bit: usize) {
+		let (word, mask) = (bit / 64, 1u64 << (bit % 64));
+		if self.bitmap[word] & mask == 0 {
+			self.bitmap[word] |= mask;
+			self.nset += 1;
+		}
+	}
+
+	fn hashed_bit(v: u64) -> usize {
+		use std::hash::{Hash, Hasher};
+		use std::collections::hash_map::DefaultHasher;
+		let mut hasher = DefaultHasher::new();
+		v.hash(&mut hasher);
+		(hasher.finish() % DISTINCT_BITS as u64) as usize
+	}
+
+	fn insert(&mut self, v: u64) {
+		if self.hashed {
+			let bit = DistinctCount::hashed_bit(v);
+			self.set(bit);
+		} else if !self.bitmap.is_empty() {
+			if (v as usize) < self.bitmap.len() * 64 {
+				self.set(v as usize);
+			}
+		} else {
+			self.exact.insert(v);
+			if self.exact.len() > MAX_EXACT_DISTINCT {
+				let exact = std::mem::replace(&mut self.exact, HashSet::new());
+				self.bitmap = vec![0; DISTINCT_BITS / 64];
+				self.hashed = true;
+				for v in exact {
+					self.set(DistinctCount::hashed_bit(v));
+				}
+			}
+		}
+	}
+
+	fn count(&self) -> usize {
+		if !self.hashed {
+			return if self.bitmap.is_empty() { self.exact.len() }
+			       else { self.nset };
+		}
+		// once every bit is set, the estimate stops growing.
+		let m = DISTINCT_BITS as f64;
+		let clear = std::cmp::max(DISTINCT_BITS - self.nset, 1) as f64;
+		(-m * (clear / m).ln()).round() as usize
+	}
+}
+
+impl GenerationStats {
+	pub fn new() -> Self {
+		GenerationStats{emitted: 0, unique: 0, total: 0, ranges: Vec::new(),
+		                completed: false, cases: DistinctCount::new(),
+		                hashes: DistinctCount::new()}
+	}
+
+	// Notes that we emitted 'code' for the given case.  'states' gives the
+	// state of every free variable in that case.  Cases are counted exactly
+	// when there are at most DISTINCT_BITS of them; otherwise 'unique',
+	// 'completed' and fraction() are estimates.
+	fn record(&mut self, case: usize, code: &[u8], states: &Vec<(String, usize)>,
+	          total: usize) {
+		use std::hash::{Hash, Hasher};
+		use std::collections::hash_map::DefaultHasher;
+		let mut hasher = DefaultHasher::new();
+		code.hash(&mut hasher);
+		self.hashes.insert(hasher.finish());
+		if self.emitted == 0 && total <= DISTINCT_BITS {
+			self.cases = DistinctCount::direct(total);
+		}
+		self.cases.insert(case as u64);
+		if self.ranges.is_empty() {
+			self.ranges = states.iter().map(|&(ref nm, st)| (nm.clone(), st, st))
+				.collect();
+		}
+		for (rng, &(_, st)) in self.ranges.iter_mut().zip(states.iter()) {
+			rng.1 = rng.1.min(st);
+			rng.2 = rng.2.max(st);
+		}
+		self.emitted += 1;
+		self.unique = self.hashes.count().min(self.emitted);
+		self.total = total;
+		self.completed = self.cases.count().min(self.emitted) >= total;
+	}
+
+	// The fraction of the state space that we have emitted programs for.  This
+	// is mostly interesting when sampling cases instead of enumerating them.
+	#[allow(dead_code)]
+	pub fn fraction(&self) -> f64 {
+		if self.total == 0 {
+			return 0.0;
+		}
+		self.cases.count().min(self.total) as f64 / self.total as f64
+	}
+}
+
+// Where a budgeted generation run stopped: the next case to generate.  Pass it
+// to generate_from() to pick up where the run left off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResumeToken {
+	pub case: usize,
+	pub total: usize,
+}
+
+impl ResumeToken {
+	// true if the run generated every remaining case.
+	#[allow(dead_code)]
+	pub fn done(&self) -> bool {
+		self.case >= self.total
+	}
+}
+
+// A free variable whose value differs between two cases; see diff_cases().
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldDiff {
+	pub name: String,
+	pub before: String,
+	pub after: String,
+}
+
+// Receives the values of the free variables of every case; see drive().
+pub trait CaseSink {
+	// 'values' holds the name and the C value of each free variable, in
+	// declaration order.
+	fn case(&mut self, values: &[(String, String)]);
+}
+
 // Program object, represents the state of the abstract program given to us by
 // the user.
-#[derive(Debug)]
+// The tables that codegen walks are all Vecs, in the order things were
+// declared.  Iterating a HashMap instead would order the output differently
+// from run to run, and then the generated programs could not be diffed.
+#[derive(Clone, Debug)]
 pub struct Program {
 	pub declarations: Vec<Declaration>,
 	// The AST is what we parsed out from the user.  Essentially everything is
@@ -114,10 +734,48 @@ pub struct Program {
 	// those objects into 'statements'.
 	pub statements: Vec<stmt::Statement>,
 	symtab: Vec<Symbol>,
+	// names of the symbols which came from "free" declarations, i.e. those whose
+	// values are actually chosen by the fuzzer.
+	freevars: Vec<String>,
+	// names of the loop variables of "repeat" blocks.  They are declared by
+	// their loops, not at the top of the program.
+	loopvars: Vec<String>,
+	// names of the free variables that were declared "shared".
+	sharedvars: Vec<String>,
 	typetab: Vec<Type>,
 	// copy of generator list.  Expected users will clone() out of it to create
 	// the real/used Generators (that live in the symbol table).
 	genlist: Vec<Box<variable::Generator>>,
+	// seed for the random values of generated cases.
+	seed: u64,
+	// the case that the generators are currently in; see seek().
+	case: usize,
+	stats: GenerationStats,
+	// refuse to guess at generators for ambiguous types; see set_strict().
+	strict: bool,
+	// log the result of every call to stderr; see set_log_calls().
+	log_calls: bool,
+	// the number of calls logged so far while generating code.
+	ncalls: std::cell::Cell<usize>,
+	// define the structs and enums of the DSL in the generated code; see
+	// set_define_types().
+	define_types: bool,
+	// the values that the last codegen() gave the free variables, by name.
+	emitted: std::cell::RefCell<Vec<(String, String)>>,
+	// print an identifier for the case when it starts; see set_case_ids().
+	case_ids: bool,
+	// delete the resources that are still live at the end; see set_cleanup().
+	cleanup: bool,
+	// use GCC's and Clang's extensions to C; see set_gnu_extensions().
+	gnu_extensions: bool,
+	// silence the warnings that generated values cause; see
+	// set_suppress_warnings().
+	suppress_warnings: bool,
+	// define the values of read-only variables as macros; see
+	// set_named_values().
+	named_values: bool,
+	// the warnings that analyze() found, for the driver to show.
+	warnings: Vec<String>,
 }
 
 impl Program {
@@ -125,7 +783,244 @@ impl Program {
 		-> Program {
 		Program{declarations: (*decls).clone(), statements: Vec::new(),
 		        ast: (*stmts).clone(),
-		        symtab: Vec::new(), typetab: Vec::new(), genlist: Vec::new()}
+		        symtab: Vec::new(), freevars: Vec::new(), loopvars: Vec::new(),
+		        sharedvars: Vec::new(), typetab: Vec::new(),
+		        genlist: Vec::new(), seed: 0, case: 0,
+		        stats: GenerationStats::new(), strict: false,
+		        log_calls: false, ncalls: std::cell::Cell::new(0),
+		        define_types: false,
+		        emitted: std::cell::RefCell::new(Vec::new()), case_ids: false,
+		        cleanup: false, gnu_extensions: false, suppress_warnings: false,
+		        named_values: false, warnings: Vec::new()}
+	}
+
+	// Starts assembling a program in code, rather than by parsing the DSL.
+	#[allow(dead_code)]
+	pub fn builder() -> ProgramBuilder {
+		ProgramBuilder{decls: Vec::new(), stmts: Vec::new()}
+	}
+
+	#[allow(dead_code)]
+	pub fn set_seed(&mut self, seed: u64) {
+		self.seed = seed;
+	}
+
+	// In strict mode, analysis fails for free variables whose generator would
+	// have to guess at the type, such as the width of a plain 'integer',
+	// instead of warning and guessing.
+	#[allow(dead_code)]
+	pub fn set_strict(&mut self, strict: bool) {
+		self.strict = strict;
+	}
+
+	// With call logging, the generated code prints the value and errno after
+	// every call whose result is assigned, to triage by diffing runs.
+	#[allow(dead_code)]
+	pub fn set_log_calls(&mut self, log: bool) {
+		self.log_calls = log;
+	}
+
+	// Writes the logging of the value that 'fqn' just returned into 'lhs', if
+	// call logging is on.  Each logged call gets the next number.  Truth values
+	// are logged as 0 or 1, as functions differ in which non-zero value they
+	// give for true.
+	pub fn log_call(&self, strm: &mut std::io::Write, lhs: &expr::Expression,
+	                fqn: &function::Function) -> std::io::Result<()> {
+		use stmt::Code;
+		if !self.log_calls {
+			return Ok(());
+		}
+		// structs do not fit in a long.
+		match lhs.extype() {
+			Type::Struct(_, _, _) | Type::Builtin(Native::Void) => return Ok(()),
+			_ => (),
+		};
+		let n = self.ncalls.get();
+		self.ncalls.set(n+1);
+		try!(write!(strm, "\nfprintf(stderr, \"call {} -> %ld (errno=%d)\\n\", \
+		                   (long)", n));
+		if fqn.bool_return {
+			try!(write!(strm, "("));
+			try!(lhs.codegen(strm, self));
+			try!(write!(strm, " != 0)"));
+		} else {
+			try!(lhs.codegen(strm, self));
+		}
+		write!(strm, ", errno);")
+	}
+
+	// Normally the headers define the structs and enums that the DSL declares.
+	// For types that only the DSL declares, this makes the generated code
+	// define them itself, before they are used.
+	#[allow(dead_code)]
+	pub fn set_define_types(&mut self, define: bool) {
+		self.define_types = define;
+	}
+
+	// With case identifiers, every generated case prints its number and a hash
+	// of the values of its free variables when it starts, so that a crash can
+	// be traced back to the case that caused it.
+	#[allow(dead_code)]
+	pub fn set_case_ids(&mut self, ids: bool) {
+		self.case_ids = ids;
+	}
+
+	// With cleanup, the generated code ends by giving every resource that is
+	// still live to a destructor, newest first, so that leak checkers only
+	// report the leaks of the API itself.
+	#[allow(dead_code)]
+	pub fn set_cleanup(&mut self, cleanup: bool) {
+		self.cleanup = cleanup;
+	}
+
+	// With GNU extensions, a struct that is only used once, by passing its
+	// address to a function, is not declared up front.  Instead, the argument
+	// is the address of a compound literal, as in "f(&(struct X){...})".  The
+	// libFuzzer harness still declares every free variable up front.
+	#[allow(dead_code)]
+	pub fn set_gnu_extensions(&mut self, gnu: bool) {
+		self.gnu_extensions = gnu;
+	}
+
+	// Some generated values are deliberately dubious, such as integers cast to
+	// pointers, and compilers warn about them.  With suppressed warnings, the
+	// declarations of such values turn those warnings off, so that the code
+	// builds with -Werror.  Such variables are always declared up front.
+	#[allow(dead_code)]
+	pub fn set_suppress_warnings(&mut self, suppress: bool) {
+		self.suppress_warnings = suppress;
+	}
+
+	// With named values, a free variable of a scalar type that is only ever
+	// read is not declared.  Instead, its value is defined as the macro
+	// "VAR_<name>" where the declaration would have been, and the calls use the
+	// macro, so that long values such as strings do not clutter them.  The
+	// libFuzzer harness still declares every free variable.
+	#[allow(dead_code)]
+	pub fn set_named_values(&mut self, named: bool) {
+		self.named_values = named;
+	}
+
+	// The macro that stands for the value of the variable with the given C
+	// name, if there is one; see set_named_values().
+	pub fn value_macro(&self, cname: &str) -> Option<String> {
+		if !self.named_values {
+			return None;
+		}
+		let sym = match self.cnamelookup(cname) {
+			Some(s) if self.freevars.contains(&s.name) => s,
+			_ => return None,
+		};
+		match *sym.typ.unqualified() {
+			Type::Builtin(_) | Type::Enum(_, _) | Type::Pointer(_) => (),
+			_ => return None,
+		};
+		// buffers are arrays, and pragmas cannot go around uses.
+		if sym.generator.length().is_some() || !self.suppressed(sym).is_empty() {
+			return None;
+		}
+		// a constructor must be called once, not at every use.
+		if sym.generator.name() == "std:construct" {
+			return None;
+		}
+		if !read_only(&self.statements, &sym.name) {
+			return None;
+		}
+		Some(format!("VAR_{}", cname))
+	}
+
+	// The warnings to turn off around the declaration of the given symbol.
+	fn suppressed(&self, sym: &Symbol) -> Vec<String> {
+		if !self.suppress_warnings {
+			return Vec::new();
+		}
+		sym.generator.warnings()
+	}
+
+	// Whether the variable with the given C name is a compound literal where
+	// it is used, instead of being declared up front.
+	pub fn is_temporary(&self, cname: &str) -> bool {
+		if !self.gnu_extensions {
+			return false;
+		}
+		let sym = match self.cnamelookup(cname) {
+			Some(s) if self.freevars.contains(&s.name) => s,
+			_ => return false,
+		};
+		match sym.typ {
+			Type::Struct(_, ref flds, _) if !flds.is_empty() => (),
+			_ => return false,
+		};
+		// pragmas cannot go inside of an expression.
+		if !self.suppressed(sym).is_empty() {
+			return false;
+		}
+		symbol_uses(&self.statements, &sym.name) == (1, 1)
+	}
+
+	// A hash of the values that the last codegen() gave the free variables.
+	pub fn values_hash(&self) -> u64 {
+		let mut text = String::new();
+		for &(ref nm, ref val) in self.emitted.borrow().iter() {
+			text.push_str(&format!("{}={}\n", nm, val));
+		}
+		util::fnv1a(text.as_bytes())
+	}
+
+	// Writes the code that prints the identifier of the given case, if case
+	// identifiers are on.  The output is flushed immediately, as the case may
+	// well crash before stdout would be flushed otherwise.
+	fn case_id(&self, strm: &mut std::io::Write, case: usize)
+		-> std::io::Result<()> {
+		if !self.case_ids {
+			return Ok(());
+		}
+		try!(writeln!(strm, "\tprintf(\"CASE %zu %s\\n\", (size_t){}, \
+		                     \"{:016x}\");", case, self.values_hash()));
+		writeln!(strm, "\tfflush(stdout);")
+	}
+
+	// Notes the value that the given symbol is about to be declared with, so
+	// that the values of the free variables can be put into a manifest.  This
+	// must come just before the declaration is generated: a random state
+	// draws its value again for the declaration, and gets the same one.
+	pub fn note_declaration(&self, sym: &Symbol) {
+		if !self.freevars.contains(&sym.name) {
+			return;
+		}
+		let value = util::replaying(|| sym.generator.value());
+		self.note_value(sym, value);
+	}
+
+	// Notes the value that was generated for the given symbol, as with
+	// note_declaration().
+	fn note_value(&self, sym: &Symbol, value: String) {
+		if self.freevars.contains(&sym.name) {
+			self.emitted.borrow_mut().push((sym.name.clone(), value));
+		}
+	}
+
+	// The value that the last codegen() gave the free variable with the given
+	// name, as note_declaration() recorded it.
+	pub fn emitted_value(&self, name: &str) -> Option<String> {
+		self.emitted.borrow().iter().rev().find(|e| e.0 == name)
+			.map(|e| e.1.clone())
+	}
+
+	// A JSON object with the case number, the values of the free variables in
+	// the code that was generated last, and whether each value is an extreme
+	// one; see Generator::is_extreme().
+	fn manifest_line(&self, case: usize) -> String {
+		let values: Vec<String> = self.emitted.borrow().iter().map(
+			|&(ref nm, ref val)| format!("\"{}\":\"{}\"", util::json_escape(nm),
+			                             util::json_escape(val))
+		).collect();
+		let extreme: Vec<String> = self.emitted.borrow().iter().map(|e| {
+			let edge = self.symlookup(&e.0).map(|s| s.generator.is_extreme());
+			format!("\"{}\":{}", util::json_escape(&e.0), edge.unwrap_or(false))
+		}).collect();
+		format!("{{\"case\":{},\"values\":{{{}}},\"extreme\":{{{}}}}}", case,
+		        values.join(","), extreme.join(","))
 	}
 
 	pub fn set_generators(&mut self, gens: &Vec<Box<Generator>>) {
@@ -144,6 +1039,12 @@ impl Program {
 		None
 	}
 
+	// Like symlookup, but by the name used in the generated code.  Unlike the
+	// user's names, these are unique.
+	pub fn cnamelookup<'a>(&'a self, cname: &str) -> Option<&'a Symbol> {
+		self.symtab.iter().find(|s| s.cname == cname)
+	}
+
 	// Lookup a function's type in the type table.
 	pub fn funlookup(&self, funcname: &str) -> Option<function::Function> {
 		use std::ops::Deref;
@@ -163,42 +1064,198 @@ impl Program {
 		return None;
 	}
 
-	fn genlookup(&self, ty: &Type, genname: &str) -> Option<Box<Generator>> {
+	fn genlookup(&self, ty: &Type, genname: &str) ->
+		Result<Box<Generator>, String> {
+		// "X weighted" samples X's edge states more often.
+		if genname.ends_with(" weighted") {
+			let inner = &genname[0 .. genname.len()-" weighted".len()];
+			let gen = try!(self.genlookup(ty, inner));
+			return Ok(Box::new(variable::GenWeighted::create(gen)));
+		}
+		// "sample(K)" picks K of the values of an enum.
+		if let Some(i) = genname.find(" sample(") {
+			let arg = &genname[i+" sample(".len() .. genname.len()-1];
+			let k = match arg.parse::<usize>() {
+				Ok(k) if k >= 2 => k,
+				_ => return Err(format!("invalid sample count '{}'; at least 2 \
+				                         values must be sampled", arg)),
+			};
+			return match *ty {
+				Type::Enum(_, _) =>
+					Ok(Box::new(variable::GenEnum::create_sampled(ty, k))),
+				_ => Err(format!("cannot sample values of '{}'; only enums can be \
+				                  sampled", ty.name())),
+			};
+		}
+		// "enum numeric" spells the values of an enum as the DSL gave them.
+		if genname.ends_with(" numeric") {
+			return match *ty {
+				Type::Enum(_, _) =>
+					Ok(Box::new(variable::GenEnum::create_by_value(ty))),
+				_ => Err(format!("'{}' is not an enum; only enumerators can be \
+				                  given as numbers", ty.name())),
+			};
+		}
+		// "cstring ascii" restricts strings to printable characters.
+		if genname.ends_with(" ascii") {
+			let cstring = Type::Pointer(Box::new(Type::Builtin(Native::Character)));
+			return match genname.to_uppercase().as_str() {
+				"CSTRING ASCII" | "STD:CSTRING ASCII" if *ty == cstring =>
+					Ok(Box::new(variable::GenCString::create_printable(ty))),
+				"CSTRING ASCII" | "STD:CSTRING ASCII" =>
+					Err(format!("cannot generate ascii strings for '{}'", ty.name())),
+				_ => Err(format!("only cstring generators can be limited to ascii, \
+				                  not '{}'", genname)),
+			};
+		}
 		#[allow(non_snake_case)]
 		let GENNAME = genname.to_string().to_uppercase();
 		for gen in self.genlist.iter() {
 			if gen.name().to_uppercase() == GENNAME {
-				return Some((*gen).clone());
+				return Ok((*gen).clone());
+			}
+		}
+		// some builtin generators can only be had by name.
+		match GENNAME.as_str() {
+			"NULL" | "STD:NULL" =>
+				return Ok(Box::new(variable::GenNull::create(ty))),
+			"BUFFER" | "STD:BUFFER" =>
+				return Ok(Box::new(variable::GenBuffer::create(ty))),
+			"HEAP" | "STD:HEAP" => return match *ty.unqualified() {
+				Type::Pointer(_) =>
+					Ok(Box::new(variable::GenHeapPointer::create(ty))),
+				_ => Err(format!("cannot allocate a '{}' on the heap; only \
+				                  pointers can point there", ty.name())),
+			},
+			"ARGV" | "STD:ARGV" => return match *ty.unqualified() {
+				Type::Pointer(ref inner) if match *inner.unqualified() {
+					Type::Pointer(_) => true, _ => false,
+				} => Ok(Box::new(variable::GenNullTerminated::create(ty))),
+				_ => Err(format!("cannot terminate '{}' with NULL; only pointers to \
+				                  pointers can be", ty.name())),
+			},
+			"FLAGS" | "STD:FLAGS" => return match *ty {
+				Type::Enum(_, _) => Ok(Box::new(variable::GenFlags::create(ty))),
+				_ => Err(format!("cannot combine flags of '{}'; only enums have \
+				                  flags", ty.name())),
+			},
+			_ => (),
+		};
+		// "values(a, b, ...)" tries exactly the given expressions.
+		if GENNAME.starts_with("VALUES(") && genname.ends_with(")") {
+			let list = &genname["values(".len() .. genname.len()-1];
+			let values = util::split_args(list);
+			if values.is_empty() {
+				return Err(format!("no values given for generator '{}'", genname));
 			}
+			return Ok(Box::new(variable::GenFixedSet::create(ty, values)));
+		}
+		// "construct(f, args...)" gets the value from a call to the API's f.
+		if GENNAME.starts_with("CONSTRUCT(") && genname.ends_with(")") {
+			let list = util::split_args(&genname["construct(".len() ..
+			                                     genname.len()-1]);
+			let (fname, args) = match list.split_first() {
+				Some((f, args)) => (f, args),
+				None => return Err(format!("no constructor given for generator \
+				                            '{}'", genname)),
+			};
+			let func = match self.funlookup(fname) {
+				Some(f) => f,
+				None => return Err(format!("constructor '{}' is not a declared \
+				                            function", fname)),
+			};
+			let nparams = func.parameters.len();
+			if args.len() < nparams || (args.len() > nparams && !func.variadic) {
+				return Err(format!("constructor '{}' takes {} arguments, not {}",
+				                   fname, nparams, args.len()));
+			}
+			if incompatible(&func.retval, ty) {
+				return Err(format!("constructor '{}' returns '{}', not '{}'", fname,
+				                   func.retval.name(), ty.name()));
+			}
+			return Ok(Box::new(variable::GenConstruct::create(ty, fname, args)));
+		}
+		// "dict("file")" tries the strings in the given file.
+		if GENNAME.starts_with("DICT(\"") && genname.ends_with("\")") {
+			let path = &genname["dict(\"".len() .. genname.len()-2];
+			let cstring = Type::Pointer(Box::new(Type::Builtin(Native::Character)));
+			if *ty != cstring {
+				return Err(format!("dictionaries hold strings; cannot use one for \
+				                    '{}'", ty.name()));
+			}
+			return match variable::GenDictString::from_file(ty, path) {
+				Ok(gen) => Ok(Box::new(gen)),
+				Err(e) => Err(format!("cannot read dictionary '{}': {}", path, e)),
+			};
 		}
 		// if we didn't find any in the list, try to create one from the type.
-		Some(variable::generator(ty))
+		match ty.ungeneratable("") {
+			Some((ref path, ref leaf)) if path.is_empty() =>
+				return Err(format!("type '{}' is not generatable", leaf.name())),
+			Some((path, leaf)) =>
+				return Err(format!("field '{}' of type '{}' is not generatable",
+				                   path, leaf.name())),
+			None => (),
+		};
+		if self.is_flags(ty) {
+			return Ok(Box::new(variable::GenFlags::create(ty)));
+		}
+		if self.strict {
+			return variable::generator_strict(ty);
+		}
+		Ok(variable::generator(ty))
+	}
+
+	// Whether the type is an enum that was declared with "enum:flags".
+	fn is_flags(&self, ty: &Type) -> bool {
+		let name = match *ty {
+			Type::Enum(ref nm, _) => nm,
+			_ => return false,
+		};
+		self.declarations.iter().any(|decl| match *decl {
+			Declaration::UDT(DeclType::Flags(ref nm, _)) => nm == name,
+			_ => false,
+		})
 	}
 
 	// Creates an entry in the symtable for every variable in the program.
-	fn populate_symtable(&mut self) {
+	fn populate_symtable(&mut self) -> Result<(),String> {
 		for ref decl in self.declarations.iter() {
 			match **decl {
 				Declaration::Free(ref fvd) => {
 					let ty = type_from_decl(&fvd.ty, &self.typetab);
-					let gen = self.genlookup(&ty, &fvd.genname).unwrap();
-					let sym = Symbol{name: fvd.name.clone(), generator: gen, typ: ty};
+					if try!(self.redeclares_shared(fvd, &ty)) {
+						continue;
+					}
+					let gen = try!(self.genlookup(&ty, &fvd.genname));
+					let sym = Symbol::new(&fvd.name, gen, ty);
 					self.symtab.push(sym);
+					self.freevars.push(fvd.name.clone());
+					if fvd.shared {
+						self.sharedvars.push(fvd.name.clone());
+					}
 				},
 				Declaration::Constrained(ref nm, ref decl) => {
 					let ty = type_from_decl(decl, &self.typetab);
 					let gen = variable::generator_single(&ty);
-					let sym = Symbol{name: nm.clone(), generator: gen, typ: ty};
+					let sym = Symbol::new(&nm, gen, ty);
 					self.symtab.push(sym);
 				},
 				Declaration::Function(ref fqn) => {
 					let ty = type_from_decl(&fqn.retval, &self.typetab);
 					use variable;
-					let gen = Box::new(variable::GenNothing{});
-					let sym = Symbol{name: fqn.name.clone(), generator: gen, typ: ty};
+					let gen = Box::new(variable::GenZero{});
+					let sym = Symbol::new(&fqn.name, gen, ty);
 					self.symtab.push(sym);
 				},
-				Declaration::UDT(_) => (),
+				// like functions, constants are defined outside of the generated
+				// code, so they are not declared as variables either.
+				Declaration::Const(ref nm, _) => {
+					let gen = Box::new(variable::GenZero{});
+					let sym = Symbol::new(nm, gen, Type::Builtin(Native::Integer));
+					self.symtab.push(sym);
+				},
+				Declaration::UDT(_) | Declaration::Link(_) => (),
 			}
 		}
 		for ref stmt in self.ast.iter() {
@@ -207,10 +1264,16 @@ impl Program {
 					match *decl {
 						Declaration::Free(ref fvd) => {
 							let ty = type_from_decl(&fvd.ty, &self.typetab);
-							let gen = self.genlookup(&ty, &fvd.genname).unwrap();
-							let sym = Symbol{name: fvd.name.clone(), generator: gen,
-							                 typ: ty.clone()};
+							if try!(self.redeclares_shared(fvd, &ty)) {
+								continue;
+							}
+							let gen = try!(self.genlookup(&ty, &fvd.genname));
+							let sym = Symbol::new(&fvd.name, gen, ty.clone());
 							self.symtab.push(sym);
+							self.freevars.push(fvd.name.clone());
+							if fvd.shared {
+								self.sharedvars.push(fvd.name.clone());
+							}
 						},
 						Declaration::Constrained(ref nm, ref decltype) => {
 							// The only difference between a constrained variable declaration
@@ -221,17 +1284,68 @@ impl Program {
 							// using a single-state generator.
 							let ty = type_from_decl(&decltype, &self.typetab);
 							let gen = variable::generator_single(&ty);
-							let sym = Symbol{name: nm.clone(), generator: gen,
-							                 typ: ty.clone()};
+							let sym = Symbol::new(&nm, gen, ty.clone());
 							self.symtab.push(sym);
 						},
-						Declaration::Function(_) => (),
-						Declaration::UDT(_) => (),
+						Declaration::Function(_) | Declaration::Const(_, _) => (),
+						Declaration::UDT(_) | Declaration::Link(_) => (),
 					};
 				},
 				_ => (),
 			};
 		}
+		let mut loops: Vec<String> = Vec::new();
+		loop_variables(&self.ast, &mut loops);
+		for nm in loops.into_iter() {
+			let ty = Type::Builtin(Native::Usize);
+			let sym = Symbol::new(&nm, variable::generator_single(&ty), ty);
+			self.symtab.push(sym);
+			self.loopvars.push(nm);
+		}
+		self.allocate_names();
+		Ok(())
+	}
+
+	// True if 'fvd' is another declaration of a shared variable that we already
+	// have a symbol for.  Such declarations must agree with the first one.
+	fn redeclares_shared(&self, fvd: &FreeVarDecl, ty: &Type) ->
+		Result<bool,String> {
+		let sym = match self.symlookup(&fvd.name) {
+			None => return Ok(false),
+			Some(sym) => sym,
+		};
+		if !fvd.shared {
+			return Ok(false);
+		}
+		if !self.sharedvars.contains(&fvd.name) {
+			return Err(format!("shared variable '{}' was already declared as a \
+			                    non-shared variable", fvd.name));
+		}
+		if sym.typ != *ty {
+			return Err(format!("shared variable '{}' is redeclared as '{}'; it \
+			                    was declared as '{}'", fvd.name, ty.name(),
+			                   sym.typ.name()));
+		}
+		Ok(true)
+	}
+
+	// Gives every symbol a unique, valid C identifier to use in generated code.
+	// Functions are external and must keep their names, so they are reserved
+	// first; variables then get a sanitized variant of their name.
+	fn allocate_names(&mut self) {
+		let mut taken: Vec<String> = Vec::new();
+		for sym in self.symtab.iter() {
+			if sym.generator.n_state() == 0 {
+				taken.push(sym.name.clone());
+			}
+		}
+		for sym in self.symtab.iter_mut() {
+			if sym.generator.n_state() == 0 {
+				continue;
+			}
+			sym.cname = util::c_identifier(&sym.name, &taken);
+			taken.push(sym.cname.clone());
+		}
 	}
 
 	// Ensures there is a type for every declaration.
@@ -244,14 +1358,16 @@ impl Program {
 				},
 				Declaration::Constrained(_, _) => (),
 				Declaration::Free(_) => (),
+				Declaration::Const(_, _) | Declaration::Link(_) => (),
 				Declaration::Function(ref fdecl) => {
 					let rtype = type_from_decl(&fdecl.retval, &self.typetab);
 					let params: Vec<Type> = fdecl.parameters.iter().map(
 						|pm| type_from_decl(&pm, &self.typetab)
 					).collect();
-					self.typetab.push(Type::Function(Box::new(
-						function::Function::new(&fdecl.name, &rtype, &params)
-					)));
+					let mut func = function::Function::new(&fdecl.name, &rtype, &params);
+					func.variadic = fdecl.variadic;
+					func.bool_return = fdecl.bool_return;
+					self.typetab.push(Type::Function(Box::new(func)));
 				},
 			};
 		}
@@ -274,10 +1390,14 @@ impl Program {
 								let atype = type_from_decl(&ag, &self.typetab);
 								args.push(atype);
 							}
-							let func = function::Function::new(&fdecl.name, &rtype, &args);
+							let mut func = function::Function::new(&fdecl.name, &rtype,
+							                                       &args);
+							func.variadic = fdecl.variadic;
+							func.bool_return = fdecl.bool_return;
 							self.typetab.push(Type::Function(Box::new(func.clone())));
 						},
 						Declaration::UDT(_) => (), // right?
+						Declaration::Const(_, _) | Declaration::Link(_) => (),
 					}
 				},
 				_ => (),
@@ -294,6 +1414,10 @@ impl Program {
 				let v = self.symlookup(nm).unwrap();
 				expr::Expression::Basic(*sop, v.clone())
 			},
+			Expr::VarOps(ref ops, ref nm) => {
+				let v = self.symlookup(nm).unwrap();
+				expr::Expression::Ops(ops.clone(), v.clone())
+			},
 			Expr::IConst(iger) => {
 				use std::str::FromStr;
 				expr::Expression::IConstant(i64::from_str(&iger).unwrap())
@@ -309,7 +1433,9 @@ impl Program {
 				};
 				// Make sure the arity matches how the function is defined.
 				// TODO: should this be a regular error (not an assert?)
-				assert_eq!(functype.parameters.len(), arglist.len());
+				assert!(functype.parameters.len() == arglist.len() ||
+				        (functype.variadic &&
+				         functype.parameters.len() < arglist.len()));
 				use std::ops::Deref;
 				let args: Vec<expr::Expression> = arglist.deref().iter().map(
 					|a| self.expr_to_expr(a.clone())
@@ -326,6 +1452,10 @@ impl Program {
 				let var = self.symlookup(&symname).unwrap();
 				expr::Expression::Field(var.clone(), fld)
 			},
+			Expr::ArrowField(symname, fld) => {
+				let var = self.symlookup(&symname).unwrap();
+				expr::Expression::ArrowField(var.clone(), fld)
+			},
 		}
 	}
 
@@ -334,6 +1464,28 @@ impl Program {
 	// post-analysis result that references our internal data structures.  This
 	// does the analysis to turn the former into the latter.
 	fn stmt_to_stmt(&self, s: Stmt) -> Option<stmt::Statement> {
+		// the preconditions of the functions that the statement calls guard it,
+		// as well as any that were given with the statement itself.
+		let mut guards = self.call_preconditions(&s);
+		let s = match s {
+			Stmt::Guarded(conds, inner) => {
+				guards.extend(conds);
+				*inner
+			},
+			s => s,
+		};
+		let resolved = match self.resolve_stmt(s) {
+			None => return None,
+			Some(st) => st,
+		};
+		if guards.is_empty() {
+			return Some(resolved);
+		}
+		let conds = guards.iter().map(|c| self.c_condition(c)).collect();
+		Some(stmt::Statement::Guarded(conds, Box::new(resolved)))
+	}
+
+	fn resolve_stmt(&self, s: Stmt) -> Option<stmt::Statement> {
 		match s {
 			Stmt::Basic(ref expr) => {
 				match *expr {
@@ -341,6 +1493,10 @@ impl Program {
 						println!("Statement with no effect: '{}{}'", op.to_string(), nm);
 						None
 					},
+					Expr::VarOps(_, ref nm) => {
+						println!("Statement with no effect on '{}'", nm);
+						None
+					},
 					Expr::IConst(ref i) => panic!("iconst {} cannot be a statement!", i),
 					Expr::FConst(ref f) => panic!("fconst {} cannot be a statement!", f),
 					Expr::Call(_, _) => {
@@ -355,22 +1511,27 @@ impl Program {
 						println!("Statement with no effect: '{}.{}'", sym, fld);
 						None
 					},
+					Expr::ArrowField(ref sym, ref fld) => {
+						println!("Statement with no effect: '{}->{}'", sym, fld);
+						None
+					},
 				}
 			},
 			Stmt::Declaration(ref decltype) => {
 				match *decltype {
 					Declaration::Constrained(ref nm, _) => {
 						let sym = self.symlookup(&nm).unwrap();
-						Some(stmt::Statement::VariableDeclaration(sym.name.clone(),
+						Some(stmt::Statement::VariableDeclaration(sym.codegen_name(),
 						                                          sym.typ.clone()))
 					},
 					Declaration::Free(ref fvd) => {
 						let sym = self.symlookup(&fvd.name).unwrap();
-						Some(stmt::Statement::VariableDeclaration(sym.name.clone(),
+						Some(stmt::Statement::VariableDeclaration(sym.codegen_name(),
 						                                          sym.typ.clone()))
 					},
 					Declaration::Function(_) => None, // right?
 					Declaration::UDT(_) => None, // right ?
+					Declaration::Const(_, _) | Declaration::Link(_) => None,
 				}
 			},
 			Stmt::Assignment(ref lhs, ref rhs) => {
@@ -409,8 +1570,92 @@ impl Program {
 				}
 				Some(stmt::Statement::While(self.expr_to_expr(expr.clone()),
 				                            Box::new(statements)))
+			},
+			Stmt::Repeat(count, ref var, ref stmts) => {
+				use std::ops::Deref;
+				let mut statements: Vec<stmt::Statement> = vec![];
+				for s in stmts.deref().iter() {
+					let stopt = match self.stmt_to_stmt(s.clone()) {
+						None => return None,
+						Some(st) => st,
+					};
+					statements.push(stopt);
+				}
+				// name_loops() has given every loop a variable by now.
+				let nm = var.as_ref().unwrap();
+				let sym = self.symlookup(nm).unwrap();
+				Some(stmt::Statement::Repeat(count, sym.codegen_name(),
+				                             Box::new(statements)))
+			},
+			Stmt::Guarded(_, _) => unreachable!(), // handled in stmt_to_stmt
+		}
+	}
+
+	// The preconditions of the functions called by the given statement, not
+	// counting those in the bodies of loops and conditionals.
+	fn call_preconditions(&self, s: &Stmt) -> Vec<String> {
+		let exprs: Vec<&Expr> = match *s {
+			Stmt::Basic(ref ex) | Stmt::Verify(ref ex) | Stmt::Constraint(ref ex) |
+			Stmt::If(ref ex, _) | Stmt::While(ref ex, _) => vec![ex],
+			Stmt::Assignment(ref lhs, ref rhs) => vec![lhs, rhs],
+			Stmt::Guarded(_, ref inner) => return self.call_preconditions(inner),
+			Stmt::Declaration(_) | Stmt::Repeat(_, _, _) => vec![],
+		};
+		let mut calls: Vec<String> = Vec::new();
+		for ex in exprs.into_iter() {
+			called_functions(ex, &mut calls);
+		}
+		let mut rv: Vec<String> = Vec::new();
+		for decl in self.declarations.iter() {
+			match *decl {
+				Declaration::Function(ref f) if calls.contains(&f.name) => {
+					for cond in f.preconditions.iter() {
+						if !rv.contains(cond) {
+							rv.push(cond.clone());
+						}
+					}
+				},
+				_ => (),
+			};
+		}
+		rv
+	}
+
+	// Renames the variables in the C condition 'cond' to their names in the
+	// generated code.  Other identifiers, such as macros, are left alone, as
+	// are the names of fields.
+	fn c_condition(&self, cond: &str) -> String {
+		let mut rv = String::with_capacity(cond.len());
+		let mut word = String::new();
+		let mut quote: Option<char> = None;
+		let mut prev: Option<char> = None;
+		for c in cond.chars().chain(Some(' ')) {
+			if quote.is_none() && (c.is_alphanumeric() || c == '_') {
+				word.push(c);
+				continue;
+			}
+			if !word.is_empty() {
+				let field = rv.ends_with('.') || rv.ends_with("->");
+				let starts_alpha = word.chars().next().map_or(false,
+					|w| w.is_alphabetic() || w == '_');
+				match self.symlookup(&word) {
+					Some(ref sym) if starts_alpha && !field &&
+					                 sym.generator.n_state() > 0 =>
+						rv.push_str(sym.codegen_name().as_str()),
+					_ => rv.push_str(&word),
+				};
+				word.clear();
 			}
+			match (quote, c) {
+				(Some(q), _) if c == q && prev != Some('\\') => quote = None,
+				(None, '"') | (None, '\'') => quote = Some(c),
+				_ => (),
+			};
+			rv.push(c);
+			prev = Some(c);
 		}
+		rv.pop(); // the ' ' we added to flush the last word.
+		rv
 	}
 
 	// Resolves references and the like in the AST.
@@ -422,14 +1667,17 @@ impl Program {
 			// hack: we want to insert functions into our symtable so that we can
 			// lookup the function's type from its name.  but we don't want to
 			// declare functions as variables.
-			// function entries in the symtable have bogus GenNothing generators
-			// (because a generator makes no sense for a function call; it generates
-			// whatever the call does!), so use that as a hack to see if it's
-			// something we should be creating.
-			if var.generator.name() == "std:nothing" {
+			// function entries in the symtable have GenZero generators (because a
+			// generator makes no sense for a function call; it generates whatever
+			// the call does!), so use that as a hack to see if it's something we
+			// should be creating.  Constants have them too.
+			if var.generator.n_state() == 0 {
+				continue;
+			}
+			if self.loopvars.contains(&var.name) {
 				continue;
 			}
-			let s = stmt::Statement::VariableDeclaration(var.name.clone(),
+			let s = stmt::Statement::VariableDeclaration(var.codegen_name(),
 			                                             var.typ.clone());
 			stmts.push(s);
 		}
@@ -448,42 +1696,664 @@ impl Program {
 		self.ast.clear();
 	}
 
+	// There are no values of type void: a variable cannot be void, and the
+	// result of a void function cannot be assigned to anything.
+	fn check_void(&self, stmts: &Vec<Stmt>) -> Result<(),String> {
+		let void = Type::Builtin(Native::Void);
+		for stmt in stmts.iter() {
+			match *stmt {
+				Stmt::Declaration(Declaration::Constrained(ref nm, ref decl)) => {
+					if type_from_decl(decl, &self.typetab) == void {
+						return Err(format!("variable '{}' cannot be void", nm));
+					}
+				},
+				Stmt::Declaration(Declaration::Free(ref fvd)) => {
+					if type_from_decl(&fvd.ty, &self.typetab) == void {
+						return Err(format!("cannot generate a value for 'void' ('{}'); \
+						                    did you mean 'pointer void'?", fvd.name));
+					}
+				},
+				Stmt::Assignment(_, Expr::Call(ref fname, _)) => {
+					match self.funlookup(fname) {
+						Some(ref f) if f.retval == void =>
+							return Err(format!("function '{}' returns void; its result \
+							                    cannot be assigned", fname)),
+						_ => (),
+					};
+				},
+				Stmt::If(_, ref body) | Stmt::While(_, ref body) |
+				Stmt::Repeat(_, _, ref body) => {
+					try!(self.check_void(body));
+				},
+				Stmt::Guarded(_, ref inner) => {
+					try!(self.check_void(&vec![(**inner).clone()]));
+				},
+				_ => (),
+			};
+		}
+		Ok(())
+	}
+
+	// Gives every "repeat" loop without a variable a unique one, and makes sure
+	// the loop variables which were named do not clash with anything.
+	fn name_loops(&mut self) -> Result<(),String> {
+		let mut taken: Vec<String> = Vec::new();
+		let decls = self.declarations.iter().chain(self.ast.iter().filter_map(
+			|s| match *s { Stmt::Declaration(ref d) => Some(d), _ => None }
+		));
+		for decl in decls {
+			match *decl {
+				Declaration::Constrained(ref nm, _) => taken.push(nm.clone()),
+				Declaration::Free(ref fvd) => taken.push(fvd.name.clone()),
+				Declaration::Function(ref f) => taken.push(f.name.clone()),
+				Declaration::Const(ref nm, _) => taken.push(nm.clone()),
+				Declaration::UDT(_) | Declaration::Link(_) => (),
+			};
+		}
+		name_loops(&mut self.ast, &mut taken)
+	}
+
+	// Gives the enumerators that refer to constants their values.
+	fn resolve_constants(&mut self) -> Result<(),String> {
+		let consts: Vec<(String, i64)> = self.declarations.iter().filter_map(
+			|decl| match *decl {
+				Declaration::Const(ref nm, value) => Some((nm.clone(), value)),
+				_ => None,
+			}
+		).collect();
+		for decl in self.declarations.iter_mut() {
+			let (enm, values) = match *decl {
+				Declaration::UDT(DeclType::Enum(ref enm, ref mut values)) |
+				Declaration::UDT(DeclType::Flags(ref enm, ref mut values)) =>
+					(enm, values),
+				_ => continue,
+			};
+			for &mut (ref name, ref mut value, ref spelling) in values.iter_mut() {
+				let (cnm, off) = match util::constant_reference(spelling) {
+					None => continue,
+					Some(reference) => reference,
+				};
+				*value = match consts.iter().find(|c| c.0 == cnm) {
+					Some(c) => c.1 + off,
+					None => return Err(format!("enumerator '{}' of '{}' refers to \
+					                            unknown constant '{}'", name, enm,
+					                           cnm)),
+				};
+			}
+		}
+		Ok(())
+	}
+
+	// Calls to variadic functions which give only the fixed arguments get a
+	// generated int and a generated string for the variadic part, as free
+	// variables of their own.
+	fn expand_variadic_calls(&mut self) {
+		let variadic: Vec<(String, usize)> = self.declarations.iter().filter_map(
+			|decl| match *decl {
+				Declaration::Function(ref f) if f.variadic =>
+					Some((f.name.clone(), f.parameters.len())),
+				_ => None,
+			}
+		).collect();
+		if variadic.is_empty() {
+			return;
+		}
+		let mut extra: Vec<FreeVarDecl> = Vec::new();
+		variadic_args(&mut self.ast, &variadic, &mut extra);
+		for fvd in extra.into_iter() {
+			self.declarations.push(Declaration::Free(fvd));
+		}
+	}
+
+	// A struct that holds itself by value, directly or through other structs,
+	// would be infinitely large.
+	fn check_recursion(&self) -> Result<(),String> {
+		match value_cycle(&self.declarations) {
+			Some(cycle) => Err(format!("struct '{}' contains itself by value ({}); \
+			                            hold one of them through a pointer",
+			                           cycle[0], cycle.join(" -> "))),
+			None => Ok(()),
+		}
+	}
+
+	// Generators need at least one value to work with, so we reject types that
+	// have none.
+	fn check_types(&self) -> Result<(),String> {
+		for ty in self.typetab.iter() {
+			match *ty {
+				Type::Enum(ref nm, ref values) if values.is_empty() =>
+					return Err(format!("enum '{}' has no values", nm)),
+				_ => (),
+			};
+		}
+		// functions can only write their outputs through pointers.
+		for decl in self.declarations.iter() {
+			let f = match *decl {
+				Declaration::Function(ref f) => f,
+				_ => continue,
+			};
+			for (i, (pm, mode)) in f.parameters.iter().zip(f.modes.iter())
+				.enumerate() {
+				let ptr = match *pm {
+					DeclType::Basic(ref ty) => match *ty.unqualified() {
+						Type::Pointer(_) => true,
+						_ => false,
+					},
+					_ => false,
+				};
+				if *mode != ParamMode::In && !ptr {
+					return Err(format!("parameter {} of '{}' is an output, but not a \
+					                    pointer", i+1, f.name));
+				}
+			}
+			// only integers can stand for truth values.
+			let integral = match f.retval {
+				DeclType::Basic(Type::Builtin(ref nat)) => match *nat {
+					Native::F32 | Native::F64 | Native::Void => false,
+					_ => true,
+				},
+				_ => false,
+			};
+			if f.bool_return && !integral {
+				return Err(format!("'{}' is marked returns:bool, but does not \
+				                    return an integer", f.name));
+			}
+		}
+		Ok(())
+	}
+
+	// The length of a variable is only known if a buffer generator made it.
+	// Likewise, only (pointers to) structs with the named field have fields.
+	fn check_lengths(&self, stmts: &Vec<Stmt>) -> Result<(),String> {
+		for stmt in stmts.iter() {
+			match *stmt {
+				Stmt::Basic(ref ex) | Stmt::Verify(ref ex) |
+				Stmt::Constraint(ref ex) => try!(self.check_lengths_expr(ex)),
+				Stmt::Assignment(ref lhs, ref rhs) => {
+					try!(self.check_lengths_expr(lhs));
+					try!(self.check_lengths_expr(rhs));
+				},
+				Stmt::If(ref ex, ref body) | Stmt::While(ref ex, ref body) => {
+					try!(self.check_lengths_expr(ex));
+					try!(self.check_lengths(body));
+				},
+				Stmt::Repeat(_, _, ref body) => try!(self.check_lengths(body)),
+				Stmt::Guarded(_, ref inner) =>
+					try!(self.check_lengths(&vec![(**inner).clone()])),
+				Stmt::Declaration(_) => (),
+			};
+		}
+		Ok(())
+	}
+
+	fn check_lengths_expr(&self, ex: &Expr) -> Result<(),String> {
+		match *ex {
+			Expr::VarRef(UOp::LengthOf, ref nm) => match self.symlookup(nm) {
+				None => Err(format!("unknown variable '{}'", nm)),
+				Some(sym) if sym.generator.length().is_none() =>
+					Err(format!("'{}' is not a buffer (gen:buffer); it has no \
+					             length", nm)),
+				Some(_) => Ok(()),
+			},
+			Expr::VarRef(UOp::Index(idx), ref nm) |
+			Expr::VarRef(UOp::AddressOfIndex(idx), ref nm) => {
+				let sym = match self.symlookup(nm) {
+					None => return Err(format!("unknown variable '{}'", nm)),
+					Some(sym) => sym,
+				};
+				match *sym.typ.unqualified() {
+					Type::Pointer(_) => (),
+					_ => return Err(format!("'{}' is not a pointer; it cannot be \
+					                         indexed", nm)),
+				};
+				// The length of a buffer depends on its state.  An index past
+				// even the longest one is always wrong.
+				if sym.generator.length().is_none() {
+					return Ok(());
+				}
+				let mut gen = sym.generator.clone_box();
+				let longest = (0..gen.n_state()).map(|st| {
+					gen.seek(st);
+					gen.length().unwrap()
+				}).max().unwrap_or(0);
+				if idx >= longest {
+					return Err(format!("index {} is out of bounds of '{}'; it has at \
+					                    most {} elements", idx, nm, longest));
+				}
+				Ok(())
+			},
+			Expr::Field(ref nm, ref fld) | Expr::ArrowField(ref nm, ref fld) => {
+				let sym = match self.symlookup(nm) {
+					None => return Err(format!("unknown variable '{}'", nm)),
+					Some(sym) => sym,
+				};
+				let strct = match (ex, sym.typ.unqualified()) {
+					(&Expr::ArrowField(_, _), &Type::Pointer(ref inner)) =>
+						(**inner).clone(),
+					(&Expr::ArrowField(_, _), _) =>
+						return Err(format!("'{}' is not a pointer; use '{}.{}'", nm, nm,
+						                   fld)),
+					(_, &Type::Pointer(_)) =>
+						return Err(format!("'{}' is a pointer; use '{}->{}'", nm, nm,
+						                   fld)),
+					(_, ty) => ty.clone(),
+				};
+				match strct {
+					Type::Struct(ref snm, _, _) if strct.field_type(fld).is_none() =>
+						Err(format!("struct '{}' has no field '{}'", snm, fld)),
+					Type::Struct(_, _, _) => Ok(()),
+					_ => Err(format!("'{}' is not a struct; it has no field '{}'", nm,
+					                 fld)),
+				}
+			},
+			Expr::Call(ref fname, ref args) => {
+				for arg in args.iter() {
+					try!(self.check_lengths_expr(arg));
+				}
+				self.check_arguments(fname, args)
+			},
+			Expr::Compound(ref lhs, _, ref rhs) => {
+				try!(self.check_lengths_expr(lhs));
+				self.check_lengths_expr(rhs)
+			},
+			// the innermost op is the one applied to the variable itself.
+			Expr::VarOps(ref ops, ref nm) => {
+				let inner = Expr::VarRef(*ops.last().unwrap(), nm.clone());
+				self.check_lengths_expr(&inner)
+			},
+			_ => Ok(()),
+		}
+	}
+
+	// Checks that a call gives the function as many arguments as it takes, and
+	// that variables given as arguments are not obviously of the wrong type,
+	// such as a pointer for an integer.
+	fn check_arguments(&self, fname: &str, args: &Vec<Expr>)
+		-> Result<(),String> {
+		let f = match self.typetab.iter().filter_map(|ty| match *ty {
+			Type::Function(ref f) if f.name == fname => Some(f),
+			_ => None,
+		}).next() {
+			None => return Ok(()),
+			Some(f) => f,
+		};
+		let n = f.parameters.len();
+		let plural = if n == 1 { "" } else { "s" };
+		if f.variadic && args.len() < n {
+			return Err(format!("'{}' takes at least {} argument{}, but {} were \
+			                    given", fname, n, plural, args.len()));
+		}
+		if !f.variadic && args.len() != n {
+			return Err(format!("'{}' takes {} argument{}, but {} were given",
+			                   fname, n, plural, args.len()));
+		}
+		for (i, (arg, param)) in args.iter().zip(f.parameters.iter()).enumerate() {
+			let argty = match *arg {
+				Expr::VarRef(UOp::None, ref nm) => match self.symlookup(nm) {
+					Some(sym) => sym.typ.clone(),
+					None => continue,
+				},
+				Expr::VarRef(UOp::AddressOf, ref nm) => match self.symlookup(nm) {
+					Some(sym) => Type::Pointer(Box::new(sym.typ.clone())),
+					None => continue,
+				},
+				_ => continue,
+			};
+			if incompatible(&argty, param) {
+				return Err(format!("argument {} of '{}' is a '{}', but '{}' takes a \
+				                    '{}'", i+1, fname, argty.name(), fname,
+				                   param.name()));
+			}
+		}
+		Ok(())
+	}
+
 	pub fn analyze(&mut self) -> Result<(),String> {
+		try!(self.resolve_constants());
+		try!(self.check_recursion());
 		self.populate_typetable();
-		self.populate_symtable();
+		try!(self.check_types());
+		let decls: Vec<Stmt> = self.declarations.iter().map(
+			|d| Stmt::Declaration(d.clone())
+		).collect();
+		try!(self.check_void(&decls));
+		try!(self.check_void(&self.ast));
+		try!(self.name_loops());
+		self.expand_variadic_calls();
+		try!(self.populate_symtable());
+		try!(self.check_lengths(&self.ast));
 		self.ast_resolve();
 		self.genlist.clear();
+		for nm in self.single_state_variables() {
+			self.warnings.push(format!("'{}' has a single state, so it is the \
+			                            same in every case", nm));
+		}
 		Ok(())
 	}
 
 	pub fn prologue(&self, strm: &mut std::io::Write, headers: &Vec<&str>) ->
+		std::io::Result<()> {
+		try!(self.preamble(strm, headers));
+		try!(writeln!(strm, "int main() {{"));
+		return Ok(());
+	}
+
+	// Everything that goes before the code of the cases.
+	fn preamble(&self, strm: &mut std::io::Write, headers: &Vec<&str>) ->
 		std::io::Result<()> {
 		try!(writeln!(strm, "#define _POSIX_C_SOURCE 201212L"));
 		try!(writeln!(strm, "#define _GNU_SOURCE 1"));
 		for h in headers.iter() {
 			try!(writeln!(strm, "#include <{}>", h));
 		}
+		try!(self.needed_headers(strm, headers));
 		try!(write!(strm, "\n"));
-		try!(writeln!(strm, "int main() {{"));
-		return Ok(());
+		try!(self.type_definitions(strm));
+		self.offset_checks(strm)
 	}
 
-	pub fn epilogue(&self, strm: &mut std::io::Write) -> std::io::Result<()> {
-		try!(writeln!(strm, "\n\treturn 0;\n}}"));
-		return Ok(());
+	// Defines the structs and enums of the DSL, if set_define_types() asked for
+	// it.  Every struct is declared up front, so that pointers to structs work
+	// in any order; types that are used by value are defined first.  Structs
+	// without fields are opaque, so they are only declared.
+	fn type_definitions(&self, strm: &mut std::io::Write) ->
+		std::io::Result<()> {
+		if !self.define_types {
+			return Ok(());
+		}
+		// the headers may well define the constants already.
+		for decl in self.declarations.iter() {
+			if let Declaration::Const(ref nm, value) = *decl {
+				try!(writeln!(strm, "#ifndef {}\n#define {} {}\n#endif", nm, nm,
+				              value));
+			}
+		}
+		let udts: Vec<&DeclType> = self.declarations.iter().filter_map(
+			|decl| match *decl {
+				Declaration::UDT(ref udt) => Some(udt),
+				_ => None,
+			}
+		).collect();
+		for udt in udts.iter() {
+			match **udt {
+				DeclType::Struct(ref nm, _, _) =>
+					try!(writeln!(strm, "struct {};", nm)),
+				_ => (),
+			};
+		}
+		let mut defined: Vec<String> = Vec::new();
+		for udt in udts.iter() {
+			try!(define_type(udt, &udts, &mut defined, strm));
+		}
+		write!(strm, "\n")
+	}
+
+	// Includes what call logging, case identifiers and booleans need, unless
+	// the user already did.
+	fn needed_headers(&self, strm: &mut std::io::Write, headers: &Vec<&str>) ->
+		std::io::Result<()> {
+		let mut needed: Vec<&str> = Vec::new();
+		if self.typetab.iter().any(mentions_bool) {
+			needed.push("stdbool.h");
+		}
+		if self.symtab.iter().any(|s| s.generator.name() == "std:heap") {
+			needed.push("stdlib.h");
+		}
+		if self.log_calls {
+			needed.push("errno.h");
+		}
+		if self.log_calls || self.case_ids {
+			needed.push("stdio.h");
+		}
+		for h in needed.iter() {
+			if !headers.contains(h) {
+				try!(writeln!(strm, "#include <{}>", h));
+			}
+		}
+		Ok(())
+	}
+
+	// Emits a compile-time check for every struct field declared with an
+	// expected offset, so that a DSL struct which does not match the real
+	// header fails to build instead of silently generating bad values.
+	pub fn offset_checks(&self, strm: &mut std::io::Write) ->
+		std::io::Result<()> {
+		let mut any = false;
+		for decl in self.declarations.iter() {
+			let (nm, fields) = match *decl {
+				Declaration::UDT(DeclType::Struct(ref nm, ref fields, _)) =>
+					(nm, fields),
+				_ => continue,
+			};
+			// the fields of anonymous members are at offsets in this struct, too.
+			let mut flds: Vec<&UDTDecl> = fields.iter().collect();
+			let mut i = 0;
+			while i < flds.len() {
+				match flds[i].ty {
+					DeclType::Struct(_, ref inner, _) if flds[i].name.is_empty() =>
+						flds.extend(inner.iter()),
+					_ => (),
+				};
+				i += 1;
+			}
+			for fld in flds.into_iter() {
+				if let Some(off) = fld.offset {
+					if !any {
+						try!(writeln!(strm, "#include <stddef.h>"));
+						any = true;
+					}
+					try!(writeln!(strm, "_Static_assert(offsetof(struct {}, {}) == {}, \
+					                     \"offset of {}.{}\");", nm, fld.name, off, nm,
+					              fld.name));
+				}
+			}
+		}
+		if any {
+			try!(write!(strm, "\n"));
+		}
+		Ok(())
+	}
+
+	pub fn epilogue<S: stmt::CodeSink + ?Sized>(&self, sink: &mut S) ->
+		std::io::Result<()> {
+		try!(self.delete_live(sink));
+		try!(self.free_heap(sink));
+		sink.text("\n\treturn 0;\n}\n")
 	}
 
 	pub fn codegen(&self, strm: &mut std::io::Write) ->
 		Result<(),std::io::Error> {
-		use stmt::Code;
-		for stmt in self.statements.iter() {
-			try!(write!(strm, "\t"));
-			try!(stmt.codegen(strm, &self));
-			try!(write!(strm, "\n"));
+		self.codegen_to(strm)
+	}
+
+	// As codegen(), but hands every declaration and statement to 'sink'
+	// separately.
+	pub fn codegen_to<S: stmt::CodeSink + ?Sized>(&self, sink: &mut S) ->
+		Result<(),std::io::Error> {
+		use stmt::{Code, Statement};
+		self.ncalls.set(0);
+		self.emitted.borrow_mut().clear();
+		for st in self.statements.iter() {
+			if let Statement::VariableDeclaration(ref nm, _) = *st {
+				if let Some(mac) = self.value_macro(nm) {
+					let sym = self.cnamelookup(nm).unwrap();
+					let value = sym.generator.value();
+					self.note_value(sym, value.clone());
+					try!(sink.text(&format!("#define {} {}\n", mac, value)));
+					continue;
+				}
+			}
+			let mut code: Vec<u8> = Vec::new();
+			try!(st.codegen(&mut code, &self));
+			let code = String::from_utf8_lossy(&code);
+			// temporaries are declared where they are used.
+			if code.is_empty() {
+				continue;
+			}
+			let warnings = match *st {
+				Statement::VariableDeclaration(ref nm, _) =>
+					self.cnamelookup(nm).map(|s| self.suppressed(s))
+						.unwrap_or(Vec::new()),
+				_ => Vec::new(),
+			};
+			if !warnings.is_empty() {
+				try!(sink.text("#pragma GCC diagnostic push\n"));
+				for w in warnings.iter() {
+					try!(sink.text(&format!("#pragma GCC diagnostic ignored \"{}\"\n",
+					                        w)));
+				}
+			}
+			try!(match *st {
+				Statement::VariableDeclaration(_, _) => sink.declaration(&code),
+				_ => sink.statement(&code),
+			});
+			if !warnings.is_empty() {
+				try!(sink.text("#pragma GCC diagnostic pop\n"));
+			}
+		}
+		Ok(())
+	}
+
+	// Writes a Makefile fragment that builds each of the given generated
+	// sources into a program of the same name, without the ".c".  The include
+	// directories go into CFLAGS, and the libraries of the DSL's "link"
+	// declarations into LDLIBS.
+	#[allow(dead_code)]
+	pub fn emit_build(&self, strm: &mut std::io::Write, sources: &Vec<&str>,
+	                  include_dirs: &Vec<&str>) -> std::io::Result<()> {
+		let links: Vec<&String> = self.declarations.iter().filter_map(
+			|decl| match *decl {
+				Declaration::Link(ref lib) => Some(lib),
+				_ => None,
+			}
+		).collect();
+		let program = |src: &str| -> String {
+			if src.ends_with(".c") {
+				src[..src.len()-2].to_string()
+			} else {
+				format!("{}.bin", src)
+			}
+		};
+		for dir in include_dirs.iter() {
+			try!(writeln!(strm, "CFLAGS += -I{}", dir));
+		}
+		for lib in links.iter() {
+			try!(writeln!(strm, "LDLIBS += -l{}", lib));
+		}
+		let programs: Vec<String> = sources.iter().map(|s| program(s)).collect();
+		try!(writeln!(strm, "all: {}", programs.join(" ")));
+		for (src, prog) in sources.iter().zip(programs.iter()) {
+			try!(writeln!(strm, "\n{}: {}", prog, src));
+			try!(writeln!(strm, "\t$(CC) $(CFLAGS) -o $@ {} $(LDLIBS)", src));
 		}
 		Ok(())
 	}
 
+	// Emits a libFuzzer harness instead of a program per case.  The input bytes
+	// are read as a case number, which is decoded like seek() does: every free
+	// variable gets a switch over the states of its generator.  The values of
+	// every state are chosen now, so random values are fixed in the harness.
+	// Constraints return early instead of exiting, as the harness is called
+	// repeatedly from the same process.  A generator with more than
+	// MAX_HARNESS_STATES states, or more cases than a uint64_t can number, is
+	// an InvalidInput error.
+	#[allow(dead_code)]
+	pub fn codegen_libfuzzer(&self, headers: &Vec<&str>,
+	                         strm: &mut std::io::Write) -> std::io::Result<()> {
+		let mut code: Vec<u8> = Vec::new();
+		try!(self.libfuzzer_harness(headers, &mut code));
+		strm.write_all(util::indent_c(&String::from_utf8_lossy(&code)).as_bytes())
+	}
+
+	fn libfuzzer_harness(&self, headers: &Vec<&str>,
+	                     strm: &mut std::io::Write) -> std::io::Result<()> {
+		use stmt::{Code, Statement};
+		// the states of the free variables are chosen up front, so there are no
+		// temporaries, and every variable is declared.
+		if self.gnu_extensions || self.named_values {
+			let mut plain = self.clone();
+			plain.gnu_extensions = false;
+			plain.named_values = false;
+			return plain.libfuzzer_harness(headers, strm);
+		}
+		let invalid = |msg: String| {
+			Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg))
+		};
+		let mut ncases: Option<u64> = Some(1);
+		for sym in self.symtab.iter()
+			.filter(|s| self.freevars.contains(&s.name)) {
+			let n = sym.generator.n_state();
+			if n > MAX_HARNESS_STATES {
+				return invalid(format!("'{}' has too many states for a libFuzzer \
+				                        harness", sym.name));
+			}
+			ncases = ncases.and_then(|c| c.checked_mul(n as u64));
+		}
+		let ncases = match ncases {
+			None => return invalid("too many cases for a libFuzzer harness"
+			                       .to_string()),
+			Some(n) => n,
+		};
+		util::seed_rng(self.seed, 0);
+		self.ncalls.set(0);
+		for h in ["stddef.h", "stdint.h"].iter().chain(headers.iter()) {
+			try!(writeln!(strm, "#include <{}>", h));
+		}
+		try!(self.needed_headers(strm, headers));
+		try!(write!(strm, "\n"));
+		try!(self.offset_checks(strm));
+		try!(writeln!(strm, "int LLVMFuzzerTestOneInput(const uint8_t* data, \
+		                     size_t size) {{"));
+		try!(writeln!(strm, "\tuint64_t idx_ = 0;"));
+		try!(writeln!(strm, "\tfor(size_t i=0; i < size && i < 8; ++i) {{"));
+		try!(writeln!(strm, "\t\tidx_ = (idx_ << 8) | data[i];"));
+		try!(writeln!(strm, "\t}}"));
+		try!(writeln!(strm, "\tidx_ = idx_ % {}ull;", ncases));
+		// the last symbol varies fastest, as in seek().
+		for sym in self.symtab.iter().rev() {
+			if !self.freevars.contains(&sym.name) {
+				continue;
+			}
+			let n = sym.generator.n_state();
+			try!(writeln!(strm, "\tconst size_t st_{} = idx_ % {};",
+			              sym.codegen_name(), n));
+			try!(writeln!(strm, "\tidx_ /= {};", n));
+		}
+		for st in self.statements.iter() {
+			match *st {
+				Statement::VariableDeclaration(ref nm, ref ty) => {
+					let sym = self.cnamelookup(nm).unwrap();
+					if !self.freevars.contains(&sym.name) {
+						try!(writeln!(strm, "\t{}", sym.generator.decl(nm) + ";"));
+						continue;
+					}
+					try!(writeln!(strm, "\t{} {};", ty.name(), nm));
+					try!(writeln!(strm, "\tswitch(st_{}) {{", nm));
+					let mut gen = sym.generator.clone_box();
+					for state in 0..gen.n_state() {
+						gen.seek(state);
+						let tmp = format!("{}_{}", nm, state);
+						try!(writeln!(strm, "\tcase {}: {{ {}; {} = {}; break; }}", state,
+						              gen.decl(&tmp), nm, tmp));
+					}
+					try!(writeln!(strm, "\t}}"));
+				},
+				Statement::Constraint(ref expr) => {
+					try!(write!(strm, "\tif(!("));
+					try!(expr.codegen(strm, &self));
+					try!(writeln!(strm, ")) {{ return 0; }}"));
+				},
+				_ => {
+					try!(write!(strm, "\t"));
+					try!(st.codegen(strm, &self));
+					try!(write!(strm, "\n"));
+				},
+			};
+		}
+		try!(self.free_heap(strm));
+		writeln!(strm, "\treturn 0;\n}}")
+	}
+
 	// We are done when all the generators for every symbol have reached their
 	// end state.
 	pub fn done(&self) -> bool {
@@ -510,111 +2380,1351 @@ impl Program {
 		for idx in nxt+1..self.symtab.len() {
 			self.symtab[idx].generator.reset();
 		}
+		self.case += 1;
+	}
+
+	// Moves every generator to the state for the given case.  Cases are numbered
+	// in the order that next() visits them: the last symbol varies fastest, so
+	// a case number is a mixed-radix number whose digits are generator states.
+	pub fn seek(&mut self, case: usize) {
+		assert!(case < self.n_states());
+		let mut rem = case;
+		// generators without states are not part of the case number.
+		for sym in self.symtab.iter_mut().rev()
+			.filter(|s| s.generator.n_state() > 0) {
+			let n = sym.generator.n_state();
+			sym.generator.seek(rem % n);
+			rem = rem / n;
+		}
+		self.case = case;
 	}
 
-	// Counts the number of states this program represents.
+	// Picks a case at random, where the state of every generator is chosen
+	// according to its weights.  Pass the case to seek() or codegen_case().
+	// Once n_states() has saturated, a combination of states may have no case
+	// number below it; the states of the earlier generators are then dropped,
+	// which leaves them at the states that seek() gives.
 	#[allow(dead_code)]
-	pub fn n_states(&self) -> usize {
-		return self.symtab.iter().fold(1, |n: usize, ref sym| {
-			return n*sym.generator.n_state();
-		});
+	pub fn sample_case(&self) -> usize {
+		use rand::distributions::{IndependentSample, Range};
+		let ncases = self.n_states();
+		self.symtab.iter().filter(|s| s.generator.n_state() > 0)
+			.fold(0, |case, sym| {
+			let n = sym.generator.n_state();
+			let st = match n > MAX_WEIGHTED_STATES {
+				true => Range::new(0, n).ind_sample(&mut util::rng()),
+				false => util::weighted_index(&sym.generator.weights()),
+			};
+			match case.checked_mul(n).and_then(|c| c.checked_add(st)) {
+				Some(c) if c < ncases => c,
+				_ => st,
+			}
+		})
 	}
-}
 
-// gives the type from the declaration.
-// it needs to take the current type list as well, because this DeclType may
-// reference other types, and it would need to produce boxes to those types.
-fn type_from_decl(decl: &DeclType, types: &Vec<Type>) -> Type {
-	match decl {
-		&DeclType::Basic(ref ty) => ty.clone(),
-		&DeclType::Struct(ref snm, ref flds) => {
-			let mut flds_rv: Vec<(String, Box<Type>)> = Vec::new();
-			for f in flds {
-				match f.ty {
-					DeclType::Basic(ref ty) =>
-						flds_rv.push((f.name.clone(), Box::new(ty.clone()))),
-					DeclType::Struct(_, _) => {
-						// correct?
-						let subtype = type_from_decl(&f.ty, types);
-						flds_rv.push((f.name.clone(), Box::new(subtype)));
+	// The cases for a quick smoke test: every combination of the first, middle
+	// and last state of each generator, instead of all of its states.  The
+	// cases are in increasing order.  Once n_states() has saturated, some
+	// combinations have no case number below it; those are left out.
+	#[allow(dead_code)]
+	pub fn smoke_cases(&self) -> Vec<usize> {
+		let ncases = self.n_states();
+		self.symtab.iter().filter(|s| s.generator.n_state() > 0)
+			.fold(vec![0], |cases, sym| {
+			let n = sym.generator.n_state();
+			let mut states = vec![0, n/2, n-1];
+			states.dedup();
+			let mut rv: Vec<usize> = Vec::new();
+			for case in cases.iter() {
+				rv.extend(states.iter().filter_map(|st| {
+					case.checked_mul(n).and_then(|c| c.checked_add(*st))
+				}).filter(|&c| c < ncases));
+			}
+			rv
+		})
+	}
+
+	// Shrinks the free i32 variables of the current, failing case to boundary
+	// values, for a cleaner reproduction; see GenI32::shrink().  'fails' tells
+	// whether the program fails at the given case.  Each variable in turn is
+	// moved to the boundary closest to its value for which the program still
+	// fails.  Gives the reduced case, and leaves the program at it.
+	pub fn reduce<F>(&mut self, mut fails: F) -> usize
+		where F: FnMut(&mut Program, usize) -> bool {
+		let mut case = self.case;
+		for name in self.freevars.clone() {
+			let pos = match self.symtab.iter().position(|s| s.name == name) {
+				Some(p) if self.symtab[p].generator.name() == "std:I32orig" => p,
+				_ => continue,
+			};
+			// where the variable's state is in the case number, as in seek().
+			let place = self.symtab[pos+1..].iter()
+				.filter(|s| s.generator.n_state() > 0)
+				.fold(Some(1), |p: Option<usize>, s| {
+					p.and_then(|p| p.checked_mul(s.generator.n_state()))
+				});
+			let place = match place {
+				Some(p) => p,
+				None => continue,
+			};
+			let n = self.symtab[pos].generator.n_state();
+			let base = case - (case / place % n) * place;
+			let current = self.case_values(case).into_iter()
+				.find(|v| v.0 == name).and_then(|v| v.1.parse::<i32>().ok());
+			let current = match current {
+				Some(v) => v,
+				None => continue,
+			};
+			let mut gen = variable::GenI32::create(&Type::Builtin(Native::I32));
+			let shrunk = gen.shrink(current, |st, _| {
+				fails(self, base + st * place)
+			});
+			if let Some(st) = shrunk {
+				case = base + st * place;
+			}
+		}
+		self.seek(case);
+		case
+	}
+
+	// The free variables whose values differ between cases 'a' and 'b', in
+	// declaration order, along with their values in each.  The values are the
+	// ones that codegen_case() gives them.  This leaves the program at 'b'.
+	#[allow(dead_code)]
+	pub fn diff_cases(&mut self, a: usize, b: usize) -> Vec<FieldDiff> {
+		let before = self.case_values(a);
+		let after = self.case_values(b);
+		before.into_iter().zip(after.into_iter())
+			.filter(|&((_, ref x), (_, ref y))| x != y)
+			.map(|((nm, x), (_, y))| FieldDiff{name: nm, before: x, after: y})
+			.collect()
+	}
+
+	// Hands the values of every case, in order, to 'sink'.  No code is
+	// emitted, so this exercises the generators without a C compiler.
+	#[allow(dead_code)]
+	pub fn drive(&mut self, sink: &mut CaseSink) {
+		for case in 0..self.n_states() {
+			let values = self.case_values(case);
+			sink.case(&values);
+		}
+	}
+
+	// The values that codegen_case() gives the free variables in 'case', by
+	// name.  This leaves the program at that case.
+	fn case_values(&mut self, case: usize) -> Vec<(String, String)> {
+		self.seek(case);
+		util::seed_rng(self.seed, case as u64);
+		// writing to memory cannot fail.
+		self.codegen(&mut Vec::new()).unwrap();
+		self.emitted.borrow().clone()
+	}
+
+	// Notes that 'code' was generated for the current state, for the
+	// statistics.  Drivers should call this for every program they emit.
+	pub fn record(&mut self, code: &[u8]) {
+		// recover every generator's state from the case number, as in seek().
+		let mut states: Vec<(String, usize)> = Vec::new();
+		let mut rem = self.case;
+		for sym in self.symtab.iter().rev()
+			.filter(|s| s.generator.n_state() > 0) {
+			let n = sym.generator.n_state();
+			if self.freevars.contains(&sym.name) {
+				states.insert(0, (sym.name.clone(), rem % n));
+			}
+			rem = rem / n;
+		}
+		let total = self.n_states();
+		self.stats.record(self.case, code, &states, total);
+	}
+
+	// The warnings that analyze() found, such as about variables that are the
+	// same in every case.
+	pub fn warnings(&self) -> &Vec<String> {
+		&self.warnings
+	}
+
+	// A snapshot of the statistics of what has been generated so far.
+	#[allow(dead_code)]
+	pub fn stats(&self) -> GenerationStats {
+		self.stats.clone()
+	}
+
+	// Generates the complete C program for a single case.  Random values are
+	// drawn from an RNG seeded with our seed and the case number, so a given
+	// case always produces the same program.
+	pub fn codegen_case(&mut self, case: usize, headers: &Vec<&str>,
+	                    strm: &mut std::io::Write) -> std::io::Result<()> {
+		self.seek(case);
+		util::seed_rng(self.seed, case as u64);
+		let mut code: Vec<u8> = Vec::new();
+		try!(self.prologue(&mut code, headers));
+		// the identifier hashes the values, so it comes after generating them.
+		let mut body: Vec<u8> = Vec::new();
+		try!(self.codegen(&mut body));
+		try!(self.case_id(&mut code, case));
+		code.extend(body);
+		try!(self.epilogue(&mut code));
+		let code = util::indent_c(&String::from_utf8_lossy(&code)).into_bytes();
+		self.record(&code);
+		strm.write_all(&code)
+	}
+
+	// Generates one translation unit with a function per case in 'cases', named
+	// test_case_N, and a run_all() that calls each of them in turn.  As in the
+	// libFuzzer harness, a failed constraint returns from its case instead of
+	// exiting, so that the remaining cases still run.  Cases return int
+	// because preconditions return 0 when they do not hold, as in main().
+	#[allow(dead_code)]
+	pub fn codegen_numbered_functions(&mut self, cases: std::ops::Range<usize>,
+	                                  headers: &Vec<&str>,
+	                                  strm: &mut std::io::Write)
+		-> std::io::Result<()> {
+		use std::io::Write;
+		use stmt::{Code, Statement};
+		let mut code: Vec<u8> = Vec::new();
+		try!(self.preamble(&mut code, headers));
+		for case in cases.clone() {
+			self.seek(case);
+			util::seed_rng(self.seed, case as u64);
+			let mut func: Vec<u8> = Vec::new();
+			self.ncalls.set(0);
+			self.emitted.borrow_mut().clear();
+			// the values differ between cases, so each function defines its own
+			// macros and undefines them after it.
+			let mut macros: Vec<String> = Vec::new();
+			for st in self.statements.iter() {
+				match *st {
+					Statement::VariableDeclaration(ref nm, _)
+						if self.value_macro(nm).is_some() => {
+						let mac = self.value_macro(nm).unwrap();
+						let sym = self.cnamelookup(nm).unwrap();
+						let value = sym.generator.value();
+						self.note_value(sym, value.clone());
+						try!(writeln!(func, "#define {} {}", mac, value));
+						macros.push(mac);
 					},
-					DeclType::Enum(_, _) => unreachable!(),
-					DeclType::StructRef(ref nm) => {
-						for t in types {
-							match t {
-								&Type::Struct(ref tgt, _) if *nm==*tgt => {
-									flds_rv.push((f.name.clone(), Box::new(t.clone())));
-									break;
-								},
-								_ => (),
-							}
-						}
+					Statement::Constraint(ref expr) => {
+						try!(write!(func, "\tif(!("));
+						try!(expr.codegen(&mut func, &self));
+						try!(writeln!(func, ")) {{ return 0; }}"));
 					},
-					DeclType::EnumRef(/*ref nm*/ _) => unimplemented!(),
-				}
+					_ => {
+						try!(write!(func, "\t"));
+						try!(st.codegen(&mut func, &self));
+						try!(write!(func, "\n"));
+					},
+				};
 			}
-			Type::Struct(snm.clone(), flds_rv)
-		},
-		&DeclType::Enum(ref enm, ref evalues) => {
-			Type::Enum(enm.clone(), evalues.clone())
-		},
-		&DeclType::StructRef(ref nm) => {
-			let mut rv: Type = Type::Builtin(Native::Void);
-			for typex in types {
-				match typex {
-					&Type::Struct(ref strct, _) if strct == nm => rv = typex.clone(),
-					_ => {},
+			try!(self.delete_live(&mut func));
+			try!(self.free_heap(&mut func));
+			try!(writeln!(func, "\treturn 0;\n}}"));
+			for mac in macros.iter() {
+				try!(writeln!(func, "#undef {}", mac));
+			}
+			try!(writeln!(func, ""));
+			let mut header: Vec<u8> = Vec::new();
+			try!(writeln!(header, "static int test_case_{:04}(void) {{", case));
+			try!(self.case_id(&mut header, case));
+			header.extend(func);
+			self.record(&header);
+			code.extend(header);
+		}
+		try!(writeln!(code, "void run_all(void) {{"));
+		for case in cases {
+			try!(writeln!(code, "\ttest_case_{:04}();", case));
+		}
+		try!(writeln!(code, "}}"));
+		strm.write_all(util::indent_c(&String::from_utf8_lossy(&code)).as_bytes())
+	}
+
+	// Generates cases from the first one on, until either 'max_cases' cases
+	// were generated or 'deadline' passed, handing each one to 'sink'.  The
+	// deadline is checked between cases, so a run may overshoot it by the
+	// time it takes to generate one case.
+	#[allow(dead_code)]
+	pub fn generate_budgeted<F>(&mut self, headers: &Vec<&str>, sink: F,
+	                            max_cases: Option<usize>,
+	                            deadline: Option<std::time::Instant>)
+		-> std::io::Result<ResumeToken>
+		where F: FnMut(usize, Vec<u8>) -> std::io::Result<()>
+	{
+		let start = ResumeToken{case: 0, total: self.n_states()};
+		self.generate_from(start, headers, sink, max_cases, deadline)
+	}
+
+	// As generate_budgeted(), but starts at the case recorded in 'token'.
+	#[allow(dead_code)]
+	pub fn generate_from<F>(&mut self, token: ResumeToken, headers: &Vec<&str>,
+	                        mut sink: F, max_cases: Option<usize>,
+	                        deadline: Option<std::time::Instant>)
+		-> std::io::Result<ResumeToken>
+		where F: FnMut(usize, Vec<u8>) -> std::io::Result<()>
+	{
+		let total = self.n_states();
+		assert_eq!(token.total, total, "token is for a different program");
+		let mut case = token.case;
+		let mut ngenerated: usize = 0;
+		while case < total {
+			if max_cases.map_or(false, |mx| ngenerated >= mx) {
+				break;
+			}
+			if deadline.map_or(false, |d| std::time::Instant::now() >= d) {
+				break;
+			}
+			let mut strm: Vec<u8> = Vec::new();
+			try!(self.codegen_case(case, headers, &mut strm));
+			try!(sink(case, strm));
+			case += 1;
+			ngenerated += 1;
+		}
+		Ok(ResumeToken{case: case, total: total})
+	}
+
+	// Generates the given cases, handing each one to 'code_sink' and writing a
+	// line to 'manifest' for it.  Each line is a JSON object with the case
+	// number, the value that each free variable was given in the code, and
+	// whether that value is at an edge of what its generator produces.
+	#[allow(dead_code)]
+	pub fn generate_with_manifest<F>(&mut self, cases: std::ops::Range<usize>,
+	                                 headers: &Vec<&str>, mut code_sink: F,
+	                                 manifest: &mut std::io::Write)
+		-> std::io::Result<()>
+		where F: FnMut(usize, Vec<u8>) -> std::io::Result<()>
+	{
+		for case in cases {
+			let mut strm: Vec<u8> = Vec::new();
+			try!(self.codegen_case(case, headers, &mut strm));
+			try!(code_sink(case, strm));
+			try!(writeln!(manifest, "{}", self.manifest_line(case)));
+		}
+		Ok(())
+	}
+
+	// Writes each of the given cases to its own file in 'dir', as a complete
+	// program that compiles on its own.  The name of each file is 'template'
+	// with "{index}" replaced by the case number; "{index:08}" pads it with
+	// zeros to 8 digits.  The directory is created if it does not exist.
+	// Returns the paths written, in order.
+	#[allow(dead_code)]
+	pub fn generate_to_dir(&mut self, dir: &Path,
+	                       cases: std::ops::Range<usize>, template: &str,
+	                       headers: &Vec<&str>)
+		-> Result<Vec<PathBuf>, CaseFileError> {
+		use std::io::Write;
+		// check the template before writing anything.
+		try!(case_file_name(template, 0).map_err(|e| {
+			CaseFileError::Template(template.to_string(), e)
+		}));
+		try!(std::fs::create_dir_all(dir).map_err(|e| {
+			CaseFileError::Io(dir.display().to_string(), e)
+		}));
+		let mut paths: Vec<PathBuf> = Vec::new();
+		for case in cases {
+			let path = dir.join(case_file_name(template, case).unwrap());
+			let mut code: Vec<u8> = Vec::new();
+			// writing to memory cannot fail.
+			self.codegen_case(case, headers, &mut code).unwrap();
+			try!(std::fs::File::create(&path)
+				.and_then(|mut f| f.write_all(&code))
+				.map_err(|e| CaseFileError::Io(path.display().to_string(), e)));
+			paths.push(path);
+		}
+		Ok(paths)
+	}
+
+	// Generates cases [start, end) using 'nthreads' threads.  Each thread works
+	// on its own copy of the program and buffers every case it generates,
+	// handing the buffer to 'sink' along with the case number.  A case depends
+	// only on the seed and its number, so the output does not depend on the
+	// number of threads; only the order in which 'sink' is called does.
+	#[allow(dead_code)]
+	pub fn generate_range_parallel<F>(&self, start: usize, end: usize,
+	                                  nthreads: usize, headers: &Vec<&str>,
+	                                  sink: F) -> std::io::Result<()>
+		where F: Fn(usize, Vec<u8>) -> std::io::Result<()> + Send + Sync + 'static
+	{
+		self.generate_parallel_with_manifest(start, end, nthreads, headers,
+			move |case, code, _| sink(case, code))
+	}
+
+	// As generate_range_parallel(), but 'sink' also gets the manifest line of
+	// each case, as generate_with_manifest() writes it.  The line is made by
+	// the same thread as the code, so the two always agree.
+	#[allow(dead_code)]
+	pub fn generate_parallel_with_manifest<F>(&self, start: usize, end: usize,
+	                                          nthreads: usize,
+	                                          headers: &Vec<&str>, sink: F)
+		-> std::io::Result<()>
+		where F: Fn(usize, Vec<u8>, String) -> std::io::Result<()> + Send + Sync +
+		         'static
+	{
+		use std::sync::Arc;
+		use std::thread;
+		assert!(nthreads > 0);
+		assert!(start <= end && end <= self.n_states());
+		let sink = Arc::new(sink);
+		let hdrs: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+		// split the range into contiguous chunks, one per thread.
+		let chunk = (end - start + nthreads - 1) / nthreads;
+		let mut threads = Vec::new();
+		for t in 0..nthreads {
+			let first = (start + t*chunk).min(end);
+			let last = (first + chunk).min(end);
+			let mut pgm = self.clone();
+			let sink = sink.clone();
+			let hdrs = hdrs.clone();
+			threads.push(thread::spawn(move || -> std::io::Result<()> {
+				let headers: Vec<&str> = hdrs.iter().map(|h| h.as_str()).collect();
+				for case in first..last {
+					let mut strm: Vec<u8> = Vec::new();
+					try!(pgm.codegen_case(case, &headers, &mut strm));
+					let line = pgm.manifest_line(case);
+					try!(sink(case, strm, line));
+				}
+				Ok(())
+			}));
+		}
+		for thr in threads {
+			match thr.join() {
+				Err(_) => panic!("case generation thread panicked"),
+				Ok(result) => try!(result),
+			};
+		}
+		Ok(())
+	}
+
+	// How the named function was classified when it was declared.
+	fn funkind(&self, funcname: &str) -> FuncKind {
+		for decl in self.declarations.iter() {
+			match decl {
+				&Declaration::Function(ref f) if f.name == funcname => return f.kind,
+				_ => (),
+			};
+		}
+		FuncKind::Operation
+	}
+
+	// Finds resources that are created by a constructor but never given to a
+	// destructor afterwards.  A constructor creates the variable its result is
+	// assigned to, as well as any variable whose address it is given; a
+	// destructor releases every variable it is given.  Returns a message for
+	// every leaked resource, in the order they were created.
+	#[allow(dead_code)]
+	pub fn leaked_resources(&self) -> Vec<String> {
+		// (variable, constructor) pairs of live resources.
+		let mut live: Vec<(String, String)> = Vec::new();
+		self.plan_resources(&self.statements, &mut live);
+		live.iter().map(|&(ref var, ref ctor)|
+			format!("'{}' is created by '{}' but never deleted", var, ctor)
+		).collect()
+	}
+
+	// Writes the calls that delete the resources which are still live at the
+	// end of the program, if cleanup is on.  A resource is given to the first
+	// destructor that takes its type, or its address.  Resources that no
+	// destructor takes are left alone.
+	fn delete_live<S: stmt::CodeSink + ?Sized>(&self, sink: &mut S) ->
+		std::io::Result<()> {
+		if !self.cleanup {
+			return Ok(());
+		}
+		let mut live: Vec<(String, String)> = Vec::new();
+		self.plan_resources(&self.statements, &mut live);
+		for &(ref var, _) in live.iter().rev() {
+			let sym = match self.symlookup(var) {
+				None => continue,
+				Some(s) => s,
+			};
+			let addr = Type::Pointer(Box::new(sym.typ.clone()));
+			for ty in self.typetab.iter() {
+				let f = match *ty {
+					Type::Function(ref f) if f.parameters.len() == 1 &&
+						self.funkind(&f.name) == FuncKind::Destructor => f,
+					_ => continue,
 				};
+				if f.parameters[0] == sym.typ {
+					try!(sink.statement(&format!("{}({});", f.name,
+					                             sym.codegen_name())));
+				} else if f.parameters[0] == addr {
+					try!(sink.statement(&format!("{}(&{});", f.name,
+					                             sym.codegen_name())));
+				} else {
+					continue;
+				}
+				break;
 			}
-			// Didn't find it?  Then bail, unknown type!
-			if rv == Type::Builtin(Native::Void) {
-				panic!("Unknown struct '{}'!", nm);
+		}
+		Ok(())
+	}
+
+	// Writes the calls that free the free variables that were allocated on the
+	// heap, unless the program gives them to a destructor itself.  Variables
+	// that are macros have no storage of their own to free.
+	fn free_heap<S: stmt::CodeSink + ?Sized>(&self, sink: &mut S) ->
+		std::io::Result<()> {
+		for sym in self.symtab.iter() {
+			if !self.freevars.contains(&sym.name) ||
+			   sym.generator.name() != "std:heap" {
+				continue;
 			}
-			rv
+			let nm = sym.codegen_name();
+			if self.value_macro(&nm).is_some() ||
+			   self.destroyed(&self.statements, &sym.name) {
+				continue;
+			}
+			try!(sink.statement(&format!("free({});", nm)));
 		}
-		&DeclType::EnumRef(ref nm) => {
-			let mut rv: Type = Type::Builtin(Native::Void);
-			for typex in types {
-				match typex {
-					&Type::Enum(ref enm, _) if enm == nm => rv = typex.clone(),
-					&Type::Enum(ref enm, _) => {
-						println!("Enum '{}' is not a match for '{}'", enm, nm);
-					}
-					_ => {},
+		Ok(())
+	}
+
+	// Whether the named variable is given to a destructor in 'stmts'.
+	fn destroyed(&self, stmts: &Vec<stmt::Statement>, name: &str) -> bool {
+		use expr::Expression;
+		use stmt::Statement;
+		stmts.iter().any(|st| match *st {
+			Statement::Expr(Expression::FqnCall(ref fqn, ref args)) |
+			Statement::Assignment(_, Expression::FqnCall(ref fqn, ref args)) =>
+				self.funkind(&fqn.name) == FuncKind::Destructor &&
+				args.iter().any(|a| match *a {
+					Expression::Basic(_, ref sym) => sym.name == name,
+					_ => false,
+				}),
+			Statement::If(_, ref body) | Statement::While(_, ref body) |
+			Statement::Repeat(_, _, ref body) => self.destroyed(body, name),
+			Statement::Guarded(_, ref inner) =>
+				self.destroyed(&vec![(**inner).clone()], name),
+			_ => false,
+		})
+	}
+
+	fn plan_resources(&self, stmts: &Vec<stmt::Statement>,
+	                  live: &mut Vec<(String, String)>) {
+		use expr::Expression;
+		use stmt::Statement;
+		for st in stmts.iter() {
+			let (result, call) = match *st {
+				Statement::Assignment(Expression::Basic(_, ref sym), ref rhs) =>
+					(Some(sym.name.clone()), rhs),
+				Statement::Assignment(_, ref rhs) => (None, rhs),
+				Statement::Expr(ref ex) | Statement::Constraint(ref ex) |
+				Statement::Verify(ref ex) => (None, ex),
+				Statement::If(_, ref body) | Statement::While(_, ref body) |
+				Statement::Repeat(_, _, ref body) => {
+					self.plan_resources(body, live);
+					continue;
+				},
+				Statement::Guarded(_, ref inner) => {
+					self.plan_resources(&vec![(**inner).clone()], live);
+					continue;
+				},
+				Statement::VariableDeclaration(_, _) => continue,
+			};
+			self.plan_call(call, result, live);
+		}
+	}
+
+	// Updates the live resources for the calls in the given expression.
+	fn plan_call(&self, ex: &expr::Expression, result: Option<String>,
+	             live: &mut Vec<(String, String)>) {
+		use expr::Expression;
+		match *ex {
+			Expression::FqnCall(ref fqn, ref args) => {
+				let kind = self.funkind(&fqn.name);
+				for arg in args.iter() {
+					match (kind, arg) {
+						(FuncKind::Constructor,
+						 &Expression::Basic(UOp::AddressOf, ref sym)) =>
+							live.push((sym.name.clone(), fqn.name.clone())),
+						(FuncKind::Destructor, &Expression::Basic(_, ref sym)) =>
+							live.retain(|&(ref var, _)| *var != sym.name),
+						_ => self.plan_call(arg, None, live),
+					};
+				}
+				match (kind, result) {
+					(FuncKind::Constructor, Some(var)) =>
+						live.push((var, fqn.name.clone())),
+					_ => (),
 				};
+			},
+			Expression::Compound(ref lhs, _, ref rhs) => {
+				self.plan_call(lhs, None, live);
+				self.plan_call(rhs, None, live);
+			},
+			_ => (),
+		};
+	}
+
+	// How the named function uses each of its parameters.
+	fn param_modes(&self, funcname: &str) -> Vec<ParamMode> {
+		for decl in self.declarations.iter() {
+			match decl {
+				&Declaration::Function(ref f) if f.name == funcname =>
+					return f.modes.clone(),
+				_ => (),
+			};
+		}
+		Vec::new()
+	}
+
+	// Links every value that a call produces to the later calls that consume
+	// it.  A call produces the variable its result is assigned to, as well as
+	// any variable given to an "out" or "inout" parameter; a call consumes
+	// the variables given to its other parameters, and those given to
+	// "inout" ones.  Links are in the order that the consumers are called.
+	#[allow(dead_code)]
+	pub fn data_flow(&self) -> Vec<DataFlow> {
+		// (variable, producer) pairs of the latest value of each variable.
+		let mut produced: Vec<(String, String)> = Vec::new();
+		let mut links: Vec<DataFlow> = Vec::new();
+		self.flow_statements(&self.statements, &mut produced, &mut links);
+		links
+	}
+
+	fn flow_statements(&self, stmts: &Vec<stmt::Statement>,
+	                   produced: &mut Vec<(String, String)>,
+	                   links: &mut Vec<DataFlow>) {
+		use expr::Expression;
+		use stmt::Statement;
+		for st in stmts.iter() {
+			let (result, call) = match *st {
+				Statement::Assignment(Expression::Basic(_, ref sym), ref rhs) =>
+					(Some(sym.name.clone()), rhs),
+				Statement::Assignment(_, ref rhs) => (None, rhs),
+				Statement::Expr(ref ex) | Statement::Constraint(ref ex) |
+				Statement::Verify(ref ex) => (None, ex),
+				Statement::If(_, ref body) | Statement::While(_, ref body) |
+				Statement::Repeat(_, _, ref body) => {
+					self.flow_statements(body, produced, links);
+					continue;
+				},
+				Statement::Guarded(_, ref inner) => {
+					self.flow_statements(&vec![(**inner).clone()], produced, links);
+					continue;
+				},
+				Statement::VariableDeclaration(_, _) => continue,
+			};
+			self.flow_call(call, result, produced, links);
+		}
+	}
+
+	// Records the links for the calls in the given expression.
+	fn flow_call(&self, ex: &expr::Expression, result: Option<String>,
+	             produced: &mut Vec<(String, String)>,
+	             links: &mut Vec<DataFlow>) {
+		use expr::Expression;
+		let (fqn, args) = match *ex {
+			Expression::FqnCall(ref fqn, ref args) => (fqn, args),
+			Expression::Compound(ref lhs, _, ref rhs) => {
+				self.flow_call(lhs, None, produced, links);
+				self.flow_call(rhs, None, produced, links);
+				return;
+			},
+			_ => return,
+		};
+		let modes = self.param_modes(&fqn.name);
+		let mode = |i: usize| *modes.get(i).unwrap_or(&ParamMode::In);
+		// every argument is read before any output is written.
+		for (i, arg) in args.iter().enumerate() {
+			match (mode(i), arg) {
+				(ParamMode::Out, _) => (),
+				(_, &Expression::Basic(_, ref sym)) => {
+					if let Some(&(_, ref producer)) =
+						produced.iter().find(|&&(ref var, _)| *var == sym.name) {
+						links.push(DataFlow{var: sym.name.clone(),
+						                    producer: producer.clone(),
+						                    consumer: fqn.name.clone()});
+					}
+				},
+				_ => self.flow_call(arg, None, produced, links),
+			};
+		}
+		let mut outputs: Vec<String> = Vec::new();
+		for (i, arg) in args.iter().enumerate() {
+			match (mode(i), arg) {
+				(ParamMode::In, _) => (),
+				(_, &Expression::Basic(_, ref sym)) => outputs.push(sym.name.clone()),
+				_ => (),
+			};
+		}
+		outputs.extend(result);
+		for var in outputs.into_iter() {
+			produced.retain(|&(ref v, _)| *v != var);
+			produced.push((var, fqn.name.clone()));
+		}
+	}
+
+	// Counts the number of states this program represents.  The product easily
+	// exceeds a usize for larger programs, so this saturates instead of
+	// overflowing.  Sources without states, such as functions, do not count;
+	// a program without free variables has a single case.
+	#[allow(dead_code)]
+	pub fn n_states(&self) -> usize {
+		return self.symtab.iter().filter(|s| s.generator.n_state() > 0)
+			.fold(1, |n: usize, ref sym| {
+			return n.saturating_mul(sym.generator.n_state());
+		});
+	}
+
+	// The symbols for every free variable, in declaration order.
+	#[allow(dead_code)]
+	pub fn free_symbols(&self) -> Vec<&Symbol> {
+		self.freevars.iter().filter_map(|nm| self.symlookup(nm)).collect()
+	}
+
+	// The free variables whose generators have a single state, such as opaque
+	// structs and null pointers.  They add nothing to the cases, which is often
+	// a mistake, like an opaque pointer that was meant to be concrete, so the
+	// caller can list them for the user to confirm; analyze() warns about each.
+	// Pinned variables are listed too; call this before pin().
+	pub fn single_state_variables(&self) -> Vec<String> {
+		self.free_symbols().iter().filter(|s| s.generator.n_state() == 1)
+			.map(|s| s.name.clone()).collect()
+	}
+
+	// Holds the named free variable at the given state of its generator, so
+	// that only the other variables vary.  The variable then has a single
+	// state.  Call this after analyze(); the program goes back to its first
+	// case.
+	#[allow(dead_code)]
+	pub fn pin(&mut self, name: &str, state: usize) -> Result<(),String> {
+		if !self.freevars.iter().any(|nm| nm == name) {
+			return Err(format!("'{}' is not a free variable", name));
+		}
+		{
+			let sym = self.symtab.iter_mut().find(|s| s.name == name).unwrap();
+			let n = sym.generator.n_state();
+			if sym.generator.name().ends_with(":pinned") {
+				return Err(format!("'{}' is pinned already", name));
 			}
-			// Didn't find it?  Then bail, unknown type!
-			if rv == Type::Builtin(Native::Void) {
-				panic!("Unknown enum '{}'!", nm);
+			if state >= n {
+				return Err(format!("cannot pin '{}' to state {}; it has {} states",
+				                   name, state, n));
 			}
-			rv
-		},
+			let gen = sym.generator.clone();
+			sym.generator = Box::new(variable::GenPinned::create(gen, state));
+		}
+		self.seek(0);
+		Ok(())
+	}
+
+	// Removes the free variables that no statement uses, so that their states
+	// do not multiply the number of cases.  Returns the names of the removed
+	// variables, so that the caller can warn about them.  Call this after
+	// analyze(); the program goes back to its first case.
+	#[allow(dead_code)]
+	pub fn prune_unused(&mut self) -> Vec<String> {
+		let unused: Vec<String> = self.freevars.iter()
+			.filter(|nm| symbol_uses(&self.statements, nm).0 == 0)
+			.cloned().collect();
+		let cnames: Vec<String> = self.symtab.iter()
+			.filter(|s| unused.contains(&s.name))
+			.map(|s| s.codegen_name()).collect();
+		self.statements.retain(|st| match *st {
+			stmt::Statement::VariableDeclaration(ref nm, _) => !cnames.contains(nm),
+			_ => true,
+		});
+		self.symtab.retain(|s| !unused.contains(&s.name));
+		self.freevars.retain(|nm| !unused.contains(nm));
+		self.sharedvars.retain(|nm| !unused.contains(nm));
+		self.seek(0);
+		unused
+	}
+
+	// Writes a tab-separated summary of the state space: a header, one row per
+	// free variable (name, type, generator, number of states), and a final row
+	// with the total number of states of the program.
+	#[allow(dead_code)]
+	pub fn describe(&self, strm: &mut std::io::Write) -> std::io::Result<()> {
+		try!(writeln!(strm, "name\ttype\tgenerator\tstates"));
+		for sym in self.free_symbols() {
+			try!(writeln!(strm, "{}\t{}\t{}\t{}", sym.name, sym.typ.name(),
+			              sym.generator.name(), sym.generator.n_state()));
+		}
+		writeln!(strm, "total\t\t\t{}", self.n_states())
+	}
+
+	// Writes the analyzed model as XML: the user-defined types, the functions
+	// and the free variables along with their generators.  This describes the
+	// state space, not the values of any particular case.
+	#[allow(dead_code)]
+	pub fn dump_model_xml(&self, strm: &mut std::io::Write) ->
+		std::io::Result<()> {
+		use util::xml_escape as esc;
+		try!(writeln!(strm, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+		try!(writeln!(strm, "<model states=\"{}\">", self.n_states()));
+		// types are entered into the table once per use, so skip repeats.
+		let mut seen: Vec<String> = Vec::new();
+		for ty in self.typetab.iter() {
+			let key = match *ty {
+				Type::Struct(ref nm, _, _) => format!("struct {}", nm),
+				Type::Enum(ref nm, _) => format!("enum {}", nm),
+				Type::Function(ref f) => format!("function {}", f.name),
+				_ => continue,
+			};
+			if seen.contains(&key) {
+				continue;
+			}
+			seen.push(key);
+			match *ty {
+				Type::Struct(ref nm, ref flds, _) => {
+					try!(writeln!(strm, "\t<struct name=\"{}\">", esc(nm)));
+					for &(ref fname, ref fty, bits) in flds.iter() {
+						try!(write!(strm, "\t\t<field name=\"{}\" type=\"{}\"",
+						            esc(fname), esc(&fty.name())));
+						if let Some(b) = bits {
+							try!(write!(strm, " bits=\"{}\"", b));
+						}
+						try!(writeln!(strm, "/>"));
+					}
+					try!(writeln!(strm, "\t</struct>"));
+				},
+				Type::Enum(ref nm, ref values) => {
+					try!(writeln!(strm, "\t<enum name=\"{}\">", esc(nm)));
+					for &(ref vname, v, _) in values.iter() {
+						try!(writeln!(strm, "\t\t<enumerator name=\"{}\" value=\"{}\"/>",
+						              esc(vname), v));
+					}
+					try!(writeln!(strm, "\t</enum>"));
+				},
+				Type::Function(ref f) => {
+					let kind = match self.funkind(&f.name) {
+						FuncKind::Constructor => "constructor",
+						FuncKind::Destructor => "destructor",
+						FuncKind::Operation => "operation",
+					};
+					try!(writeln!(strm, "\t<function name=\"{}\" returns=\"{}\" \
+					                     kind=\"{}\">", esc(&f.name),
+					              esc(&f.retval.name()), kind));
+					for pm in f.parameters.iter() {
+						try!(writeln!(strm, "\t\t<parameter type=\"{}\"/>",
+						              esc(&pm.name())));
+					}
+					try!(writeln!(strm, "\t</function>"));
+				},
+				_ => unreachable!(),
+			};
+		}
+		for sym in self.free_symbols() {
+			try!(writeln!(strm, "\t<variable name=\"{}\" type=\"{}\" \
+			                     generator=\"{}\" states=\"{}\"/>", esc(&sym.name),
+			              esc(&sym.typ.name()), esc(&sym.generator.name()),
+			              sym.generator.n_state()));
+		}
+		writeln!(strm, "</model>")
 	}
 }
 
-#[cfg(test)]
-mod test {
-	use api;
-	use fuzz;
-	use typ::{Native, Type};
+// Assembles the declarations and statements of a Program, as the parser
+// would.  Declarations are analyzed in the order they are added, so add types
+// before anything that refers to them.
+#[allow(dead_code)]
+pub struct ProgramBuilder {
+	decls: Vec<Declaration>,
+	stmts: Vec<Stmt>,
+}
 
-	#[test]
-	fn empty_struct() {
-		let s = "struct entry { }";
-		assert!(fuzz::parse_LDeclarations(s).is_ok());
-		assert_eq!(fuzz::parse_LDeclarations(s).unwrap().len(), 1);
-		let ref decl: api::Declaration = fuzz::parse_LDeclarations(s).unwrap()[0];
-		let decl = match decl {
-			&api::Declaration::UDT(ref udt) => udt,
-			_ => panic!("invalid declaration parse {:?}", decl),
+#[allow(dead_code)]
+impl ProgramBuilder {
+	// 'udt' must be a DeclType::Struct or DeclType::Enum.
+	pub fn udt(mut self, udt: DeclType) -> Self {
+		self.decls.push(Declaration::UDT(udt));
+		self
+	}
+	pub fn free(mut self, name: &str, genname: &str, ty: DeclType) -> Self {
+		self.decls.push(Declaration::Free(FreeVarDecl::new(name, genname, ty)));
+		self
+	}
+	pub fn shared(mut self, name: &str, genname: &str, ty: DeclType) -> Self {
+		let mut fvd = FreeVarDecl::new(name, genname, ty);
+		fvd.shared = true;
+		self.decls.push(Declaration::Free(fvd));
+		self
+	}
+	pub fn constrained(mut self, name: &str, ty: DeclType) -> Self {
+		self.decls.push(Declaration::Constrained(name.to_string(), ty));
+		self
+	}
+	pub fn function(mut self, func: FuncDecl) -> Self {
+		self.decls.push(Declaration::Function(func));
+		self
+	}
+	pub fn statement(mut self, stmt: Stmt) -> Self {
+		self.stmts.push(stmt);
+		self
+	}
+	pub fn build(self) -> Program {
+		Program::new(&self.decls, &self.stmts)
+	}
+}
+
+// Why a DSL file could not be parsed: either it could not be read at all, or
+// its contents are not valid.  Both name the file.
+#[derive(Debug)]
+pub enum FileError {
+	Io(String, std::io::Error),
+	// the file, and where in it parsing failed.
+	Parse(String, String),
+}
+impl fmt::Display for FileError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			&FileError::Io(ref path, ref err) =>
+				write!(f, "cannot read '{}': {}", path, err),
+			&FileError::Parse(ref path, ref msg) => write!(f, "{}: {}", path, msg),
+		}
+	}
+}
+
+// Why generate_to_dir() failed: either the file name template is not valid,
+// or a file could not be written.
+#[derive(Debug)]
+pub enum CaseFileError {
+	// the template, and what is wrong with it.
+	Template(String, String),
+	// the file or directory that could not be written.
+	Io(String, std::io::Error),
+}
+impl fmt::Display for CaseFileError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			&CaseFileError::Template(ref tmpl, ref msg) =>
+				write!(f, "invalid file name template '{}': {}", tmpl, msg),
+			&CaseFileError::Io(ref path, ref err) =>
+				write!(f, "cannot write '{}': {}", path, err),
+		}
+	}
+}
+
+// Expands a file name template for the given case, as generate_to_dir()
+// describes.  Every case needs a name of its own, so the template must
+// mention the index.
+fn case_file_name(template: &str, case: usize) -> Result<String, String> {
+	let mut name = String::new();
+	let mut indexed = false;
+	let mut rest = template;
+	while let Some(open) = rest.find('{') {
+		name.push_str(&rest[..open]);
+		let close = match rest[open..].find('}') {
+			None => return Err("unterminated '{'".to_string()),
+			Some(c) => open + c,
 		};
-		use api::DeclType;
-		match decl {
-			&DeclType::Basic(_) => panic!("type should be Struct, is Basic"),
-			&DeclType::Enum(_, _) => panic!("type should be Struct, is Enum"),
+		let field = &rest[open+1..close];
+		let spec = match field.find(':') {
+			None => "",
+			Some(colon) => &field[colon+1..],
+		};
+		if field != "index" && !field.starts_with("index:") {
+			return Err(format!("unknown field '{{{}}}'", field));
+		}
+		if !spec.chars().all(|c| c.is_digit(10)) {
+			return Err(format!("invalid width '{}'", spec));
+		}
+		let width = spec.parse::<usize>().unwrap_or(0);
+		if spec.starts_with("0") {
+			name.push_str(&format!("{:01$}", case, width));
+		} else {
+			name.push_str(&format!("{:1$}", case, width));
+		}
+		indexed = true;
+		rest = &rest[close+1..];
+	}
+	name.push_str(rest);
+	if !indexed {
+		return Err("no '{index}' field".to_string());
+	}
+	Ok(name)
+}
+
+// Reads and parses the declarations in the given file.
+#[allow(dead_code)]
+pub fn parse_declarations_file(path: &Path)
+	-> Result<Vec<Declaration>, FileError> {
+	use std::io::Read;
+	let name = path.display().to_string();
+	let mut text = String::new();
+	let read = std::fs::File::open(path)
+		.and_then(|mut f| f.read_to_string(&mut text));
+	match read {
+		Err(e) => return Err(FileError::Io(name, e)),
+		Ok(_) => (),
+	};
+	fuzz::parse_LDeclarations(text.as_str()).map_err(|e| {
+		FileError::Parse(name.clone(), util::parse_error(text.as_str(), e))
+	})
+}
+
+// Names the unnamed loop variables in 'stmts', and errors out if a named one
+// is in 'taken'.  Every loop variable is added to 'taken'.
+fn name_loops(stmts: &mut Vec<Stmt>, taken: &mut Vec<String>) ->
+	Result<(),String> {
+	for stmt in stmts.iter_mut() {
+		match *stmt {
+			Stmt::Repeat(_, ref mut var, ref mut body) => {
+				let nm = match var.take() {
+					Some(ref nm) if taken.contains(nm) =>
+						return Err(format!("loop variable '{}' is already declared", nm)),
+					Some(nm) => nm,
+					None => util::c_identifier("i", taken),
+				};
+				taken.push(nm.clone());
+				*var = Some(nm);
+				try!(name_loops(body, taken));
+			},
+			Stmt::If(_, ref mut body) | Stmt::While(_, ref mut body) => {
+				try!(name_loops(body, taken));
+			},
+			_ => (),
+		};
+	}
+	Ok(())
+}
+
+// Counts the uses of the named variable in 'stmts': all of them, and those
+// that pass its address directly to a function.  Conditions of guarded
+// statements are C code, so any mention of the name counts as a use.
+fn symbol_uses(stmts: &Vec<stmt::Statement>, name: &str) -> (usize, usize) {
+	use expr::Expression;
+	use stmt::Statement;
+	fn expr_uses(ex: &Expression, name: &str, uses: &mut (usize, usize)) {
+		match *ex {
+			Expression::Basic(_, ref sym) | Expression::Ops(_, ref sym) |
+			Expression::Field(ref sym, _) |
+			Expression::ArrowField(ref sym, _) if sym.name == name => uses.0 += 1,
+			Expression::Compound(ref lhs, _, ref rhs) => {
+				expr_uses(lhs, name, uses);
+				expr_uses(rhs, name, uses);
+			},
+			Expression::FqnCall(_, ref args) => {
+				for arg in args.iter() {
+					match *arg {
+						Expression::Basic(UOp::AddressOf, ref sym) if sym.name == name =>
+							uses.1 += 1,
+						_ => (),
+					};
+					expr_uses(arg, name, uses);
+				}
+			},
+			_ => (),
+		}
+	}
+	let mut uses = (0, 0);
+	for st in stmts.iter() {
+		match *st {
+			Statement::VariableDeclaration(_, _) => (),
+			Statement::Expr(ref ex) | Statement::Verify(ref ex) |
+			Statement::Constraint(ref ex) => expr_uses(ex, name, &mut uses),
+			Statement::Assignment(ref lhs, ref rhs) => {
+				expr_uses(lhs, name, &mut uses);
+				expr_uses(rhs, name, &mut uses);
+			},
+			Statement::If(ref cond, ref body) |
+			Statement::While(ref cond, ref body) => {
+				expr_uses(cond, name, &mut uses);
+				let inner = symbol_uses(body, name);
+				uses = (uses.0 + inner.0, uses.1 + inner.1);
+			},
+			Statement::Repeat(_, _, ref body) => {
+				let inner = symbol_uses(body, name);
+				uses = (uses.0 + inner.0, uses.1 + inner.1);
+			},
+			Statement::Guarded(ref conds, ref inner) => {
+				uses.0 += conds.iter().filter(|c| c.contains(name)).count();
+				let inner = symbol_uses(&vec![(**inner).clone()], name);
+				uses = (uses.0 + inner.0, uses.1 + inner.1);
+			},
+		};
+	}
+	uses
+}
+
+// Whether the named variable is only ever read in 'stmts', by its value or
+// what it points to, so that its value can stand in for it.  Conditions of
+// guarded statements are C code, so any mention of the name rules it out.
+fn read_only(stmts: &Vec<stmt::Statement>, name: &str) -> bool {
+	use expr::Expression;
+	use stmt::Statement;
+	fn expr_reads(ex: &Expression, name: &str) -> bool {
+		match *ex {
+			Expression::Basic(UOp::None, _) | Expression::Basic(UOp::Deref, _) =>
+				true,
+			Expression::Basic(_, ref sym) | Expression::Ops(_, ref sym) |
+			Expression::Field(ref sym, _) |
+			Expression::ArrowField(ref sym, _) => sym.name != name,
+			Expression::Compound(ref lhs, _, ref rhs) =>
+				expr_reads(lhs, name) && expr_reads(rhs, name),
+			Expression::FqnCall(_, ref args) =>
+				args.iter().all(|a| expr_reads(a, name)),
+			_ => true,
+		}
+	}
+	stmts.iter().all(|st| match *st {
+		Statement::VariableDeclaration(_, _) => true,
+		Statement::Expr(ref ex) | Statement::Verify(ref ex) |
+		Statement::Constraint(ref ex) => expr_reads(ex, name),
+		Statement::Assignment(ref lhs, ref rhs) =>
+			symbol_uses(&vec![Statement::Expr(lhs.clone())], name).0 == 0 &&
+			expr_reads(rhs, name),
+		Statement::If(ref cond, ref body) |
+		Statement::While(ref cond, ref body) =>
+			expr_reads(cond, name) && read_only(body, name),
+		Statement::Repeat(_, _, ref body) => read_only(body, name),
+		Statement::Guarded(ref conds, ref inner) =>
+			!conds.iter().any(|c| c.contains(name)) &&
+			read_only(&vec![(**inner).clone()], name),
+	})
+}
+
+// Appends the variadic arguments to the calls in 'stmts' of the 'variadic'
+// functions, given as (name, number of fixed parameters) pairs.  The variables
+// for the arguments are added to 'extra'.  Their names are not valid in the
+// DSL, so they cannot clash with the user's.
+fn variadic_args(stmts: &mut Vec<Stmt>, variadic: &Vec<(String, usize)>,
+                 extra: &mut Vec<FreeVarDecl>) {
+	fn expand(ex: &mut Expr, variadic: &Vec<(String, usize)>,
+	          extra: &mut Vec<FreeVarDecl>) {
+		match *ex {
+			Expr::Call(ref fname, ref mut args) => {
+				for arg in args.iter_mut() {
+					expand(arg, variadic, extra);
+				}
+				if !variadic.contains(&(fname.clone(), args.len())) {
+					return;
+				}
+				let n = extra.len()/2 + 1;
+				let int = DeclType::Basic(Type::Builtin(Native::I32));
+				let string = DeclType::Basic(Type::Pointer(Box::new(
+					Type::Builtin(Native::Character))));
+				for &(suffix, ref gen, ref ty) in [("int", "std:I32", int),
+				                                   ("str", "std:cstring", string)]
+					.iter() {
+					let nm = format!("{}:va{}:{}", fname, n, suffix);
+					args.push(Expr::VarRef(UOp::None, nm.clone()));
+					extra.push(FreeVarDecl::new(&nm, gen, ty.clone()));
+				}
+			},
+			Expr::Compound(ref mut lhs, _, ref mut rhs) => {
+				expand(lhs, variadic, extra);
+				expand(rhs, variadic, extra);
+			},
+			_ => (),
+		};
+	}
+	for stmt in stmts.iter_mut() {
+		match *stmt {
+			Stmt::Basic(ref mut ex) | Stmt::Verify(ref mut ex) |
+			Stmt::Constraint(ref mut ex) => expand(ex, variadic, extra),
+			Stmt::Assignment(_, ref mut rhs) => expand(rhs, variadic, extra),
+			Stmt::If(ref mut ex, ref mut body) |
+			Stmt::While(ref mut ex, ref mut body) => {
+				expand(ex, variadic, extra);
+				variadic_args(body, variadic, extra);
+			},
+			Stmt::Repeat(_, _, ref mut body) => variadic_args(body, variadic, extra),
+			Stmt::Guarded(_, ref mut inner) => {
+				let mut body = vec![(**inner).clone()];
+				variadic_args(&mut body, variadic, extra);
+				**inner = body.pop().unwrap();
+			},
+			Stmt::Declaration(_) => (),
+		};
+	}
+}
+
+// Collects the names of the functions called in 'ex'.
+fn called_functions(ex: &Expr, calls: &mut Vec<String>) {
+	match *ex {
+		Expr::Call(ref fname, ref args) => {
+			calls.push(fname.clone());
+			for arg in args.iter() {
+				called_functions(arg, calls);
+			}
+		},
+		Expr::Compound(ref lhs, _, ref rhs) => {
+			called_functions(lhs, calls);
+			called_functions(rhs, calls);
+		},
+		Expr::VarRef(_, _) | Expr::VarOps(_, _) | Expr::IConst(_) |
+		Expr::FConst(_) | Expr::Field(_, _) | Expr::ArrowField(_, _) => (),
+	};
+}
+
+// Collects the variables of the "repeat" loops in 'stmts'.
+fn loop_variables(stmts: &Vec<Stmt>, vars: &mut Vec<String>) {
+	for stmt in stmts.iter() {
+		match *stmt {
+			Stmt::Repeat(_, ref var, ref body) => {
+				vars.push(var.clone().unwrap());
+				loop_variables(body, vars);
+			},
+			Stmt::If(_, ref body) | Stmt::While(_, ref body) => {
+				loop_variables(body, vars);
+			},
+			_ => (),
+		};
+	}
+}
+
+// The parser cannot know the fields of a struct that a pointer points to, so
+// it gives a "fake" struct without any; likewise for enums.  This swaps in the
+// declared types, for e.g. functions that return a "pointer struct foo".
+fn complete_type(ty: &Type, types: &Vec<Type>) -> Type {
+	match *ty {
+		Type::Pointer(ref inner) =>
+			Type::Pointer(Box::new(complete_type(inner, types))),
+		Type::Qualified(ref ptr, ref quals) =>
+			Type::Qualified(Box::new(complete_type(ptr, types)), quals.clone()),
+		Type::Struct(ref nm, ref flds, _) if flds.is_empty() => {
+			types.iter().find(|t| match **t {
+				Type::Struct(ref tgt, _, _) => tgt == nm,
+				_ => false,
+			}).unwrap_or(ty).clone()
+		},
+		Type::Enum(ref nm, ref values) if values.is_empty() => {
+			types.iter().find(|t| match **t {
+				Type::Enum(ref tgt, _) => tgt == nm,
+				_ => false,
+			}).unwrap_or(ty).clone()
+		},
+		_ => ty.clone(),
+	}
+}
+
+// Like type_from_decl, but errors on references to undeclared types instead
+// of panicking.
+fn resolve_decl(decl: &DeclType, types: &Vec<Type>) -> Result<Type, String> {
+	match *decl {
+		DeclType::StructRef(ref nm) => {
+			let known = types.iter().any(|t| match *t {
+				Type::Struct(ref tgt, _, _) => tgt == nm,
+				_ => false,
+			});
+			if !known {
+				return Err(format!("unknown struct '{}'", nm));
+			}
+		},
+		DeclType::EnumRef(ref nm) => {
+			let known = types.iter().any(|t| match *t {
+				Type::Enum(ref tgt, _) => tgt == nm,
+				_ => false,
+			});
+			if !known {
+				return Err(format!("unknown enum '{}'", nm));
+			}
+		},
+		DeclType::Struct(_, ref flds, _) => {
+			for f in flds.iter() {
+				try!(resolve_decl(&f.ty, types));
+			}
+		},
+		DeclType::Basic(_) | DeclType::Enum(_, _) | DeclType::Flags(_, _) => (),
+	};
+	Ok(type_from_decl(decl, types))
+}
+
+// gives the type from the declaration.
+// it needs to take the current type list as well, because this DeclType may
+// reference other types, and it would need to produce boxes to those types.
+fn type_from_decl(decl: &DeclType, types: &Vec<Type>) -> Type {
+	match decl {
+		&DeclType::Basic(ref ty) => complete_type(ty, types),
+		&DeclType::Struct(ref snm, ref flds, packed) => {
+			let mut flds_rv: Vec<Field> = Vec::new();
+			for f in flds {
+				match f.ty {
+					DeclType::Basic(ref ty) =>
+						flds_rv.push((f.name.clone(), Box::new(ty.clone()), f.bits)),
+					DeclType::Struct(_, _, _) => {
+						// correct?
+						let subtype = type_from_decl(&f.ty, types);
+						flds_rv.push((f.name.clone(), Box::new(subtype), None));
+					},
+					DeclType::Enum(_, _) | DeclType::Flags(_, _) => unreachable!(),
+					DeclType::StructRef(ref nm) => {
+						for t in types {
+							match t {
+								&Type::Struct(ref tgt, _, _) if *nm==*tgt => {
+									flds_rv.push((f.name.clone(), Box::new(t.clone()),
+									              None));
+									break;
+								},
+								_ => (),
+							}
+						}
+					},
+					DeclType::EnumRef(_) => {
+						let enm = type_from_decl(&f.ty, types);
+						flds_rv.push((f.name.clone(), Box::new(enm), None));
+					},
+				}
+			}
+			Type::Struct(snm.clone(), flds_rv, packed)
+		},
+		&DeclType::Enum(ref enm, ref evalues) |
+		&DeclType::Flags(ref enm, ref evalues) => {
+			Type::Enum(enm.clone(), evalues.clone())
+		},
+		&DeclType::StructRef(ref nm) => {
+			let mut rv: Type = Type::Builtin(Native::Void);
+			for typex in types {
+				match typex {
+					&Type::Struct(ref strct, _, _) if strct == nm => rv = typex.clone(),
+					_ => {},
+				};
+			}
+			// Didn't find it?  Then bail, unknown type!
+			if rv == Type::Builtin(Native::Void) {
+				panic!("Unknown struct '{}'!", nm);
+			}
+			rv
+		}
+		&DeclType::EnumRef(ref nm) => {
+			let mut rv: Type = Type::Builtin(Native::Void);
+			for typex in types {
+				match typex {
+					&Type::Enum(ref enm, _) if enm == nm => rv = typex.clone(),
+					&Type::Enum(ref enm, _) => {
+						println!("Enum '{}' is not a match for '{}'", enm, nm);
+					}
+					_ => {},
+				};
+			}
+			// Didn't find it?  Then bail, unknown type!
+			if rv == Type::Builtin(Native::Void) {
+				panic!("Unknown enum '{}'!", nm);
+			}
+			rv
+		},
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std;
+	use api;
+	use fuzz;
+	use tempdir;
+	use util;
+	use typ::{EnumValue, Native, Type};
+
+	#[test]
+	fn empty_struct() {
+		let s = "struct entry { }";
+		assert!(fuzz::parse_LDeclarations(s).is_ok());
+		assert_eq!(fuzz::parse_LDeclarations(s).unwrap().len(), 1);
+		let ref decl: api::Declaration = fuzz::parse_LDeclarations(s).unwrap()[0];
+		let decl = match decl {
+			&api::Declaration::UDT(ref udt) => udt,
+			_ => panic!("invalid declaration parse {:?}", decl),
+		};
+		use api::DeclType;
+		match decl {
+			&DeclType::Basic(_) => panic!("type should be Struct, is Basic"),
+			&DeclType::Enum(_, _) => panic!("type should be Struct, is Enum"),
+			&DeclType::Flags(_, _) => panic!("type should be Struct, is Flags"),
 			&DeclType::EnumRef(_) => panic!("type should be Struct, is EnumRef"),
 			&DeclType::StructRef(_) => panic!("type should be Struct, is StructRef"),
-			&DeclType::Struct(ref nm, ref decllist) => {
+			&DeclType::Struct(ref nm, ref decllist, _) => {
 				assert_eq!(*nm, "entry".to_string());
 				assert_eq!(decllist.len(), 0)
 			},
@@ -622,227 +3732,3254 @@ mod test {
 	}
 
 	#[test]
-	fn struct_pointer_char() {
-		let s = "struct Ent { pointer char key; }";
-		assert!(fuzz::parse_LDeclarations(s).is_ok());
-		assert_eq!(fuzz::parse_LDeclarations(s).unwrap().len(), 1);
-		let ref decl: api::Declaration = fuzz::parse_LDeclarations(s).unwrap()[0];
-		let decl = match decl {
-			&api::Declaration::UDT(ref udt) => udt,
-			_ => panic!("invalid declaration parse {:?}", decl),
-		};
-		use api::DeclType;
-		match decl {
-			&DeclType::Basic(_) => panic!("type should be UDT, is Basic"),
-			&DeclType::Enum(_, _) => panic!("type should be UDT, is Enum"),
-			&DeclType::EnumRef(_) => panic!("type should be UDT, is EnumRef"),
-			&DeclType::StructRef(_) => panic!("type should be UDT, is StructRef"),
-			&DeclType::Struct(ref nm, ref decllist) => {
-				assert_eq!(*nm, "Ent".to_string());
-				assert_eq!(decllist.len(), 1);
-				let ref key: api::UDTDecl = decllist[0];
-				assert_eq!(key.name, "key");
-				match key.ty {
-					api::DeclType::Struct(_, _) => panic!("incorrect type UDT for 'key'"),
-					api::DeclType::Enum(_, _) => panic!("incorrect type Enum for 'key'"),
-					api::DeclType::EnumRef(_) => panic!("incorrect type for 'key'"),
-					api::DeclType::StructRef(_) => panic!("incorrect type for 'key'"),
-					api::DeclType::Basic(ref blt) => {
-						let ch = Type::Builtin(Native::Character);
-						assert_eq!(blt, &Type::Pointer(Box::new(ch)));
-					}
-				}
-			},
+	fn struct_pointer_char() {
+		let s = "struct Ent { pointer char key; }";
+		assert!(fuzz::parse_LDeclarations(s).is_ok());
+		assert_eq!(fuzz::parse_LDeclarations(s).unwrap().len(), 1);
+		let ref decl: api::Declaration = fuzz::parse_LDeclarations(s).unwrap()[0];
+		let decl = match decl {
+			&api::Declaration::UDT(ref udt) => udt,
+			_ => panic!("invalid declaration parse {:?}", decl),
+		};
+		use api::DeclType;
+		match decl {
+			&DeclType::Basic(_) => panic!("type should be UDT, is Basic"),
+			&DeclType::Enum(_, _) => panic!("type should be UDT, is Enum"),
+			&DeclType::Flags(_, _) => panic!("type should be UDT, is Flags"),
+			&DeclType::EnumRef(_) => panic!("type should be UDT, is EnumRef"),
+			&DeclType::StructRef(_) => panic!("type should be UDT, is StructRef"),
+			&DeclType::Struct(ref nm, ref decllist, _) => {
+				assert_eq!(*nm, "Ent".to_string());
+				assert_eq!(decllist.len(), 1);
+				let ref key: api::UDTDecl = decllist[0];
+				assert_eq!(key.name, "key");
+				match key.ty {
+					api::DeclType::Struct(_, _, _) =>
+						panic!("incorrect type UDT for 'key'"),
+					api::DeclType::Enum(_, _) => panic!("incorrect type Enum for 'key'"),
+					api::DeclType::Flags(_, _) =>
+						panic!("incorrect type Flags for 'key'"),
+					api::DeclType::EnumRef(_) => panic!("incorrect type for 'key'"),
+					api::DeclType::StructRef(_) => panic!("incorrect type for 'key'"),
+					api::DeclType::Basic(ref blt) => {
+						let ch = Type::Builtin(Native::Character);
+						assert_eq!(blt, &Type::Pointer(Box::new(ch)));
+					}
+				}
+			},
+		};
+	}
+
+	#[test]
+	fn struct_multiple_fields() {
+		let s = "struct Entry {\n".to_string() +
+			"pointer char key;\n" +
+			"pointer void value;\n" +
+		"}";
+		assert!(fuzz::parse_LDeclarations(s.as_str()).is_ok());
+		assert_eq!(fuzz::parse_LDeclarations(s.as_str()).unwrap().len(), 1);
+		let ref decl: api::Declaration =
+			fuzz::parse_LDeclarations(s.as_str()).unwrap()[0];
+		let decl = match decl {
+			&api::Declaration::UDT(ref udt) => udt,
+			_ => panic!("invalid declaration parse {:?}", decl),
+		};
+		use api::DeclType;
+		match decl {
+			&DeclType::Basic(_) => panic!("type should be UDT, is Basic"),
+			&DeclType::Enum(_, _) => panic!("type should be UDT, is Enum"),
+			&DeclType::Flags(_, _) => panic!("type should be UDT, is Flags"),
+			&DeclType::EnumRef(_) => panic!("type should be UDT, is EnumRef"),
+			&DeclType::StructRef(_) => panic!("type should be UDT, is StructRef"),
+			&DeclType::Struct(ref nm, ref decllist, _) => {
+				assert_eq!(*nm, "Entry".to_string());
+				assert_eq!(decllist.len(), 2);
+				let ref key: api::UDTDecl = decllist[0];
+				assert_eq!(key.name, "key");
+				match key.ty {
+					DeclType::Struct(_, _, _) =>
+						panic!("incorrect type UDT for 'key'"),
+					DeclType::Enum(_, _) => panic!("incorrect type Enum for 'key'"),
+					DeclType::Flags(_, _) => panic!("incorrect type Flags for 'key'"),
+					DeclType::EnumRef(_) => panic!("incorrect type for 'key'"),
+					DeclType::StructRef(_) => panic!("incorrect type for 'key'"),
+					DeclType::Basic(ref blt) => {
+						let ch = Type::Builtin(Native::Character);
+						assert_eq!(blt, &Type::Pointer(Box::new(ch)));
+					}
+				}
+				let ref value: api::UDTDecl = decllist[1];
+				assert_eq!(value.name, "value");
+				match value.ty {
+					DeclType::Struct(_, _, _) =>
+						panic!("incorrect type UDT for 'key'"),
+					DeclType::Enum(_, _) => panic!("incorrect type Enum for 'key'"),
+					DeclType::Flags(_, _) => panic!("incorrect type Flags for 'key'"),
+					DeclType::EnumRef(_) => panic!("incorrect type for 'key'"),
+					DeclType::StructRef(_) => panic!("incorrect type for 'key'"),
+					DeclType::Basic(ref blt) => {
+						let ch = Type::Builtin(Native::Void);
+						assert_eq!(blt, &Type::Pointer(Box::new(ch)));
+					}
+				}
+			},
+		};
+	}
+
+	#[test]
+	fn enum_single() {
+		let s = "enum Enumeration { BLAH = 0 , }";
+		match fuzz::parse_LDeclarations(s) {
+			Ok(_) => {},
+			Err(e) => panic!("{:?}", e),
+		};
+		let t = "enum Enumeration { BLA = 0 , }";
+		assert!(fuzz::parse_LDeclarations(t).is_ok());
+		assert_eq!(fuzz::parse_LDeclarations(t).unwrap().len(), 1);
+	}
+
+	#[test]
+	fn enum_multi() {
+		let s = "enum Enumeration { FOO = 0 , BAR = 1 , BAZ = 42 , }";
+		let decls = match fuzz::parse_LDeclarations(s) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		assert_eq!(decls.len(), 1);
+	}
+
+	// pulls the list of enumerators out of a single parsed enum declaration.
+	fn enum_values(s: &str) -> Vec<EnumValue> {
+		let decls = match fuzz::parse_LDeclarations(s) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		assert_eq!(decls.len(), 1);
+		match decls[0] {
+			api::Declaration::UDT(api::DeclType::Enum(_, ref v)) => v.clone(),
+			_ => panic!("invalid declaration parse {:?}", decls[0]),
+		}
+	}
+
+	#[test]
+	fn enum_implicit_values() {
+		let values = enum_values("enum E { A, B, C, }");
+		let nums: Vec<i64> = values.iter().map(|v| v.1).collect();
+		assert_eq!(nums, vec![0, 1, 2]);
+		assert_eq!(values[2], ("C".to_string(), 2, "2".to_string()));
+	}
+
+	#[test]
+	fn enum_explicit_values() {
+		let values = enum_values("enum E { A = 4, B = 2, C = 9, }");
+		let nums: Vec<i64> = values.iter().map(|v| v.1).collect();
+		assert_eq!(nums, vec![4, 2, 9]);
+	}
+
+	#[test]
+	fn enum_mixed_values() {
+		let values = enum_values("enum E { A, B = 5, C, D = 0x10, E, }");
+		let nums: Vec<i64> = values.iter().map(|v| v.1).collect();
+		assert_eq!(nums, vec![0, 5, 6, 16, 17]);
+	}
+
+	#[test]
+	fn enum_hex_char() {
+		let values = enum_values("enum F { A = 0x10, B = 'z', C = '\\n', }");
+		assert_eq!(values[0], ("A".to_string(), 16, "0x10".to_string()));
+		assert_eq!(values[1], ("B".to_string(), 122, "'z'".to_string()));
+		assert_eq!(values[2], ("C".to_string(), 10, "'\\n'".to_string()));
+
+		// by value, the generator emits the values as they were declared.
+		use variable::{GenEnum, Generator};
+		let enm = Type::Enum("F".to_string(), values);
+		assert_eq!(GenEnum::create(&enm).value(), "A");
+		let mut gen = GenEnum::create_by_value(&enm);
+		assert_eq!(gen.value(), "0x10");
+		gen.next();
+		assert_eq!(gen.value(), "'z'");
+
+		let values = enum_values("enum G { A = '\\1', B = -0x8000000000000000, }");
+		assert_eq!(values[0].1, 1);
+		assert_eq!(values[1].1, i64::min_value());
+		for &(s, err) in [
+			("enum G { A = '\\q', }", "unknown escape sequence"),
+			("enum G { A = 0x8000000000000000, }", "error parsing '0x8"),
+			("enum G { A = 9223372036854775808, }", "error parsing '9"),
+			("enum G { A = 9223372036854775807, B, }",
+			 "enumerator 'B' overflows after 'A'"),
+			("enum:flags G { A = 0x7fffffffffffffff, B, }",
+			 "enumerator 'B' overflows after 'A'"),
+			("enum G { A = X + 9223372036854775807, B, }",
+			 "enumerator 'B' overflows after 'A'"),
+		].iter() {
+			match fuzz::parse_LDeclarations(s) {
+				Ok(d) => panic!("parsed {:?}", d),
+				Err(e) => {
+					let msg = util::parse_error(s, e);
+					assert!(msg.starts_with(err), msg);
+				},
+			};
+		}
+	}
+
+	#[test]
+	fn enum_float_rejected() {
+		let s = "enum F { A = 1.5 , }";
+		assert!(fuzz::parse_LDeclarations(s).is_err());
+	}
+
+	#[test]
+	fn parse_flags_enum() {
+		let s = "enum:flags MODE {\n".to_string() +
+			"RD = 1,\n" +
+			"WR = 2,\n" +
+			"EX = 0x10,\n" +
+		"}\n" +
+		"enum KIND { A, B, }\n" +
+		"var:free m gen:MODE enum MODE\n" +
+		"var:free k gen:KIND enum KIND\n";
+		let decls = fuzz::parse_LDeclarations(s.as_str()).unwrap();
+		match decls[0] {
+			api::Declaration::UDT(api::DeclType::Flags(ref nm, ref values)) => {
+				assert_eq!(nm, "MODE");
+				assert_eq!(values[2], ("EX".to_string(), 16, "0x10".to_string()));
+			},
+			_ => panic!("not a flags enum: {:?}", decls[0]),
+		};
+		assert_eq!(decls[0].to_dsl(),
+		           "enum:flags MODE {\n\tRD = 1,\n\tWR = 2,\n\tEX = 0x10,\n}");
+
+		let mut pgm = api::Program::new(&decls, &vec![]);
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let m = pgm.symlookup("m").unwrap();
+		assert_eq!(m.generator.name(), "std:flags:MODE");
+		assert_eq!(m.generator.n_state(), 1 + 3 + 1 + 2);
+		let k = pgm.symlookup("k").unwrap();
+		assert_eq!(k.generator.name(), "std:enum:KIND");
+	}
+
+	#[test]
+	fn struct_fvar_single() {
+		let s = "struct X { } var:free blah gen:I32 i32";
+		let decls = match fuzz::parse_LDeclarations(s) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		assert_eq!(decls.len(), 2);
+	}
+
+	#[test]
+	fn struct_bitfield() {
+		let s = "struct flagged {\n".to_string() +
+			"unsigned flags : 3;\n" +
+			"i32 whole;\n" +
+		"}";
+		let decls = match fuzz::parse_LDeclarations(s.as_str()) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		let udt = match decls[0] {
+			api::Declaration::UDT(ref udt) => udt,
+			ref d => panic!("invalid declaration parse {:?}", d),
+		};
+		match udt {
+			&api::DeclType::Struct(_, ref flds, _) => {
+				assert_eq!(flds.len(), 2);
+				assert_eq!(flds[0].name, "flags");
+				assert_eq!(flds[0].bits, Some(3));
+				assert_eq!(flds[1].bits, None);
+			},
+			_ => panic!("type should be Struct, is {:?}", udt),
+		};
+		// ... and the width makes it to the analyzed type.
+		match api::type_from_decl(udt, &vec![]) {
+			Type::Struct(_, ref flds, _) => {
+				assert_eq!(*flds[0].1, Type::Builtin(Native::Unsigned));
+				assert_eq!(flds[0].2, Some(3));
+				assert_eq!(flds[1].2, None);
+			},
+			ty => panic!("struct decl became {:?}", ty),
+		};
+
+		for &(fld, err) in [
+			("unsigned flags : 0;", "bitfield 'flags' must have a nonzero width"),
+			("unsigned flags : -1;", "error parsing bitfield width '-1'"),
+		].iter() {
+			let s = format!("struct flagged {{ {} }}", fld);
+			match fuzz::parse_LDeclarations(s.as_str()) {
+				Ok(d) => panic!("parsed {:?}", d),
+				Err(e) => {
+					let msg = util::parse_error(s.as_str(), e);
+					assert!(msg.starts_with(err), msg);
+				},
+			};
+		}
+	}
+
+	#[test]
+	fn restrict_pointer() {
+		let s = "var:free p gen:ptr restrict pointer int\n".to_string() +
+			"function:decl fill void { aligned(16) pointer int, }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		use typ::{Name, Qualifier};
+		let intp = Type::Pointer(Box::new(Type::Builtin(Native::Integer)));
+		let p = pgm.symlookup("p").unwrap();
+		assert_eq!(p.typ, Type::Qualified(Box::new(intp.clone()),
+		                                  vec![Qualifier::Restrict]));
+		let fill = pgm.funlookup("fill").unwrap();
+		assert_eq!(fill.parameters[0].name(),
+		           "int* __attribute__((aligned(16)))");
+		// the qualifiers carry through to the declaration, but not the cast.
+		assert_eq!(p.generator.decl("p"), "int* restrict p = (int *)0ull");
+
+		for &(ty, err) in [
+			("restrict int", "qualifier Restrict given for non-pointer"),
+			("aligned(3) pointer int", "alignment must be a positive power of \
+			                            two, not 3"),
+			("aligned(0) pointer int", "not 0"),
+		].iter() {
+			let s = format!("function:decl fill void {{ {}, }}\n", ty);
+			match fuzz::parse_LDeclarations(s.as_str()) {
+				Ok(d) => panic!("parsed {:?}", d),
+				Err(e) => {
+					let msg = util::parse_error(s.as_str(), e);
+					assert!(msg.contains(err), msg);
+				},
+			};
+		}
+	}
+
+	#[test]
+	fn const_pointee() {
+		let s = "function:decl log_msg int { pointer const char, }\n\
+			function:decl peek int { const pointer int, }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		use typ::Name;
+		let msg = pgm.funlookup("log_msg").unwrap().parameters[0].clone();
+		assert_eq!(msg.name(), "char const*");
+		// a pointer to const chars is still a string.
+		assert_eq!(::variable::generator(&msg).name(), "std:cstring");
+		let peek = pgm.funlookup("peek").unwrap();
+		assert_eq!(peek.parameters[0].name(), "int* const");
+	}
+
+	#[test]
+	fn enum_decl_type() {
+		let s = "enum ACTION { FIND = 0, ENTER = 1, }\n".to_string() +
+			"struct request { enum ACTION act; i32 count; }";
+		let decls = match fuzz::parse_LDeclarations(s.as_str()) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		assert_eq!(decls.len(), 2);
+		let mut types: Vec<Type> = Vec::new();
+		for decl in decls.iter() {
+			match decl {
+				&api::Declaration::UDT(ref udt) =>
+					types.push(api::type_from_decl(udt, &types)),
+				_ => panic!("invalid declaration parse {:?}", decl),
+			};
+		}
+		let values = vec![("FIND".to_string(), 0, "0".to_string()),
+		                  ("ENTER".to_string(), 1, "1".to_string())];
+		let action = Type::Enum("ACTION".to_string(), values);
+		assert_eq!(types[0], action);
+		match types[1] {
+			Type::Struct(ref nm, ref flds, _) => {
+				assert_eq!(nm, "request");
+				assert_eq!(flds[0].0, "act");
+				assert_eq!(*flds[0].1, action);
+				assert_eq!(flds[1].0, "count");
+			},
+			ref t => panic!("struct decl became {:?}", t),
+		};
+	}
+
+	#[test]
+	fn struct_field_names() {
+		let s = "struct Ent { pointer char key; }";
+		let decls = match fuzz::parse_LDeclarations(s) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		let ty = match decls[0] {
+			api::Declaration::UDT(ref udt) => api::type_from_decl(udt, &vec![]),
+			ref d => panic!("invalid declaration parse {:?}", d),
+		};
+		match ty {
+			Type::Struct(_, ref flds, _) => assert_eq!(flds[0].0, "key"),
+			_ => panic!("struct decl became {:?}", ty),
+		};
+		use variable;
+		let gen = variable::generator(&ty);
+		assert!(gen.value().contains(".key = "));
+	}
+
+	#[test]
+	fn struct_field_offset() {
+		let s = "struct hdr { u32 magic @ 0; u8 flags; pointer char name @ 8; }";
+		let decls = match fuzz::parse_LDeclarations(s) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		match decls[0] {
+			api::Declaration::UDT(api::DeclType::Struct(_, ref flds, _)) => {
+				assert_eq!(flds.len(), 3);
+				assert_eq!(flds[0].offset, Some(0));
+				assert_eq!(flds[1].offset, None);
+				assert_eq!(flds[2].name, "name");
+				assert_eq!(flds[2].offset, Some(8));
+			},
+			ref d => panic!("invalid declaration parse {:?}", d),
+		};
+
+		for &(fld, err) in [
+			("u32 magic @ -4;", "error parsing offset '-4' of field 'magic'"),
+			("u32 flags : 3 @ 4;", "bitfield 'flags' cannot have an offset"),
+		].iter() {
+			let s = format!("struct hdr {{ {} }}", fld);
+			match fuzz::parse_LDeclarations(s.as_str()) {
+				Ok(d) => panic!("parsed {:?}", d),
+				Err(e) => {
+					let msg = util::parse_error(s.as_str(), e);
+					assert!(msg.starts_with(err), msg);
+				},
+			};
+		}
+	}
+
+	#[test]
+	fn struct_field_offset_codegen() {
+		let s = "struct hdr { u32 magic; u64 len @ 8; }\n".to_string() +
+			"var:free x gen:std:I32 i32\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &vec!["hdr.h"], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("#include <stddef.h>"));
+		assert!(code.contains("_Static_assert(offsetof(struct hdr, len) == 8, \
+		                       \"offset of hdr.len\");"));
+		assert!(!code.contains("offsetof(struct hdr, magic)"));
+		// the check is outside of main.
+		assert!(code.find("_Static_assert").unwrap() <
+		        code.find("int main()").unwrap());
+	}
+
+	#[test]
+	fn anonymous_struct_member() {
+		let s = "struct Outer { i32 tag; struct { i32 x; i32 y @ 8; }; }";
+		let decls = match fuzz::parse_LDeclarations(s) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		let udt = match decls[0] {
+			api::Declaration::UDT(ref udt) => udt,
+			ref d => panic!("invalid declaration parse {:?}", d),
+		};
+		match *udt {
+			api::DeclType::Struct(_, ref flds, _) => {
+				assert_eq!(flds.len(), 2);
+				assert_eq!(flds[1].name, "");
+				match flds[1].ty {
+					api::DeclType::Struct(ref nm, ref inner, _) => {
+						assert_eq!(nm, "");
+						assert_eq!(inner[1].name, "y");
+					},
+					ref t => panic!("anonymous member parsed as {:?}", t),
+				};
+			},
+			_ => panic!("type should be Struct, is {:?}", udt),
+		};
+		// the members' fields are accessed as if they were the outer struct's.
+		let ty = api::type_from_decl(udt, &vec![]);
+		assert_eq!(ty.field_type("x"), Some(Type::Builtin(Native::I32)));
+		assert_eq!(ty.field_type("tag"), Some(Type::Builtin(Native::I32)));
+		assert_eq!(ty.field_type("z"), None);
+
+		let s = s.to_string() + "\nvar:free o gen:Struct struct Outer\n" +
+			"verify:new o.y > 0\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &vec![], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("offsetof(struct Outer, y) == 8"));
+		assert!(code.contains("assert(o.y > 0);"));
+
+		let s = "struct Outer { i32 tag; struct { i32 x; } @ 4; }";
+		match fuzz::parse_LDeclarations(s) {
+			Ok(d) => panic!("parsed {:?}", d),
+			Err(e) => assert_eq!(util::parse_error(s, e),
+			                     "anonymous members cannot have an offset; give \
+			                      their fields offsets instead"),
+		};
+	}
+
+	#[test]
+	fn parse_function_delete() {
+		let s = "function:new new_table pointer struct table {usize,}\n".to_string()
+			+ "function:delete free_table void {pointer struct table,}\n"
+			+ "function:use lookup i32 {pointer struct table, i32,}\n";
+		let decls: Vec<api::Declaration> = match fuzz::parse_LDeclarations(&s) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		let kinds: Vec<api::FuncKind> = decls.iter().map(|d| match d {
+			&api::Declaration::Function(ref f) => f.kind,
+			_ => panic!("non function type {:?}", d),
+		}).collect();
+		assert_eq!(kinds, vec![api::FuncKind::Constructor,
+		                       api::FuncKind::Destructor,
+		                       api::FuncKind::Operation]);
+	}
+
+	#[test]
+	fn leaked_resource_flagged() {
+		let decls = "var:free n gen:Usize usize\n".to_string() +
+			"var:free t gen:ptr pointer struct table\n" +
+			"var:free u gen:ptr pointer struct table\n" +
+			"function:new new_table pointer struct table {usize,}\n" +
+			"function:delete free_table void {pointer struct table,}\n";
+		let s = decls.clone() +
+			"t = function:call new_table { n }\n" +
+			"u = function:call new_table { n }\n" +
+			"function:call free_table { u }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let leaks = pgm.leaked_resources();
+		assert_eq!(leaks.len(), 1);
+		assert!(leaks[0].contains("'t'"));
+
+		let s = decls + "t = function:call new_table { n }\n" +
+			"function:call free_table { t }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert!(pgm.leaked_resources().is_empty());
+	}
+
+	#[test]
+	fn cleanup_deletes_live_resources() {
+		let s = "struct handle { i32 fd; }\n".to_string() +
+			"var:free n gen:Usize usize\n" +
+			"var:constrained t pointer struct table\n" +
+			"var:constrained u pointer struct table\n" +
+			"var:constrained v pointer struct table\n" +
+			"var:constrained h struct handle\n" +
+			"function:new new_table pointer struct table {usize,}\n" +
+			"function:delete free_table void {pointer struct table,}\n" +
+			"function:new open_handle void {out pointer struct handle,}\n" +
+			"function:delete close_handle void {pointer struct handle,}\n" +
+			"t = function:call new_table { n }\n" +
+			"u = function:call new_table { n }\n" +
+			"function:call open_handle { &h }\n" +
+			"v = function:call new_table { n }\n" +
+			"function:call free_table { u }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let hdrs: Vec<&str> = vec!["stdlib.h"];
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert_eq!(code.matches("free_table(").count(), 1);
+
+		pgm.set_cleanup(true);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		let lines: Vec<&str> = code.lines().map(|l| l.trim()).collect();
+		let ret = lines.iter().position(|l| *l == "return 0;").unwrap();
+		// in reverse order of creation, and the deleted 'u' is left alone.
+		assert_eq!(&lines[ret-4..ret], &["free_table(v);", "close_handle(&h);",
+		                                 "free_table(t);", ""]);
+		assert_eq!(code.matches("free_table(u);").count(), 1);
+
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_numbered_functions(0..1, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("free_table(t);\n\treturn 0;"), code);
+	}
+
+	#[test]
+	fn parse_bool_types() {
+		use typ::Name;
+		let s = "function:decl f bool {_Bool, pointer bool,}";
+		let decls = fuzz::parse_LDeclarations(s).unwrap();
+		let fqn = match decls[0] {
+			api::Declaration::Function(ref f) => f.to_function(&vec![]).unwrap(),
+			_ => panic!("non function type {:?}", decls[0]),
+		};
+		let boolean = Type::Builtin(Native::Boolean);
+		assert_eq!(fqn.retval, boolean);
+		assert_eq!(fqn.parameters, vec![boolean.clone(), Type::Pointer(
+			Box::new(boolean.clone()))]);
+		assert_eq!(Native::Boolean.name(), "bool");
+		assert_eq!(decls[0].to_dsl(), "function:decl f bool {bool, pointer bool,}");
+
+		let s = "var:free b gen:std:bool bool\n".to_string() +
+			"function:decl set void {bool,} precondition b == (bool)1;\n" +
+			"function:call set { b }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let hdrs: Vec<&str> = vec!["stdlib.h"];
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(1, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("#include <stdbool.h>\n"), code);
+		assert!(code.contains("bool b = 1;"), code);
+
+		// no bool, no stdbool.h.
+		let s = "var:free x gen:std:I32 i32\n".to_string() +
+			"function:decl set void {i32,}\n" +
+			"function:call set { x }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(!code.contains("stdbool.h"), code);
+	}
+
+	#[test]
+	fn parse_c_integer_types() {
+		use typ::{DataModel, Name};
+		let s = "function:decl f long {".to_string() +
+			"long long, unsigned long, short,\n" +
+			"unsigned short, unsigned long long, unsigned,}";
+		let decls = fuzz::parse_LDeclarations(s.as_str()).unwrap();
+		let fqn = match decls[0] {
+			api::Declaration::Function(ref f) => f.to_function(&vec![]).unwrap(),
+			_ => panic!("non function type {:?}", decls[0]),
+		};
+		assert_eq!(fqn.retval, Type::Builtin(Native::Long));
+		let params: Vec<Native> = fqn.parameters.iter().map(|p| match *p {
+			Type::Builtin(n) => n,
+			_ => panic!("non builtin parameter {:?}", p),
+		}).collect();
+		assert_eq!(params, vec![Native::LongLong, Native::ULong, Native::Short,
+		                        Native::UShort, Native::ULongLong,
+		                        Native::Unsigned]);
+		let names: Vec<String> = params.iter().map(|p| p.name()).collect();
+		assert_eq!(names, vec!["long long", "unsigned long", "short",
+		                       "unsigned short", "unsigned long long",
+		                       "unsigned"]);
+		assert_eq!(decls[0].to_dsl(), "function:decl f long {long long, \
+		           unsigned long, short, unsigned short, unsigned long long, \
+		           unsigned,}");
+
+		// whether a long is 64 bits depends on the target.
+		assert_eq!(Native::Long.fixed(DataModel::LP64), Native::I64);
+		assert_eq!(Native::Long.fixed(DataModel::LLP64), Native::I32);
+		assert_eq!(Native::ULong.fixed(DataModel::ILP32), Native::U32);
+		assert_eq!(Native::LongLong.fixed(DataModel::ILP32), Native::I64);
+		assert_eq!(Native::Short.fixed(DataModel::default()), Native::I16);
+	}
+
+	#[test]
+	fn parse_function_new() {
+		let s = "function:decl hcreate_r int {usize, pointer struct hsearch_data,}";
+		let decls: Vec<api::Declaration> = match fuzz::parse_LDeclarations(s) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		assert_eq!(decls.len(), 1);
+		let fqn = match decls[0] {
+			api::Declaration::Function(ref f) => f,
+			_ => panic!("non function type {:?}", decls[0]),
+		};
+		assert_eq!(fqn.name, "hcreate_r");
+		match fqn.retval {
+			api::DeclType::Basic(ref ty) => match ty {
+				&Type::Builtin(ref t) => assert_eq!(*t, Native::Integer),
+				_ => panic!("basic type, but {:?}, not integer", ty),
+			},
+			_ => panic!("retval should be a basic type, not {:?}", fqn.retval),
+		};
+		assert_eq!(fqn.parameters.len(), 2);
+		match fqn.parameters[0] {
+			api::DeclType::Basic(ref ty) => match ty {
+				&Type::Builtin(ref t) => assert_eq!(*t, Native::Usize),
+				_ => panic!("basic type, but {:?} not usize", ty),
+			},
+			_ => panic!("arg0 should be a basic type, not {:?}", fqn.parameters[0]),
+		};
+		let ptr: &Type = match fqn.parameters[1] {
+			api::DeclType::Basic(ref ptr) => ptr,
+			_ => panic!("invalid arg1: {:?}", fqn.parameters[1]),
+		};
+		let boxptr = match ptr {
+			&Type::Pointer(ref b) => b,
+			_ => panic!("invalid ptr type {:?}", ptr),
+		};
+		use std::ops::Deref;
+		match boxptr.deref() {
+			&Type::Struct(ref nm, _, _) => assert_eq!(nm, "hsearch_data"),
+			_ => panic!("invalid box ptr {:?}", boxptr),
+		};
+	}
+
+	#[test]
+	fn parse_function_no_arguments() {
+		let s = "function:decl getpid int {}";
+		let decls: Vec<api::Declaration> = match fuzz::parse_LDeclarations(s) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{}", util::parse_error(s, e)),
+		};
+		assert_eq!(decls.len(), 1);
+		match decls[0] {
+			api::Declaration::Function(ref f) => {
+				assert_eq!(f.name, "getpid");
+				assert!(f.parameters.is_empty());
+			},
+			_ => panic!("non function type {:?}", decls[0]),
+		};
+	}
+
+	#[test]
+	fn parse_function_bad_arguments() {
+		let s = "function:decl f int {int,,}";
+		match fuzz::parse_LDeclarations(s) {
+			Ok(d) => panic!("parsed an empty argument: {:?}", d),
+			Err(e) => {
+				let msg = util::parse_error(s, e);
+				assert!(msg.starts_with("line 1, column 26: unexpected ','"), msg);
+			},
+		};
+
+		let s = "function:decl f int {\n\tint";
+		match fuzz::parse_LDeclarations(s) {
+			Ok(d) => panic!("parsed an unterminated argument list: {:?}", d),
+			Err(e) => {
+				let msg = util::parse_error(s, e);
+				assert!(msg.starts_with("line 2, column 5: unexpected end of input"),
+				        msg);
+				assert!(msg.contains("\",\""), msg);
+			},
+		};
+	}
+
+	#[test]
+	fn struct_returning_functions() {
+		let s = "struct foo { i32 x; }\n".to_string() +
+			"var:constrained p pointer struct foo\n" +
+			"var:constrained v struct foo\n" +
+			"function:new make pointer struct foo {}\n" +
+			"function:decl get struct foo {pointer struct foo,}\n" +
+			"function:delete release void {pointer struct foo,}\n" +
+			"p = function:call make {}\n" +
+			"v = function:call get { p }\n" +
+			"function:call release { p }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
+		};
+		match pgm.declarations[3] {
+			api::Declaration::Function(ref f) => {
+				let fake = Type::Struct("foo".to_string(), vec![], false);
+				let ptr = api::DeclType::Basic(Type::Pointer(Box::new(fake)));
+				assert_eq!(f.retval, ptr);
+			},
+			ref d => panic!("not a function: {:?}", d),
+		};
+		match pgm.declarations[4] {
+			api::Declaration::Function(ref f) =>
+				assert_eq!(f.retval, api::DeclType::StructRef("foo".to_string())),
+			ref d => panic!("not a function: {:?}", d),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+
+		// both resolve to the complete struct, fields and all.
+		let foo = Type::Struct("foo".to_string(), vec![
+			("x".to_string(), Box::new(Type::Builtin(Native::I32)), None),
+		], false);
+		let make = pgm.funlookup("make").unwrap();
+		assert_eq!(make.retval, Type::Pointer(Box::new(foo.clone())));
+		let get = pgm.funlookup("get").unwrap();
+		assert_eq!(get.retval, foo);
+		assert_eq!(pgm.symlookup("p").unwrap().typ,
+		           Type::Pointer(Box::new(foo.clone())));
+		assert_eq!(pgm.symlookup("v").unwrap().typ, foo);
+
+		let mut code: Vec<u8> = Vec::new();
+		pgm.codegen(&mut code).unwrap();
+		let code = String::from_utf8(code).unwrap();
+		assert!(code.contains("\tstruct foo* p = "), code);
+		assert!(code.contains("\tstruct foo v = "), code);
+		assert!(code.contains("\tp = make();\n"), code);
+		assert!(code.contains("\tv = get(p);\n"), code);
+		assert!(pgm.leaked_resources().is_empty());
+	}
+
+	#[test]
+	fn function_from_declaration() {
+		let s = "struct hsearch_data { pointer void table; }\n".to_string() +
+			"function:decl hcreate_r int {usize, pointer struct hsearch_data,}";
+		let decls: Vec<api::Declaration> =
+			match fuzz::parse_LDeclarations(s.as_str()) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		let hsd = match decls[0] {
+			api::Declaration::UDT(ref udt) => api::type_from_decl(udt, &vec![]),
+			_ => panic!("non UDT {:?}", decls[0]),
+		};
+		let fqn = match decls[1] {
+			api::Declaration::Function(ref f) => f,
+			_ => panic!("non function type {:?}", decls[1]),
+		};
+		let func = fqn.to_function(&vec![hsd.clone()]).unwrap();
+		assert_eq!(func.name, "hcreate_r");
+		assert_eq!(func.retval, Type::Builtin(Native::Integer));
+		assert_eq!(func.parameters, vec![
+			Type::Builtin(Native::Usize),
+			Type::Pointer(Box::new(hsd)),
+		]);
+
+		let unknown = api::FuncDecl::new("f", api::DeclType::Basic(
+			Type::Builtin(Native::Void)), vec![
+			api::DeclType::StructRef("nothere".to_string()),
+		], api::FuncKind::Operation);
+		assert_eq!(unknown.to_function(&vec![]).unwrap_err(),
+		           "unknown struct 'nothere'");
+	}
+
+	#[test]
+	fn parse_two_function_decls() {
+		let s = "function:decl hcreate_r int {".to_string() +
+			"usize, pointer struct hsearch_data," +
+		"}" +
+		"function:decl hsearch_r int {" +
+			"int, int, pointer pointer int, pointer struct hsearch_data," +
+		"}";
+		let decls: Vec<api::Declaration> =
+			match fuzz::parse_LDeclarations(s.as_str()) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		assert_eq!(decls.len(), 2);
+		let fqn = match decls[0] {
+			api::Declaration::Function(ref f) => f,
+			_ => panic!("non function type {:?}", decls[0]),
+		};
+		assert_eq!(fqn.name, "hcreate_r");
+	}
+
+	#[test]
+	fn opaque_struct_in_function() {
+		let s = "struct hsearch_data {}\n".to_string() +
+		"var:free tbl gen:opaque struct hsearch_data\n" +
+		"function:decl hcreate_r int {" +
+			"usize, pointer struct hsearch_data,\n" +
+		"}\n";
+		let decls: Vec<api::Declaration> =
+			match fuzz::parse_LDeclarations(s.as_str()) {
+			Ok(parsed) => parsed,
+			Err(e) => panic!("{:?}", e),
+		};
+		assert_eq!(decls.len(), 3);
+		// should assert that the hcreate_r's 2nd arg == types[0].
+	}
+
+	#[test]
+	fn compound_expr() {
+		let s = "var:free x gen:std:I32 i32\n".to_string() +
+			"var:free y gen:std:I32 i32\n" +
+			"constraint:new x > 0 && y < 0\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+	}
+
+	#[test]
+	fn field_expr() {
+		let s = "struct Entry {\n".to_string() +
+				"pointer char key;\n" +
+				"pointer void value;\n" +
+			"}\n" +
+			"var:free e gen:opaque struct Entry\n" +
+			"e.value = 0\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+	}
+
+	#[test]
+	fn sizeof_argument() {
+		let s = "struct hdr { i32 magic; }\n".to_string() +
+			"var:free buf gen:Struct struct hdr\n" +
+			"function:decl parse i32 {pointer struct hdr, usize,}\n" +
+			"function:call parse { & buf sizeof(buf) }\n" +
+			"function:call parse { & buf op:sizeof buf }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		let calls: Vec<&str> =
+			code.lines().filter(|l| l.contains("parse(")).collect();
+		assert_eq!(calls, vec!["\tparse(&buf, sizeof(buf));"; 2]);
+	}
+
+	#[test]
+	fn parse_repeat() {
+		let s = "function:decl grow void {}\n".to_string() +
+			"repeat 100 { function:call grow {} }\n" +
+			"repeat 3 as k { function:call grow {} function:call grow {} }\n";
+		let pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		assert_eq!(pgm.ast.len(), 2);
+		match pgm.ast[0] {
+			api::Stmt::Repeat(100, None, ref body) => assert_eq!(body.len(), 1),
+			ref st => panic!("invalid repeat parse {:?}", st),
+		};
+		match pgm.ast[1] {
+			api::Stmt::Repeat(3, Some(ref var), ref body) => {
+				assert_eq!(var, "k");
+				assert_eq!(body.len(), 2);
+			},
+			ref st => panic!("invalid repeat parse {:?}", st),
+		};
+
+		for n in ["-1", "18446744073709551616"].iter() {
+			let s = format!("function:decl grow void {{}}\n\
+			                 repeat {} {{ function:call grow {{}} }}\n", n);
+			match fuzz::parse_LProgram(s.as_str()) {
+				Ok(_) => panic!("parsed a repeat count of {}", n),
+				Err(e) => {
+					let msg = util::parse_error(s.as_str(), e);
+					let err = format!("error parsing repeat count '{}'", n);
+					assert!(msg.starts_with(&err), msg);
+				},
+			};
+		}
+	}
+
+	#[test]
+	fn repeat_codegen() {
+		// the user's 'i' takes the default name, so the loop gets another one.
+		let s = "var:free i gen:std:I32 i32\n".to_string() +
+			"function:decl put void {usize,}\n" +
+			"repeat 100 { function:call put { i_2 } }\n" +
+			"repeat 2 as n { repeat 5 as m { function:call put { m } } }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		// loop variables do not add states.
+		assert_eq!(pgm.n_states(), pgm.symlookup("i").unwrap().generator.n_state());
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("for(size_t i_2 = 0; i_2 < 100; ++i_2) {\n\
+		                       \tput(i_2);\n}"));
+		assert!(code.contains("for(size_t n = 0; n < 2; ++n) {\n\
+		                       \tfor(size_t m = 0; m < 5; ++m) {\n\
+		                       \tput(m);\n}"));
+		// ... and are only declared by their loops.
+		assert_eq!(code.matches("i_2 =").count(), 1);
+
+		let s = "var:free n gen:std:I32 i32\n".to_string() +
+			"repeat 2 as n { }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		let err = pgm.analyze().unwrap_err();
+		assert!(err.contains("'n' is already declared"));
+	}
+
+	#[test]
+	fn empty_enum_rejected() {
+		let s = "enum E { }\n".to_string() +
+			"var:free e gen:Enum enum E\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		let err = pgm.analyze().unwrap_err();
+		assert!(err.contains("enum 'E' has no values"));
+	}
+
+	#[test]
+	fn buffer_length_argument() {
+		let s = "var:free buf gen:buffer pointer i32\n".to_string() +
+			"function:decl sum i32 {pointer i32, usize,}\n" +
+			"function:call sum { buf lengthof(buf) }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert_eq!(pgm.symlookup("buf").unwrap().generator.name(), "std:buffer");
+		// the third state is the five element buffer.
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(2, &vec![], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("sum(buf, 5ull);"));
+		let decl = code.lines().find(|l| l.contains("buf = ")).unwrap();
+		let elems = &decl[decl.find('{').unwrap()+1 .. decl.rfind('}').unwrap()];
+		assert_eq!(elems.split(", ").count(), 5);
+
+		// ... and only buffers have lengths.
+		let s = "var:free n gen:std:I32 i32\n".to_string() +
+			"function:decl sum i32 {pointer i32, usize,}\n" +
+			"function:call sum { n lengthof(n) }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		let err = pgm.analyze().unwrap_err();
+		assert!(err.contains("'n' is not a buffer"));
+	}
+
+	// Checks that every element in 'xml' is closed, in order, and returns the
+	// names of the elements that were opened.
+	fn xml_elements(xml: &str) -> Vec<String> {
+		let mut open: Vec<String> = Vec::new();
+		let mut all: Vec<String> = Vec::new();
+		for tag in xml.split('<').skip(1) {
+			let tag = &tag[..tag.find('>').expect("unterminated tag")];
+			if tag.starts_with("?") {
+				continue;
+			}
+			if tag.starts_with("/") {
+				assert_eq!(open.pop(), Some(tag[1..].to_string()));
+				continue;
+			}
+			let name = tag.split(|c| c == ' ' || c == '/').next().unwrap();
+			all.push(name.to_string());
+			if !tag.ends_with("/") {
+				open.push(name.to_string());
+			}
+		}
+		assert!(open.is_empty(), "unclosed elements {:?}", open);
+		all
+	}
+
+	#[test]
+	fn model_xml() {
+		let s = "struct hsearch_data {}\n".to_string() +
+			"struct entry { pointer char key; u32 flags : 3; }\n" +
+			"enum ACTION { FIND = 0 , ENTER = 1 , }\n" +
+			"var:free nel gen:Usize usize\n" +
+			"var:free tbl gen:opaque struct hsearch_data\n" +
+			"var:free act gen:Enum enum ACTION\n" +
+			"function:decl hcreate_r int {\n" +
+				"usize, pointer struct hsearch_data,\n" +
+			"}\n" +
+			"function:call hcreate_r { nel op:& tbl }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.dump_model_xml(&mut strm).unwrap();
+		let xml = String::from_utf8(strm).unwrap();
+		let elements = xml_elements(&xml);
+		assert_eq!(elements[0], "model");
+		assert_eq!(elements.iter().filter(|e| *e == "struct").count(), 2);
+		assert_eq!(elements.iter().filter(|e| *e == "variable").count(), 3);
+		assert!(xml.contains("<field name=\"flags\" type=\"uint32_t\" \
+		                      bits=\"3\"/>"));
+		assert!(xml.contains("<enumerator name=\"ENTER\" value=\"1\"/>"));
+		let start = xml.find("<function name=\"hcreate_r\"").unwrap();
+		let end = start + xml[start..].find("</function>").unwrap();
+		assert_eq!(xml[start..end].matches("<parameter ").count(), 2);
+		assert!(xml.contains("<variable name=\"act\" type=\"ACTION\" \
+		                      generator=\"std:enum:ACTION\" states=\"2\"/>"));
+	}
+
+	#[test]
+	fn strict_integer() {
+		let s = "var:free x gen:std:I32 int\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		let mut strict = pgm.clone();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		use variable;
+		let i32gen = variable::generator(&Type::Builtin(Native::I32));
+		assert_eq!(pgm.symlookup("x").unwrap().generator.name(), i32gen.name());
+		strict.set_strict(true);
+		assert!(strict.analyze().unwrap_err().contains("'int' is ambiguous"));
+	}
+
+	#[test]
+	fn builder_matches_parser() {
+		use api::{DeclType, FuncDecl, FuncKind};
+		use opcode::UOp;
+		let s = "struct hsearch_data {}\n".to_string() +
+			"var:free nel gen:Usize usize\n" +
+			"function:decl hcreate_r int {\n" +
+				"usize, pointer struct hsearch_data,\n" +
+			"}\n" +
+			"function:call hcreate_r { nel op:& tbl }\n";
+		let parsed: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		let hsd = Type::Struct("hsearch_data".to_string(), vec![], false);
+		let usize_t = DeclType::Basic(Type::Builtin(Native::Usize));
+		let int_t = DeclType::Basic(Type::Builtin(Native::Integer));
+		let hsd_ptr = DeclType::Basic(Type::Pointer(Box::new(hsd)));
+		let hcreate = FuncDecl::new("hcreate_r", int_t,
+		                            vec![usize_t.clone(), hsd_ptr],
+		                            FuncKind::Operation);
+		let call = api::Expr::Call("hcreate_r".to_string(), Box::new(vec![
+			api::Expr::VarRef(UOp::None, "nel".to_string()),
+			api::Expr::VarRef(UOp::AddressOf, "tbl".to_string()),
+		]));
+		let built = api::Program::builder()
+			.udt(DeclType::Struct("hsearch_data".to_string(), vec![], false))
+			.free("nel", "Usize", usize_t)
+			.function(hcreate)
+			.statement(api::Stmt::Basic(call))
+			.build();
+		assert_eq!(built.declarations, parsed.declarations);
+		assert_eq!(built.ast, parsed.ast);
+	}
+
+	#[test]
+	fn header_enum_names() {
+		let s = "enum mode { BLAH = 0, BLEH = 7, }\n".to_string() +
+			"var:free m gen:enum enum mode\n" +
+			"var:free n gen:enum numeric enum mode\n" +
+			"function:decl set void {enum mode, enum mode,}\n" +
+			"function:call set { m n }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert_eq!(pgm.symlookup("n").unwrap().generator.name(),
+		           "std:enum:numeric:mode");
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("mode m = BLAH;"), code);
+		assert!(code.contains("mode n = 0;"), code);
+
+		let s = "var:free x gen:I32 numeric i32\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		let err = pgm.analyze().unwrap_err();
+		assert!(err.starts_with("'int32_t' is not an enum"), err);
+	}
+
+	#[test]
+	fn enum_sample_selector() {
+		let s = "enum OP { NOP = 0, ADD = 1, SUB = 2, MUL = 3, DIV = 4, }\n"
+			.to_string() +
+			"var:free all gen:enum enum OP\n" +
+			"var:free some gen:enum sample(3) enum OP\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert_eq!(pgm.symlookup("all").unwrap().generator.n_state(), 5);
+		let some = pgm.symlookup("some").unwrap().generator.clone();
+		assert_eq!(some.name(), "std:enum:sample:OP");
+		assert_eq!(some.n_state(), 3);
+		let mut gen = some.clone();
+		gen.seek(0);
+		assert_eq!(gen.value(), "NOP");
+		gen.seek(2);
+		assert_eq!(gen.value(), "DIV");
+
+		let s = "var:free n gen:I32 sample(3) i32\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		assert!(pgm.analyze().unwrap_err().contains("only enums can be sampled"));
+	}
+
+	#[test]
+	fn void_function_call() {
+		let s = "function:decl reset void {}\n".to_string() +
+			"function:call reset {}\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		assert_eq!(String::from_utf8(strm).unwrap(), "\treset();\n");
+	}
+
+	#[test]
+	fn void_free_variable_rejected() {
+		let s = "var:free v gen:std:X void\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		assert_eq!(pgm.analyze().unwrap_err(),
+		           "cannot generate a value for 'void' ('v'); did you mean \
+		            'pointer void'?");
+
+		let s = "var:free v gen:std:X pointer void\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		pgm.analyze().unwrap();
+	}
+
+	#[test]
+	fn void_result_binding_rejected() {
+		let s = "var:constrained r i32\n".to_string() +
+			"function:decl reset void {}\n" +
+			"r = function:call reset {}\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		let err = pgm.analyze().unwrap_err();
+		assert!(err.contains("'reset' returns void"));
+
+		let s = "var:constrained r void\n".to_string() +
+			"function:decl reset void {}\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		assert!(pgm.analyze().is_err());
+	}
+
+	#[test]
+	fn describe_state_space() {
+		let s = "struct hsearch_data {}\n".to_string() +
+			"var:free nel gen:Usize usize\n" +
+			"var:free x gen:std:I32 i32\n" +
+			"var:free tbl gen:opaque struct hsearch_data\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.describe(&mut strm).unwrap();
+		let table = String::from_utf8(strm).unwrap();
+		let rows: Vec<&str> = table.lines().skip(1).collect();
+		assert_eq!(rows.len(), 3+1);
+		assert_eq!(rows[0], "nel\tsize_t\tstd:usize\t4");
+		assert_eq!(rows[1], "x\tint32_t\tstd:I32orig\t7");
+		assert_eq!(rows[2],
+		           "tbl\tstruct hsearch_data\tstd:opaque:struct hsearch_data\t1");
+		assert_eq!(rows[3], "total\t\t\t28");
+	}
+
+	#[test]
+	fn prune_unused_variables() {
+		let s = "var:free a gen:std:I32 i32\n".to_string() +
+			"var:free unused gen:cstring pointer char\n" +
+			"var:free b gen:std:I32 i32\n" +
+			"var:free c gen:std:I32 i32\n" +
+			"function:decl f void {i32, pointer i32,} precondition c>0;\n" +
+			"function:call f { a op:& b }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert_eq!(pgm.n_states(), 7 * 14 * 7 * 7);
+		assert_eq!(pgm.prune_unused(), vec!["unused".to_string()]);
+		assert_eq!(pgm.n_states(), 7 * 7 * 7);
+		let names: Vec<String> = pgm.free_symbols().iter()
+			.map(|s| s.name.clone()).collect();
+		assert_eq!(names, vec!["a", "b", "c"]);
+		pgm.seek(pgm.n_states() - 1);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(!code.contains("unused"), code);
+		assert!(code.contains("f(a, &b);\n"), code);
+		assert!(pgm.prune_unused().is_empty());
+	}
+
+	#[test]
+	fn single_state_variables() {
+		let s = "struct ctx {}\n".to_string() +
+			"var:free c gen:opaque struct ctx\n" +
+			"var:free n gen:std:I32 i32\n" +
+			"var:free p gen:null pointer i32\n" +
+			"function:decl f void {pointer struct ctx, i32, pointer i32,}\n" +
+			"function:call f { op:& c n p }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert_eq!(pgm.single_state_variables(), vec!["c", "p"]);
+		assert_eq!(*pgm.warnings(), vec![
+			"'c' has a single state, so it is the same in every case",
+			"'p' has a single state, so it is the same in every case",
+		]);
+	}
+
+	#[test]
+	fn diff_consecutive_cases() {
+		let s = "enum mode { A = 0, B = 1, C = 2, }\n".to_string() +
+			"var:free m gen:enum enum mode\n" +
+			"var:free f gen:std:bool bool\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		// the last variable varies fastest.
+		assert_eq!(pgm.diff_cases(2, 3), vec![api::FieldDiff{
+			name: "f".to_string(), before: "0".to_string(), after: "1".to_string(),
+		}]);
+		let diff = pgm.diff_cases(1, 4);
+		let names: Vec<&str> = diff.iter().map(|d| d.name.as_str()).collect();
+		assert_eq!(names, vec!["m", "f"]);
+		assert_eq!((diff[0].before.as_str(), diff[0].after.as_str()), ("A", "C"));
+		assert!(pgm.diff_cases(5, 5).is_empty());
+		assert_eq!(pgm.stats().emitted, 0);
+	}
+
+	#[test]
+	fn drive_case_sink() {
+		struct Recorder {
+			cases: Vec<Vec<(String, String)>>,
+		}
+		impl api::CaseSink for Recorder {
+			fn case(&mut self, values: &[(String, String)]) {
+				self.cases.push(values.to_vec());
+			}
+		}
+		let s = "enum mode { A = 0, B = 1, C = 2, }\n".to_string() +
+			"var:free m gen:enum enum mode\n" +
+			"var:free f gen:std:bool bool\n" +
+			"var:free x gen:std:I32 i32\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut rec = Recorder{cases: Vec::new()};
+		pgm.drive(&mut rec);
+		assert_eq!(rec.cases.len(), pgm.n_states());
+		assert_eq!(rec.cases.len(), 3 * 2 * 7);
+		for values in rec.cases.iter() {
+			let names: Vec<&str> = values.iter().map(|v| v.0.as_str()).collect();
+			assert_eq!(names, vec!["m", "f", "x"]);
+			assert!(["A", "B", "C"].contains(&values[0].1.as_str()), "{:?}", values);
+			assert!(["0", "1"].contains(&values[1].1.as_str()), "{:?}", values);
+			// INT32_MIN is spelled as an expression.
+			let x = values[2].1.trim_matches(|c| c == '(' || c == ')');
+			assert!(x.parse::<i64>().is_ok() || x == "-2147483647-1", "{:?}",
+			        values);
+		}
+		// every combination of the enum and the bool shows up, 7 times each.
+		let mut combos: Vec<(String, String)> = rec.cases.iter()
+			.map(|v| (v[0].1.clone(), v[1].1.clone())).collect();
+		combos.dedup();
+		assert_eq!(combos.len(), 3 * 2);
+		// driving is repeatable, as generating code is.
+		let mut again = Recorder{cases: Vec::new()};
+		pgm.drive(&mut again);
+		assert_eq!(again.cases, rec.cases);
+	}
+
+	#[test]
+	fn pin_variables() {
+		let s = "enum mode { A = 0, B = 1, C = 2, }\n".to_string() +
+			"var:free m gen:enum enum mode\n" +
+			"var:free n gen:enum enum mode\n" +
+			"var:free f gen:std:bool bool\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert_eq!(pgm.n_states(), 3 * 3 * 2);
+		pgm.pin("f", 1).unwrap();
+		assert_eq!(pgm.n_states(), 3 * 3);
+		pgm.pin("m", 1).unwrap();
+		assert_eq!(pgm.n_states(), 3);
+		let mut ns: Vec<String> = Vec::new();
+		for case in 0..pgm.n_states() {
+			let mut strm: Vec<u8> = Vec::new();
+			pgm.codegen_case(case, &vec![], &mut strm).unwrap();
+			let code = String::from_utf8(strm).unwrap();
+			assert!(code.contains("mode m = B;"), code);
+			assert!(code.contains("bool f = 1;"), code);
+			ns.push(pgm.diff_cases(0, case).iter().map(|d| d.name.clone())
+			        .collect::<Vec<String>>().join(","));
+		}
+		// only the unpinned variable varies.
+		assert_eq!(ns, vec!["", "n", "n"]);
+
+		assert_eq!(pgm.pin("m", 0).unwrap_err(), "'m' is pinned already");
+		assert_eq!(pgm.pin("n", 3).unwrap_err(),
+		           "cannot pin 'n' to state 3; it has 3 states");
+		assert_eq!(pgm.pin("q", 0).unwrap_err(), "'q' is not a free variable");
+	}
+
+	#[test]
+	fn suppress_pointer_warnings() {
+		let s = "struct pt { i32 x; pointer i32 p; }\n".to_string() +
+			"var:free p gen:std:ptr pointer i32\n" +
+			"var:free v gen:std:pt struct pt\n" +
+			"var:free n gen:std:I32 i32\n" +
+			"function:decl use void {pointer i32, pointer struct pt, i32,}\n" +
+			"function:call use { p &v n }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		pgm.seek(pgm.n_states() - 1);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let plain = String::from_utf8(strm).unwrap();
+		assert!(!plain.contains("#pragma"), plain);
+
+		pgm.set_suppress_warnings(true);
+		pgm.set_gnu_extensions(true);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		let push = "#pragma GCC diagnostic push\n\
+		            #pragma GCC diagnostic ignored \"-Wint-to-pointer-cast\"\n";
+		let pop = "#pragma GCC diagnostic pop\n";
+		assert!(code.contains(&format!("{}\tint32_t* p = (int32_t *)", push)),
+		        code);
+		assert!(code.contains(&format!("ull;\n{}", pop)), code);
+		// the struct holds a pointer, too, so it is not a temporary.
+		assert!(code.contains(&format!("{}\tstruct pt v = {{", push)), code);
+		assert!(code.contains(&format!("}};\n{}", pop)), code);
+		assert!(code.contains("\tuse(p, &v, n);\n"), code);
+		// only the declarations with pointers are wrapped.
+		assert_eq!(code.matches(pop).count(), 2);
+		assert!(code.contains("\tint32_t n = "), code);
+	}
+
+	#[test]
+	fn named_values() {
+		let s = "var:free foo gen:std:I32 i32\n".to_string() +
+			"var:free s gen:cstring pointer char\n" +
+			"var:free res gen:std:I32 i32\n" +
+			"function:decl f void {i32, pointer char, pointer i32,}\n" +
+			"function:call f { foo s op:& res }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		pgm.set_named_values(true);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		let def = code.find("#define VAR_foo ").expect(&code);
+		let call = code.find("f(VAR_foo, VAR_s, &res);").expect(&code);
+		assert!(def < call, code);
+		assert!(code.contains("#define VAR_s "), code);
+		assert!(!code.contains("int32_t foo"), code);
+		// a variable whose address is taken may be written, so it stays.
+		assert!(code.contains("\tint32_t res = "), code);
+		assert!(!code.contains("VAR_res"), code);
+	}
+
+	#[test]
+	fn named_values_numbered_functions() {
+		let s = "var:free foo gen:std:I32 i32\n".to_string() +
+			"function:decl f void {i32,}\n" +
+			"function:call f { foo }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		pgm.set_named_values(true);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_numbered_functions(0..2, &vec![], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(!code.contains("int32_t foo"), code);
+		// every function defines the macro before its call, and undefines it
+		// before the next function defines it again.
+		let mut rest = code.as_str();
+		for case in 0..2 {
+			let func = rest.find(&format!("test_case_{:04}(void) {{", case))
+				.expect(&code);
+			let def = rest.find("#define VAR_foo ").expect(&code);
+			let call = rest.find("f(VAR_foo);").expect(&code);
+			let undef = rest.find("#undef VAR_foo").expect(&code);
+			assert!(func < def && def < call && call < undef, code);
+			rest = &rest[undef+"#undef VAR_foo".len()..];
+		}
+		assert!(!rest.contains("VAR_foo"), code);
+	}
+
+	#[test]
+	fn codegen_case_deterministic() {
+		let s = "var:free x gen:std:I32 i32\n".to_string() +
+			"var:free str gen:cstring pointer char\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		pgm.set_seed(42);
+		let hdrs: Vec<&str> = vec!["stdlib.h"];
+		// x and str are both in (random) states 4.
+		let case = 4*8 + 4;
+		let mut first: Vec<u8> = Vec::new();
+		pgm.codegen_case(case, &hdrs, &mut first).unwrap();
+		let mut other: Vec<u8> = Vec::new();
+		pgm.codegen_case(case+1, &hdrs, &mut other).unwrap();
+		let mut second: Vec<u8> = Vec::new();
+		pgm.codegen_case(case, &hdrs, &mut second).unwrap();
+		assert_eq!(String::from_utf8(first).unwrap(),
+		           String::from_utf8(second).unwrap());
+	}
+
+	#[test]
+	fn generate_parallel_deterministic() {
+		use std::sync::{Arc, Mutex};
+		let s = "var:free x gen:std:I32 i32\n".to_string() +
+			"var:free str gen:cstring pointer char\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		pgm.set_seed(7);
+		let hdrs: Vec<&str> = vec!["stdlib.h"];
+		let ncases = pgm.n_states();
+		let mut results: Vec<Vec<(usize, Vec<u8>)>> = Vec::new();
+		for &nthreads in [1, 4].iter() {
+			let cases: Arc<Mutex<Vec<(usize, Vec<u8>)>>> =
+				Arc::new(Mutex::new(Vec::new()));
+			let sinkcases = cases.clone();
+			pgm.generate_range_parallel(0, ncases, nthreads, &hdrs,
+				move |case, code| {
+					sinkcases.lock().unwrap().push((case, code));
+					Ok(())
+				}
+			).unwrap();
+			let mut cases = cases.lock().unwrap().clone();
+			cases.sort();
+			assert_eq!(cases.len(), ncases);
+			results.push(cases);
+		}
+		assert!(results[0] == results[1]);
+	}
+
+	#[test]
+	fn struct_field_arguments() {
+		let decls = "struct pt { i32 x; i32 y; }\n".to_string() +
+			"var:free p gen:std:pt struct pt\n" +
+			"var:free pp gen:std:null pointer struct pt\n" +
+			"var:free n gen:std:I32 i32\n" +
+			"function:decl draw void {i32, i32,}\n";
+		let s = decls.clone() + "function:call draw { p.x pp->y }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("\tdraw(p.x, pp->y);\n"), code);
+
+		for &(call, err) in [
+			("p.z pp->y", "struct 'pt' has no field 'z'"),
+			("p.x pp->z", "struct 'pt' has no field 'z'"),
+			("p->x pp->y", "'p' is not a pointer; use 'p.x'"),
+			("p.x pp.y", "'pp' is a pointer; use 'pp->y'"),
+			("n.x p.y", "'n' is not a struct; it has no field 'x'"),
+			("q.x p.y", "unknown variable 'q'"),
+		].iter() {
+			let s = decls.clone() + "function:call draw { " + call + " }\n";
+			let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+			assert_eq!(pgm.analyze().unwrap_err(), err);
+		}
+	}
+
+	#[test]
+	fn packed_structs() {
+		let s = "packed struct hdr { bool tag; i32 len @ 1; }";
+		let decls = fuzz::parse_LDeclarations(s).unwrap();
+		let flds = match decls[0] {
+			api::Declaration::UDT(api::DeclType::Struct(ref nm, ref flds, true)) => {
+				assert_eq!(nm, "hdr");
+				flds.clone()
+			},
+			ref d => panic!("not a packed struct: {:?}", d),
+		};
+		assert_eq!(flds.len(), 2);
+		let printed = decls[0].to_dsl();
+		assert!(printed.starts_with("packed struct hdr {"), printed);
+		assert_eq!(fuzz::parse_LDeclarations(printed.as_str()).unwrap(), decls);
+
+		let vars = " var:free h gen:std:hdr struct hdr\n";
+		let mut packed: api::Program =
+			fuzz::parse_LProgram(&(s.to_string() + vars)).unwrap();
+		match packed.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let plain = s["packed ".len()..].to_string() + vars;
+		let mut plain: api::Program = fuzz::parse_LProgram(&plain).unwrap();
+		match plain.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		// packing changes the layout, not the values.
+		assert_eq!(packed.n_states(), plain.n_states());
+		match packed.symlookup("h").unwrap().typ {
+			Type::Struct(ref nm, _, true) => assert_eq!(nm, "hdr"),
+			ref ty => panic!("not a packed struct: {:?}", ty),
+		};
+		match plain.symlookup("h").unwrap().typ {
+			Type::Struct(_, _, false) => (),
+			ref ty => panic!("packed: {:?}", ty),
+		};
+		packed.set_define_types(true);
+		let mut strm: Vec<u8> = Vec::new();
+		packed.codegen_case(0, &vec!["stdint.h"], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("struct hdr;\n"), code);
+		assert!(code.contains("struct hdr {\n\tbool tag;\n\tint32_t len;\n\
+		                       } __attribute__((packed));\n"), code);
+		assert!(code.contains("offsetof(struct hdr, len) == 1"), code);
+	}
+
+	#[test]
+	fn null_terminated_arguments() {
+		let s = "var:free path gen:values(\"/bin/sh\") pointer char\n".to_string() +
+			"var:free args gen:argv pointer pointer char\n" +
+			"function:decl execv int {pointer char, pointer pointer char,}\n" +
+			"function:call execv { path args }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert_eq!(pgm.n_states(), 4);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("\tchar** args = (char*[]){NULL};\n"), code);
+		assert!(code.contains("\texecv(path, args);\n"), code);
+
+		let s = s.replace("gen:argv pointer pointer char", "gen:argv pointer char");
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		let err = pgm.analyze().unwrap_err();
+		assert!(err.contains("cannot terminate 'char*' with NULL"), err);
+	}
+
+	#[test]
+	fn construct_handles() {
+		let s = "struct FILE {}\n".to_string() +
+			"var:free f gen:construct(fopen, \"test.txt\", \"r,b\") \
+			 pointer struct FILE\n" +
+			"function:new fopen pointer struct FILE {pointer char, pointer char,}\n" +
+			"function:decl fgetc int {pointer struct FILE,}\n" +
+			"function:call fgetc { f }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert_eq!(pgm.n_states(), 1);
+		pgm.set_named_values(true);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		let decl = code.find("\tstruct FILE* f = fopen(\"test.txt\", \"r,b\");\n")
+			.expect(&code);
+		let call = code.find("\tfgetc(f);\n").expect(&code);
+		assert!(decl < call, code);
+
+		for &(genspec, err) in [
+			("construct(fclose)", "constructor 'fclose' is not a declared function"),
+			("construct(fopen, \"x\")",
+			 "constructor 'fopen' takes 2 arguments, not 1"),
+			("construct(fgetc, f)",
+			 "constructor 'fgetc' returns 'int', not 'struct FILE*'"),
+		].iter() {
+			let s = s.replace("construct(fopen, \"test.txt\", \"r,b\")", genspec);
+			let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+			let e = pgm.analyze().unwrap_err();
+			assert!(e.contains(err), e);
+		}
+	}
+
+	#[test]
+	fn array_fields() {
+		let s = "struct mat { int m[2][2]; bool ok; }";
+		let decls = fuzz::parse_LDeclarations(s).unwrap();
+		let flds = match decls[0] {
+			api::Declaration::UDT(api::DeclType::Struct(_, ref flds, _)) =>
+				flds.clone(),
+			ref d => panic!("not a struct: {:?}", d),
+		};
+		let row = Type::Array(Box::new(Type::Builtin(Native::Integer)), 2);
+		assert_eq!(flds[0].ty,
+		           api::DeclType::Basic(Type::Array(Box::new(row), 2)));
+		let printed = decls[0].to_dsl();
+		assert!(printed.contains("\tint m[2][2];\n"), printed);
+		assert_eq!(fuzz::parse_LDeclarations(printed.as_str()).unwrap(), decls);
+
+		let vars = " var:free x gen:std:mat struct mat\n";
+		let mut pgm: api::Program =
+			fuzz::parse_LProgram(&(s.to_string() + vars)).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		// four elements of seven states each, and a bool.
+		assert_eq!(pgm.n_states(), 7 * 7 * 7 * 7 * 2);
+		pgm.set_define_types(true);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &vec!["stdint.h"], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("struct mat {\n\tint m[2][2];\n\tbool ok;\n};\n"),
+		        code);
+		assert!(code.contains(".m = {{"), code);
+		assert_eq!(code.matches("}, {").count(), 1, "{}", code);
+
+		for size in ["0", "-2", "99999999999999999999999"].iter() {
+			let s = format!("struct mat {{ int m[2][{}]; }}", size);
+			match fuzz::parse_LDeclarations(s.as_str()) {
+				Ok(d) => panic!("parsed {:?}", d),
+				Err(e) => assert_eq!(util::parse_error(s.as_str(), e),
+				                     format!("invalid size '{}' of array 'm'", size)),
+			};
+		}
+	}
+
+	// The states of a large array field saturate the count, in the struct that
+	// holds it too, and seeking to any of them must not count up to it.
+	#[test]
+	fn large_array_field() {
+		let s = "struct mat { int m[32]; bool ok; }\n\
+		         var:free x gen:std:mat struct mat\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert_eq!(pgm.n_states(), usize::max_value());
+		let last = usize::max_value() - 1;
+		for &(case, ok) in [(0, "0"), (1, "1"), (last, "0")].iter() {
+			let mut strm: Vec<u8> = Vec::new();
+			pgm.codegen_case(case, &vec!["stdint.h"], &mut strm).unwrap();
+			let code = String::from_utf8(strm).unwrap();
+			assert!(code.contains(&format!(".ok = {},", ok)), "{}", code);
+		}
+	}
+
+	#[test]
+	fn recursive_structs() {
+		for &(s, err) in [
+			("struct Node { i32 v; struct Node next; }",
+			 "struct 'Node' contains itself by value (Node -> Node); hold one of \
+			  them through a pointer"),
+			("struct A { i32 v; struct B b; }\nstruct B { struct A a; }",
+			 "struct 'A' contains itself by value (A -> B -> A); hold one of \
+			  them through a pointer"),
+			("struct C { struct { struct C c; }; }",
+			 "struct 'C' contains itself by value (C -> C); hold one of \
+			  them through a pointer"),
+		].iter() {
+			let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+			assert_eq!(pgm.analyze().unwrap_err(), err);
+		}
+
+		let s = "struct Node { i32 v; pointer struct Node next; }\n".to_string() +
+			"struct List { struct Node head; pointer struct List rest; }\n" +
+			"var:free n gen:std:Node struct Node\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert!(pgm.n_states() > 1);
+	}
+
+	#[test]
+	fn define_dsl_types() {
+		let s = "enum COLOR { RED, GREEN = 0x10, }\n".to_string() +
+		"struct pair { i32 a; pointer char b; }\n" +
+		"struct hidden {}\n" +
+		"struct node {\n" +
+			"i32 value;\n" +
+			"pointer struct node next;\n" +
+			"struct pair both;\n" +
+			"enum COLOR color;\n" +
+			"struct { u8 lo : 4; u8 hi : 4; };\n" +
+		"}\n" +
+		"var:free n gen:std:node struct node\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let hdrs: Vec<&str> = vec!["stdint.h", "stdlib.h"];
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(!code.contains("struct pair {"));
+
+		pgm.set_define_types(true);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		let defs = "struct pair;\n".to_string() +
+			"struct hidden;\n" +
+			"struct node;\n" +
+			"typedef enum COLOR {\n" +
+			"\tRED = 0,\n" +
+			"\tGREEN = 0x10,\n" +
+			"} COLOR;\n" +
+			"struct pair {\n" +
+			"\tint32_t a;\n" +
+			"\tchar* b;\n" +
+			"};\n" +
+			"struct node {\n" +
+			"\tint32_t value;\n" +
+			"\tstruct node* next;\n" +
+			"\tstruct pair both;\n" +
+			"\tCOLOR color;\n" +
+			"\tstruct {\n" +
+			"\t\tuint8_t lo : 4;\n" +
+			"\t\tuint8_t hi : 4;\n" +
+			"\t};\n" +
+			"};\n\n";
+		assert!(code.contains(&defs), code);
+		assert!(code.find(&defs).unwrap() < code.find("int main() {").unwrap());
+		assert!(code.find("#include <stdlib.h>").unwrap() <
+		        code.find(&defs).unwrap());
+		assert_eq!(code.matches("struct hidden").count(), 1);
+	}
+
+	#[test]
+	fn ungeneratable_struct_field() {
+		use typ::Name;
+		let s = "struct inner { i64 big; }\n".to_string() +
+			"struct outer { i32 a; struct inner in; }\n" +
+			"var:free o gen:std:outer struct outer\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		assert_eq!(pgm.analyze().unwrap_err(),
+		           "field 'outer.in.big' of type 'int64_t' is not generatable");
+		let i64t = Type::Builtin(Native::I64);
+		let outer = pgm.typetab.iter().find(|t| t.name() == "struct outer")
+			.unwrap().clone();
+		assert_eq!(outer.is_generatable(), Err(i64t.clone()));
+		assert_eq!(i64t.is_generatable(), Err(i64t.clone()));
+
+		let s = "var:free x gen:std:X i64\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		assert_eq!(pgm.analyze().unwrap_err(),
+		           "type 'int64_t' is not generatable");
+
+		// bitfields of any integer type are fine, as are pointers to anything.
+		let s = "struct flags { u8 on : 1; i64 mode : 3; }\n".to_string() +
+			"struct ok { i32 a; pointer i64 p; struct flags f; usize n; }\n" +
+			"var:free v gen:std:ok struct ok\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let ok = pgm.symlookup("v").unwrap().typ.clone();
+		assert_eq!(ok.is_generatable(), Ok(()));
+	}
+
+	#[test]
+	fn ascii_cstring_generator() {
+		let s = "var:free str gen:cstring ascii pointer char\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		pgm.analyze().unwrap();
+		let gen = pgm.symlookup("str").unwrap().generator.clone();
+		assert_eq!(gen.name(), "std:cstring:ascii");
+		assert_eq!(gen.n_state(), 5);
+
+		let s = "var:free n gen:I32 ascii i32\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		assert!(pgm.analyze().unwrap_err().contains("limited to ascii"));
+	}
+
+	#[test]
+	fn case_identifiers() {
+		let s = "var:free a gen:std:I32 i32\n".to_string() +
+			"var:free b gen:std:I32 i32\n" +
+			"function:decl abs i32 {i32,}\n" +
+			"function:call abs { a }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		pgm.analyze().unwrap();
+		pgm.set_case_ids(true);
+		let headers = vec!["stdlib.h"];
+		let mut hashes: Vec<u64> = Vec::new();
+		for &case in [0, 7, pgm.n_states()-1].iter() {
+			let mut strm: Vec<u8> = Vec::new();
+			pgm.codegen_case(case, &headers, &mut strm).unwrap();
+			let code = String::from_utf8(strm).unwrap();
+			let id = format!("printf(\"CASE %zu %s\\n\", (size_t){}, \"{:016x}\");",
+			                 case, pgm.values_hash());
+			assert!(code.contains(&id), "{} not in {}", id, code);
+			assert!(code.contains("#include <stdio.h>\n"), code);
+			assert!(code.find(&id).unwrap() < code.find("abs(").unwrap(), code);
+			hashes.push(pgm.values_hash());
+		}
+		// the hash depends on the values, and only on them.
+		assert!(hashes[0] != hashes[1] && hashes[1] != hashes[2]);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(7, &headers, &mut strm).unwrap();
+		assert_eq!(pgm.values_hash(), hashes[1]);
+
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_numbered_functions(3..5, &headers, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("(size_t)3, \""), code);
+		let id = format!("(size_t)4, \"{:016x}\"", pgm.values_hash());
+		assert!(code.contains(&id), code);
+	}
+
+	#[test]
+	fn parse_output_parameters() {
+		let s = "function:decl divide i32 \
+		         {i32, i32, out pointer int, inout pointer i32,}\n";
+		let pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		let f = match pgm.declarations[0] {
+			api::Declaration::Function(ref f) => f.clone(),
+			ref d => panic!("not a function: {:?}", d),
+		};
+		assert_eq!(f.modes, vec![api::ParamMode::In, api::ParamMode::In,
+		                         api::ParamMode::Out, api::ParamMode::InOut]);
+		assert_eq!(f.parameters[2], api::DeclType::Basic(Type::Pointer(
+			Box::new(Type::Builtin(Native::Integer)))));
+		let dsl = pgm.declarations[0].to_dsl();
+		assert!(dsl.contains("out pointer int, inout pointer i32,"), dsl);
+		let reparsed: api::Program = fuzz::parse_LProgram(&dsl).unwrap();
+		assert_eq!(reparsed.declarations, pgm.declarations);
+
+		let s = "function:decl divide i32 {out i32,}\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		assert_eq!(pgm.analyze().unwrap_err(),
+		           "parameter 1 of 'divide' is an output, but not a pointer");
+	}
+
+	#[test]
+	fn output_parameters_flow() {
+		let s = "var:free a gen:std:I32 i32\n".to_string() +
+			"var:constrained q i32\n" +
+			"var:constrained r i32\n" +
+			"var:constrained sum i32\n" +
+			"function:decl divide void {i32, i32, out pointer i32, \
+			 out pointer i32,}\n" +
+			"function:decl accumulate void {inout pointer i32, i32,}\n" +
+			"function:decl add i32 {i32, i32,}\n" +
+			"function:call add { q a }\n" +
+			"function:call divide { a a & q & r }\n" +
+			"sum = function:call add { q a }\n" +
+			"function:call accumulate { & sum r }\n" +
+			"function:call add { sum a }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let link = |var: &str, producer: &str, consumer: &str| api::DataFlow{
+			var: var.to_string(), producer: producer.to_string(),
+			consumer: consumer.to_string(),
+		};
+		assert_eq!(pgm.data_flow(), vec![
+			link("q", "divide", "add"),
+			link("sum", "add", "accumulate"),
+			link("r", "divide", "accumulate"),
+			link("sum", "accumulate", "add"),
+		]);
+	}
+
+	#[test]
+	fn parse_constants() {
+		let s = "const BUFSIZ = 8192;\n".to_string() +
+			"const MASK = 0x0F;\n" +
+			"enum size { SMALL = 1, LARGE = BUFSIZ, HUGE, LAST = BUFSIZ + 7, }\n";
+		let pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		assert_eq!(pgm.declarations[0],
+		           api::Declaration::Const("BUFSIZ".to_string(), 8192));
+		assert_eq!(pgm.declarations[1],
+		           api::Declaration::Const("MASK".to_string(), 15));
+		let dsl: Vec<String> = pgm.declarations.iter().map(|d| d.to_dsl())
+			.collect();
+		assert_eq!(dsl[0], "const BUFSIZ = 8192;");
+		let reparsed: api::Program = fuzz::parse_LProgram(&dsl.join("\n"))
+			.unwrap();
+		assert_eq!(reparsed.declarations, pgm.declarations);
+	}
+
+	#[test]
+	fn link_libraries() {
+		let s = "link \"pthread\";\n".to_string() +
+			"link \"m\";\n" +
+			"const N = 4;\n" +
+			"var:free n gen:std:I32 i32\n" +
+			"function:decl f void {i32,}\n" +
+			"function:call f { n }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
+		};
+		assert_eq!(pgm.declarations[0],
+		           api::Declaration::Link("pthread".to_string()));
+		assert_eq!(pgm.declarations[1].to_dsl(), "link \"m\";");
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.emit_build(&mut strm, &vec!["out/case0.c", "out/case1.c"],
+		               &vec!["/opt/api/include"]).unwrap();
+		let recipe = String::from_utf8(strm).unwrap();
+		assert_eq!(recipe, "CFLAGS += -I/opt/api/include\n\
+		                    LDLIBS += -lpthread\n\
+		                    LDLIBS += -lm\n\
+		                    all: out/case0 out/case1\n\
+		                    \n\
+		                    out/case0: out/case0.c\n\
+		                    \t$(CC) $(CFLAGS) -o $@ out/case0.c $(LDLIBS)\n\
+		                    \n\
+		                    out/case1: out/case1.c\n\
+		                    \t$(CC) $(CFLAGS) -o $@ out/case1.c $(LDLIBS)\n");
+	}
+
+	#[test]
+	fn enum_values_from_constants() {
+		let decls = "const BUFSIZ = 8192;\n".to_string() +
+			"enum size { SMALL = 1, LARGE = BUFSIZ, HUGE, LAST = BUFSIZ + 7, }\n";
+		let s = decls.clone() + "var:free sz gen:enum enum size\n" +
+			"var:free n gen:values(BUFSIZ, 0) i32\n" +
+			"function:decl resize void {enum size, i32,}\n" +
+			"function:call resize { sz BUFSIZ }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let values: Vec<(String, i64, String)> = match pgm.typetab[0] {
+			Type::Enum(_, ref values) => values.clone(),
+			ref t => panic!("not an enum: {:?}", t),
+		};
+		let expected: Vec<(&str, i64, &str)> = vec![
+			("SMALL", 1, "1"), ("LARGE", 8192, "BUFSIZ"),
+			("HUGE", 8193, "BUFSIZ + 1"), ("LAST", 8199, "BUFSIZ + 7"),
+		];
+		assert_eq!(values, expected.iter().map(|&(n, v, s)|
+			(n.to_string(), v, s.to_string())).collect::<Vec<_>>());
+		pgm.set_define_types(true);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(1, &vec!["stdio.h"], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("#ifndef BUFSIZ\n#define BUFSIZ 8192\n#endif\n"),
+		        code);
+		assert!(code.contains("resize(sz, BUFSIZ);"), code);
+		assert!(!code.contains(" BUFSIZ ="), code);
+
+		let s = decls.replace("const BUFSIZ = 8192;\n", "");
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		assert_eq!(pgm.analyze().unwrap_err(), "enumerator 'LARGE' of 'size' \
+		           refers to unknown constant 'BUFSIZ'");
+	}
+
+	#[test]
+	fn parse_variadic_function() {
+		let s = "function:use printf int {pointer char, ...}\n";
+		let pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		let f = match pgm.declarations[0] {
+			api::Declaration::Function(ref f) => f.clone(),
+			ref d => panic!("not a function: {:?}", d),
+		};
+		assert!(f.variadic);
+		assert_eq!(f.parameters.len(), 1);
+		let dsl = pgm.declarations[0].to_dsl();
+		assert_eq!(dsl, "function:decl printf int {pointer char, ...}");
+		let reparsed: api::Program = fuzz::parse_LProgram(&dsl).unwrap();
+		assert_eq!(reparsed.declarations, pgm.declarations);
+	}
+
+	#[test]
+	fn codegen_to_sink() {
+		use stmt::CodeSink;
+		struct Collector {
+			decls: Vec<String>,
+			stmts: Vec<String>,
+		}
+		impl CodeSink for Collector {
+			fn declaration(&mut self, code: &str) -> std::io::Result<()> {
+				self.decls.push(code.to_string());
+				Ok(())
+			}
+			fn statement(&mut self, code: &str) -> std::io::Result<()> {
+				self.stmts.push(code.to_string());
+				Ok(())
+			}
+			fn text(&mut self, _: &str) -> std::io::Result<()> { Ok(()) }
+		}
+		let s = "var:free x gen:values(3) i32\n".to_string() +
+			"var:constrained r int\n" +
+			"function:decl get int {i32,}\n" +
+			"function:decl put void {int,}\n" +
+			"r = function:call get { x }\n" +
+			"function:call put { r }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut sink = Collector{decls: Vec::new(), stmts: Vec::new()};
+		pgm.codegen_to(&mut sink).unwrap();
+		assert_eq!(sink.decls, vec!["int32_t x = 3;", "int r = 0;"]);
+		assert_eq!(sink.stmts, vec!["r = get(x);", "put(r);"]);
+
+		// a byte stream gets a line for each.
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		assert_eq!(String::from_utf8(strm).unwrap(),
+		           "\tint32_t x = 3;\n\tint r = 0;\n\tr = get(x);\n\tput(r);\n");
+	}
+
+	#[test]
+	fn gnu_statement_expressions() {
+		let s = "struct pt { i32 x; i32 y; }\n".to_string() +
+			"var:free p gen:std:Struct struct pt\n" +
+			"var:free q gen:std:Struct struct pt\n" +
+			"function:decl draw void {pointer struct pt,}\n" +
+			"function:decl move void {pointer struct pt, pointer struct pt,}\n" +
+			"function:call draw { &p }\n" +
+			"function:call move { &q &q }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let hdrs: Vec<&str> = vec!["stdint.h"];
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &hdrs, &mut strm).unwrap();
+		let plain = String::from_utf8(strm).unwrap();
+		assert!(plain.contains("struct pt p = {"), plain);
+		assert!(plain.contains("draw(&p);"), plain);
+
+		pgm.set_gnu_extensions(true);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		let start = code.find("draw(&(struct pt){").expect(&code);
+		let end = start + code[start..].find("});").expect(&code) + 1;
+		assert!(!code.contains("struct pt p"), code);
+		// the value is the same either way.
+		let squash = |s: &str| s.split_whitespace().collect::<Vec<&str>>()
+			.join(" ");
+		let value = squash(&code[start+"draw(&(struct pt)".len() .. end]);
+		assert!(squash(&plain).contains(&value), value);
+		// 'q' is used twice, so it is declared up front as before.
+		assert!(code.contains("struct pt q = {"), code);
+		assert!(code.contains("move(&q, &q);"), code);
+
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_libfuzzer(&hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("draw(&p);"), code);
+	}
+
+	#[test]
+	fn struct_by_value_and_by_pointer() {
+		let s = "struct pt { i32 x; i32 y; }\n".to_string() +
+			"var:free v gen:std:Struct struct pt\n" +
+			"var:free r gen:std:Struct struct pt\n" +
+			"function:decl by_value void {struct pt,}\n" +
+			"function:decl by_pointer void {pointer struct pt,}\n" +
+			"function:call by_value { v }\n" +
+			"function:call by_pointer { &r }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let hdrs: Vec<&str> = vec!["stdint.h"];
+		for gnu in [false, true].iter() {
+			pgm.set_gnu_extensions(*gnu);
+			let mut strm: Vec<u8> = Vec::new();
+			pgm.codegen_case(0, &hdrs, &mut strm).unwrap();
+			let code = String::from_utf8(strm).unwrap();
+			// a struct by value is declared, then passed as is ...
+			assert!(code.contains("struct pt v = {"), code);
+			assert!(code.contains("by_value(v);"), code);
+			assert!(!code.contains("&v"), code);
+			// ... and a pointer to a struct is the address of one.
+			if *gnu {
+				assert!(code.contains("by_pointer(&(struct pt){"), code);
+				assert!(!code.contains("struct pt r"), code);
+			} else {
+				assert!(code.contains("struct pt r = {"), code);
+				assert!(code.contains("by_pointer(&r);"), code);
+			}
+		}
+
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_libfuzzer(&hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("by_value(v);"), code);
+		assert!(code.contains("by_pointer(&r);"), code);
+	}
+
+	#[test]
+	fn call_arguments_checked() {
+		let decls = "struct pt { i32 x; }\n".to_string() +
+			"var:free n gen:std:I32 i32\n" +
+			"var:free p gen:std:Struct struct pt\n" +
+			"var:free s gen:cstring pointer char\n" +
+			"function:decl put void {i32, pointer char,}\n" +
+			"function:decl draw void {pointer struct pt,}\n" +
+			"function:decl count void {}\n";
+		let analyze = |calls: &str| -> Result<(),String> {
+			let s = decls.clone() + calls;
+			let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+			pgm.analyze()
+		};
+		assert_eq!(analyze("function:call put { n s }\n"), Ok(()));
+		assert_eq!(analyze("function:call draw { &p }\n"), Ok(()));
+		assert_eq!(analyze("function:call put { n }\n").unwrap_err(),
+		           "'put' takes 2 arguments, but 1 were given");
+		assert_eq!(analyze("function:call count { n }\n").unwrap_err(),
+		           "'count' takes 0 arguments, but 1 were given");
+		assert_eq!(analyze("function:call put { s n }\n").unwrap_err(),
+		           "argument 1 of 'put' is a 'char*', but 'put' takes a 'int32_t'");
+		assert_eq!(analyze("function:call draw { p }\n").unwrap_err(),
+		           "argument 1 of 'draw' is a 'struct pt', but 'draw' takes a \
+		            'struct pt*'");
+	}
+
+	#[test]
+	fn codegen_is_deterministic() {
+		let s = "enum COLOR { RED, GREEN, BLUE, }\n".to_string() +
+			"struct pair { i32 a; pointer char b; }\n" +
+			"struct node { i32 value; struct pair both; enum COLOR color; }\n" +
+			"struct opaque {}\n" +
+			"var:free x gen:std:I32 i32\n" +
+			"var:free s gen:cstring pointer char\n" +
+			"var:free n gen:std:node struct node\n" +
+			"var:free c gen:enum enum COLOR\n" +
+			"var:constrained r int\n" +
+			"function:decl first int {i32, pointer char,}\n" +
+			"function:decl second void {struct node,}\n" +
+			"function:decl third void {enum COLOR, int,}\n" +
+			"r = function:call first { x s }\n" +
+			"function:call second { n }\n" +
+			"function:call third { c r }\n";
+		let hdrs: Vec<&str> = vec!["stdint.h", "stdlib.h"];
+		// every run starts over from parsing, as separate invocations would.
+		let run = || {
+			let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+			match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+			pgm.set_define_types(true);
+			pgm.set_log_calls(true);
+			pgm.set_seed(5);
+			let mut strm: Vec<u8> = Vec::new();
+			for case in [0, 3, 41].iter() {
+				pgm.codegen_case(*case, &hdrs, &mut strm).unwrap();
+			}
+			strm
+		};
+		let first = run();
+		for _ in 0..9 {
+			assert!(run() == first);
+		}
+	}
+
+	#[test]
+	fn bool_returns_normalized() {
+		let s = "var:free x gen:std:I32 i32\n".to_string() +
+			"var:constrained r int\n" +
+			"var:constrained b int\n" +
+			"function:decl isvalid int {i32,} returns:bool precondition x > 0;\n" +
+			"function:decl count int {i32,}\n" +
+			"r = function:call isvalid { x }\n" +
+			"b = function:call count { x }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		let dsl = pgm.declarations[3].to_dsl();
+		assert_eq!(dsl, "function:decl isvalid int {i32,} returns:bool \
+		                 precondition x>0;");
+		let reparsed: api::Program = fuzz::parse_LProgram(&dsl).unwrap();
+		assert_eq!(reparsed.declarations[0], pgm.declarations[3]);
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		pgm.set_log_calls(true);
+		let hdrs: Vec<&str> = vec!["stdlib.h"];
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("\"call 0 -> %ld (errno=%d)\\n\", (long)(r != 0), \
+		                       errno);"), code);
+		assert!(code.contains("\"call 1 -> %ld (errno=%d)\\n\", (long)b, \
+		                       errno);"), code);
+
+		let s = "function:decl find pointer void {} returns:bool\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		assert_eq!(pgm.analyze().unwrap_err(),
+		           "'find' is marked returns:bool, but does not return an integer");
+	}
+
+	#[test]
+	fn variadic_arguments_appended() {
+		let s = "var:free fmt gen:cstring pointer char\n".to_string() +
+			"var:free n gen:std:I32 i32\n" +
+			"function:use printf int {pointer char, ...}\n" +
+			"function:call printf { fmt }\n" +
+			"function:call printf { fmt n }\n" +
+			"function:call printf { fmt }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		// two calls get an int and a string each.
+		assert_eq!(pgm.free_symbols().len(), 6);
+		assert_eq!(pgm.n_states(), 14 * 7 * (7 * 14) * (7 * 14));
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("int32_t printf_va1_int = "), code);
+		assert!(code.contains("char* printf_va2_str = "), code);
+		assert!(code.contains("\tprintf(fmt, printf_va1_int, printf_va1_str);\n\
+		                       \tprintf(fmt, n);\n\
+		                       \tprintf(fmt, printf_va2_int, printf_va2_str);\n"),
+		        code);
+	}
+
+	#[test]
+	fn weighted_sampling() {
+		let s = "var:free n gen:std:I32 i32\n".to_string() +
+			"var:free str gen:cstring weighted pointer char\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		pgm.analyze().unwrap();
+		assert_eq!(pgm.symlookup("str").unwrap().generator.name(),
+		           "std:cstring:weighted");
+		let (mut edges, mut seen) = (0, vec![false; 7]);
+		for _ in 0..1000 {
+			let case = pgm.sample_case();
+			assert!(case < pgm.n_states());
+			seen[case / 14] = true;
+			if [0, 1, 7].contains(&(case % 14)) {
+				edges += 1;
+			}
+		}
+		// the edge states have 24 of the total weight of 35.
+		assert!(edges > 600, "only {} of the strings were edge cases", edges);
+		assert!(seen.iter().all(|&s| s));
+
+		// a 64-element array saturates the number of cases.
+		let s = "struct big { i32 v[64]; }\n\
+			var:free b gen:Struct struct big\n\
+			var:free ok gen:bool bool\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		pgm.analyze().unwrap();
+		assert_eq!(pgm.n_states(), usize::max_value());
+		for _ in 0..100 {
+			let case = pgm.sample_case();
+			assert!(case < pgm.n_states());
+			pgm.seek(case);
+		}
+	}
+
+	#[test]
+	fn bound_sources_have_no_states() {
+		let s = "const LIMIT = 10;\n".to_string() +
+			"function:decl reset void {}\n" +
+			"function:decl count int {}\n" +
+			"function:call reset {}\n" +
+			"function:call count {}\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		pgm.analyze().unwrap();
+		for nm in ["LIMIT", "reset", "count"].iter() {
+			let gen = &pgm.symlookup(nm).unwrap().generator;
+			assert_eq!(gen.n_state(), 0);
+			assert_eq!(gen.value(), "");
+		}
+		assert_eq!(pgm.n_states(), 1);
+		assert_eq!(pgm.smoke_cases(), vec![0]);
+		assert_eq!(pgm.sample_case(), 0);
+		let hdrs: Vec<&str> = vec!["stdlib.h"];
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("reset();"), code);
+		assert!(!code.contains("LIMIT"), code);
+
+		// ... and do not change the number of states of free variables.
+		let s = "var:free x gen:values(1, 2, 3) i32\n".to_string() +
+			"function:decl use_x void {i32,}\n" +
+			"function:call use_x { x }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		pgm.analyze().unwrap();
+		assert_eq!(pgm.n_states(), 3);
+		pgm.seek(2);
+		assert_eq!(pgm.symlookup("x").unwrap().generator.value(), "3");
+	}
+
+	#[test]
+	fn smoke_cases() {
+		let s = "var:free x gen:values(0, 1, 2, 3, 4, 5, 6, 7, 8, 9) i32\n"
+			.to_string() +
+			"var:free b gen:bool bool\n" +
+			"var:free c gen:values(7) i32\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		pgm.analyze().unwrap();
+		assert_eq!(pgm.n_states(), 20);
+		let cases = pgm.smoke_cases();
+		assert_eq!(cases.len(), 3*2);
+		let (mut xs, mut bs) = (Vec::new(), Vec::new());
+		for case in cases.iter() {
+			pgm.seek(*case);
+			xs.push(pgm.symlookup("x").unwrap().generator.value());
+			bs.push(pgm.symlookup("b").unwrap().generator.value());
+		}
+		xs.dedup();
+		assert_eq!(xs, vec!["0", "5", "9"]);
+		bs.sort();
+		bs.dedup();
+		assert_eq!(bs, vec!["0", "1"]);
+		let mut sorted = cases.clone();
+		sorted.sort();
+		sorted.dedup();
+		assert_eq!(sorted, cases);
+
+		// a 64-element array saturates the number of cases.
+		let s = "struct big { i32 v[64]; }\n\
+			var:free b gen:Struct struct big\n\
+			var:free ok gen:bool bool\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		pgm.analyze().unwrap();
+		let max = usize::max_value();
+		assert_eq!(pgm.n_states(), max);
+		let cases = pgm.smoke_cases();
+		assert_eq!(cases, vec![0, 1, max-1]);
+		for case in cases.iter() {
+			pgm.seek(*case);
+		}
+	}
+
+	#[test]
+	fn reduce_to_boundaries() {
+		let s = "var:free n gen:std:I32 i32\n\
+			var:free b gen:bool bool\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		pgm.analyze().unwrap();
+		let value = |pgm: &mut api::Program, case: usize, name: &str| {
+			pgm.case_values(case).into_iter().find(|v| v.0 == name).unwrap().1
+		};
+		// a small negative n, and a true b.
+		pgm.seek(2*2 + 1);
+		let reduced = pgm.reduce(|pgm, case| {
+			value(pgm, case, "n").starts_with("-") ||
+				value(pgm, case, "n").starts_with("(-")
+		});
+		// zero is closer, but is not negative; b stays as it was.
+		assert_eq!(reduced, 0*2 + 1);
+		assert_eq!(value(&mut pgm, reduced, "n"), "(-2147483647-1)");
+		assert_eq!(value(&mut pgm, reduced, "b"), "1");
+
+		// a failure that needs the exact value cannot be shrunk.
+		pgm.seek(2*2 + 1);
+		assert_eq!(pgm.reduce(|_, case| case == 2*2 + 1), 2*2 + 1);
+	}
+
+	#[test]
+	fn declarations_file() {
+		use std::io::Write;
+		let dir = tempdir::TempDir::new("apifuzz").unwrap();
+		let path = dir.path().join("decls.fuzz");
+		{
+			let mut f = std::fs::File::create(&path).unwrap();
+			f.write_all(b"var:free x gen:std:I32 i32\n").unwrap();
+		}
+		let decls = api::parse_declarations_file(&path).unwrap();
+		assert_eq!(decls.len(), 1);
+
+		{
+			let mut f = std::fs::File::create(&path).unwrap();
+			f.write_all(b"var:free x gen:std:I32 i32\nvar:free y i32\n").unwrap();
+		}
+		let err = api::parse_declarations_file(&path).unwrap_err();
+		let msg = err.to_string();
+		match err {
+			api::FileError::Parse(_, _) => (),
+			api::FileError::Io(_, e) => panic!("{}", e),
+		};
+		assert!(msg.starts_with(&format!("{}: line 2, column 12:",
+		                                 path.display())), msg);
+
+		let missing = std::path::Path::new("/nonexistent/x.fuzz");
+		match api::parse_declarations_file(missing) {
+			Err(api::FileError::Io(ref p, _)) => assert_eq!(p, "/nonexistent/x.fuzz"),
+			Err(e) => panic!("{}", e),
+			Ok(_) => panic!("read a nonexistent file"),
+		};
+	}
+
+	#[test]
+	fn empty_program() {
+		for text in vec!["", " \n\t\n"] {
+			let mut pgm: api::Program = fuzz::parse_LProgram(text).unwrap();
+			assert_eq!(pgm.declarations.len(), 0);
+			assert_eq!(pgm.statements.len(), 0);
+			pgm.analyze().unwrap();
+			assert_eq!(pgm.n_states(), 1);
+			let mut strm: Vec<u8> = Vec::new();
+			pgm.codegen_case(0, &vec![], &mut strm).unwrap();
+			let code = String::from_utf8(strm).unwrap();
+			assert!(code.contains("int main() {\n"), code);
+			assert!(code.ends_with("\treturn 0;\n}\n"), code);
+			let mut strm: Vec<u8> = Vec::new();
+			pgm.codegen_libfuzzer(&vec![], &mut strm).unwrap();
+			assert!(pgm.done());
+		}
+
+		use std::io::Write;
+		let dir = tempdir::TempDir::new("apifuzz").unwrap();
+		let path = dir.path().join("empty.fuzz");
+		{
+			let mut f = std::fs::File::create(&path).unwrap();
+			f.write_all(b"\n\n").unwrap();
+		}
+		let decls = api::parse_declarations_file(&path).unwrap();
+		assert_eq!(decls.len(), 0);
+	}
+
+	#[test]
+	fn generate_to_dir() {
+		use std::io::Read;
+		let decls = vec![];
+		let stmts = vec![free_i32("x")];
+		let mut pgm = api::Program::new(&decls, &stmts);
+		pgm.analyze().unwrap();
+		let tmp = tempdir::TempDir::new("apifuzz").unwrap();
+		let dir = tmp.path().join("cases");
+		let hdrs = vec!["stdint.h"];
+		let paths = pgm.generate_to_dir(&dir, 0..4, "case_{index:08}.c", &hdrs)
+			.unwrap();
+		let names: Vec<String> = paths.iter()
+			.map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+			.collect();
+		assert_eq!(names, vec!["case_00000000.c", "case_00000001.c",
+		                       "case_00000002.c", "case_00000003.c"]);
+		for (case, path) in paths.iter().enumerate() {
+			assert!(path.starts_with(&dir));
+			let mut code = String::new();
+			std::fs::File::open(path).unwrap().read_to_string(&mut code).unwrap();
+			let mut expected: Vec<u8> = Vec::new();
+			pgm.codegen_case(case, &hdrs, &mut expected).unwrap();
+			assert_eq!(code.as_bytes(), &expected[..]);
+			assert!(code.contains("#include <stdint.h>"), code);
+			assert!(code.contains("int32_t x = "), code);
+			assert!(code.contains("int main() {"), code);
+		}
+		std::fs::remove_dir_all(&dir).unwrap();
+
+		let paths = pgm.generate_to_dir(&dir, 5..7, "{index}-{index:3}", &hdrs)
+			.unwrap();
+		assert_eq!(paths, vec![dir.join("5-  5"), dir.join("6-  6")]);
+		std::fs::remove_dir_all(&dir).unwrap();
+
+		for tmpl in vec!["case.c", "case_{idx}.c", "case_{index:x}.c",
+		                 "case_{index"] {
+			match pgm.generate_to_dir(&dir, 0..1, tmpl, &hdrs) {
+				Err(api::CaseFileError::Template(ref t, _)) => assert_eq!(t, tmpl),
+				Err(e) => panic!("{}", e),
+				Ok(_) => panic!("accepted template '{}'", tmpl),
+			};
+		}
+		assert!(!dir.exists());
+
+		// a file is in the way of the directory.
+		let notdir = tmp.path().join("notdir");
+		std::fs::File::create(&notdir).unwrap();
+		match pgm.generate_to_dir(&notdir, 0..1, "{index}.c", &hdrs) {
+			Err(api::CaseFileError::Io(ref p, _)) =>
+				assert_eq!(p, &notdir.display().to_string()),
+			Err(e) => panic!("{}", e),
+			Ok(_) => panic!("wrote into a file"),
+		};
+	}
+
+	#[test]
+	fn dictionary_generator() {
+		use std::io::Write;
+		let dir = tempdir::TempDir::new("apifuzz").unwrap();
+		let path = dir.path().join("http.dict");
+		{
+			let mut f = std::fs::File::create(&path).unwrap();
+			f.write_all(b"GET\nPOST\r\n\nHEAD\n").unwrap();
+		}
+		let path = path.to_str().unwrap();
+		let s = format!("var:free verb gen:dict(\"{}\") pointer char\n", path);
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let gen = pgm.symlookup("verb").unwrap().generator.clone();
+		assert_eq!(gen.name(), "std:dict");
+		assert_eq!(gen.n_state(), 3 + 3);
+		assert_eq!(gen.value(), "\"GET\"");
+
+		let s = "var:free verb gen:dict(\"/nonexistent/x.dict\") pointer char\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		let err = pgm.analyze().unwrap_err();
+		assert!(err.starts_with("cannot read dictionary '/nonexistent/x.dict'"),
+		        err);
+	}
+
+	#[test]
+	fn generation_manifest() {
+		use std::sync::{Arc, Mutex};
+		let s = "var:free x gen:std:I32 i32\n".to_string() +
+			"var:free str gen:cstring pointer char\n" +
+			"var:constrained r int\n" +
+			"function:decl f int {i32, pointer char,}\n" +
+			"r = function:call f { x str }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		pgm.set_seed(11);
+		let hdrs: Vec<&str> = vec!["stdlib.h"];
+		let ncases = 40;
+		let mut codes: Vec<(usize, Vec<u8>)> = Vec::new();
+		let mut manifest: Vec<u8> = Vec::new();
+		pgm.generate_with_manifest(0..ncases, &hdrs, |case, code| {
+			codes.push((case, code));
+			Ok(())
+		}, &mut manifest).unwrap();
+		let manifest = String::from_utf8(manifest).unwrap();
+		let lines: Vec<&str> = manifest.lines().collect();
+		assert_eq!(lines.len(), ncases);
+		assert_eq!(codes.len(), ncases);
+		for (&(case, ref code), line) in codes.iter().zip(lines.iter()) {
+			let code = String::from_utf8(code.clone()).unwrap();
+			assert!(line.starts_with(&format!("{{\"case\":{},\"values\":{{", case)));
+			// the values are what the declarations in the code assign.
+			for &(prefix, var) in [("int32_t x = ", "x"),
+			                       ("char* str = ", "str")].iter() {
+				let decl = code.lines().map(|l| l.trim())
+					.find(|l| l.starts_with(prefix)).unwrap();
+				let value = &decl[prefix.len()..decl.len()-1];
+				let entry = format!("\"{}\":\"{}\"", var, util::json_escape(value));
+				assert!(line.contains(&entry), "{} not in {}", entry, line);
+			}
+			assert!(!line.contains("\"r\""));
+			// str varies fastest.  The edges of x are INT_MIN, 0 and INT_MAX,
+			// and those of str are NULL, "" and a very long string.
+			let extreme = format!("\"extreme\":{{\"x\":{},\"str\":{}}}}}",
+			                      [0, 3, 6].contains(&(case / 14)),
+			                      [0, 1, 7].contains(&(case % 14)));
+			assert!(line.ends_with(&extreme), "{} does not end {}", line, extreme);
+		}
+
+		// the parallel path makes the same lines.
+		let parallel: Arc<Mutex<Vec<(usize, String)>>> =
+			Arc::new(Mutex::new(Vec::new()));
+		let sinklines = parallel.clone();
+		pgm.generate_parallel_with_manifest(0, ncases, 3, &hdrs,
+			move |case, _, line| {
+				sinklines.lock().unwrap().push((case, line));
+				Ok(())
+			}
+		).unwrap();
+		let mut parallel = parallel.lock().unwrap().clone();
+		parallel.sort();
+		let parallel: Vec<&str> = parallel.iter().map(|l| l.1.as_str()).collect();
+		assert_eq!(parallel, lines);
+	}
+
+	#[test]
+	fn log_call_results() {
+		let s = "var:free x gen:std:I32 i32\n".to_string() +
+			"var:constrained r int\n" +
+			"var:constrained p pointer void\n" +
+			"function:decl get int {i32,}\n" +
+			"function:decl put void {i32,}\n" +
+			"function:decl grab pointer void {}\n" +
+			"r = function:call get { x }\n" +
+			"function:call put { r }\n" +
+			"p = function:call grab {}\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let hdrs: Vec<&str> = vec!["stdlib.h", "stdio.h"];
+		let mut plain: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &hdrs, &mut plain).unwrap();
+		let plain = String::from_utf8(plain).unwrap();
+		assert!(!plain.contains("fprintf"));
+		assert!(!plain.contains("errno"));
+
+		pgm.set_log_calls(true);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("#include <errno.h>\n"), code);
+		assert_eq!(code.matches("#include <stdio.h>").count(), 1);
+		let log = |n: usize, var: &str| format!("fprintf(stderr, \
+			\"call {} -> %ld (errno=%d)\\n\", (long){}, errno);", n, var);
+		let lines: Vec<&str> = code.lines().map(|l| l.trim()).collect();
+		let get = lines.iter().position(|l| *l == "r = get(x);").unwrap();
+		assert_eq!(lines[get+1], log(0, "r"));
+		let put = lines.iter().position(|l| *l == "put(r);").unwrap();
+		assert!(!lines[put+1].contains("fprintf"));
+		let grab = lines.iter().position(|l| *l == "p = grab();").unwrap();
+		assert_eq!(lines[grab+1], log(1, "p"));
+		assert_eq!(code.matches("fprintf").count(), 2);
+	}
+
+	#[test]
+	fn numbered_case_functions() {
+		let s = "var:free x gen:std:I32 i32\n".to_string() +
+			"function:decl use_x void {i32,}\n" +
+			"constraint:new x > 0\n" +
+			"function:call use_x { x }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		pgm.set_seed(3);
+		let hdrs: Vec<&str> = vec!["stdlib.h"];
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_numbered_functions(2..6, &hdrs, &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		let defs: Vec<&str> = code.lines()
+			.filter(|l| l.starts_with("static int test_case_")).collect();
+		assert_eq!(defs, vec!["static int test_case_0002(void) {",
+		                      "static int test_case_0003(void) {",
+		                      "static int test_case_0004(void) {",
+		                      "static int test_case_0005(void) {"]);
+		let dispatch = &code[code.find("void run_all(void) {").unwrap()..];
+		let calls: Vec<&str> = dispatch.lines()
+			.filter(|l| l.contains("test_case_")).collect();
+		assert_eq!(calls, vec!["\ttest_case_0002();", "\ttest_case_0003();",
+		                       "\ttest_case_0004();", "\ttest_case_0005();"]);
+		assert!(code.contains("\tif(!(x > 0)) { return 0; }\n"), code);
+		assert!(!code.contains("int main"));
+
+		// each function holds the same code as the program for its case.
+		let mut single: Vec<u8> = Vec::new();
+		pgm.codegen_case(4, &hdrs, &mut single).unwrap();
+		let single = String::from_utf8(single).unwrap();
+		let decl = single.lines().find(|l| l.contains("x = ")).unwrap();
+		let func = &code[code.find("test_case_0004(void)").unwrap()..];
+		assert_eq!(func.lines().nth(1).unwrap(), decl);
+	}
+
+	#[test]
+	fn generate_budgeted_resume() {
+		let s = "var:free x gen:std:I32 i32\n".to_string() +
+			"var:free str gen:cstring pointer char\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
 		};
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let hdrs: Vec<&str> = vec!["stdlib.h"];
+		let mut cases: Vec<usize> = Vec::new();
+		let token = pgm.generate_budgeted(&hdrs,
+			|case, _| { cases.push(case); Ok(()) }, Some(10), None).unwrap();
+		assert_eq!(cases, (0..10).collect::<Vec<usize>>());
+		assert_eq!(token.case, 10);
+		assert!(!token.done());
+
+		let mut resumed: Vec<usize> = Vec::new();
+		let token = pgm.generate_from(token, &hdrs,
+			|case, _| { resumed.push(case); Ok(()) }, Some(3), None).unwrap();
+		assert_eq!(resumed, vec![10, 11, 12]);
+		assert_eq!(token.case, 13);
+
+		// an expired deadline generates nothing.
+		let past = std::time::Instant::now();
+		let again = pgm.generate_from(token, &hdrs, |_, _| panic!("generated"),
+		                              None, Some(past)).unwrap();
+		assert_eq!(again, token);
+
+		let token = pgm.generate_from(token, &hdrs, |_, _| Ok(()), None, None)
+			.unwrap();
+		assert!(token.done());
+		assert_eq!(token.case, pgm.n_states());
 	}
 
 	#[test]
-	fn struct_multiple_fields() {
-		let s = "struct Entry {\n".to_string() +
-			"pointer char key;\n" +
-			"pointer void value;\n" +
-		"}";
-		assert!(fuzz::parse_LDeclarations(s.as_str()).is_ok());
-		assert_eq!(fuzz::parse_LDeclarations(s.as_str()).unwrap().len(), 1);
-		let ref decl: api::Declaration =
-			fuzz::parse_LDeclarations(s.as_str()).unwrap()[0];
-		let decl = match decl {
-			&api::Declaration::UDT(ref udt) => udt,
-			_ => panic!("invalid declaration parse {:?}", decl),
+	fn stats_exhaustive() {
+		let s = "enum ACTION { FIND = 0, ENTER = 1, }\n".to_string() +
+			"var:free a gen:Enum enum ACTION\n" +
+			"var:free x gen:std:I32 i32\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
 		};
-		use api::DeclType;
-		match decl {
-			&DeclType::Basic(_) => panic!("type should be UDT, is Basic"),
-			&DeclType::Enum(_, _) => panic!("type should be UDT, is Enum"),
-			&DeclType::EnumRef(_) => panic!("type should be UDT, is EnumRef"),
-			&DeclType::StructRef(_) => panic!("type should be UDT, is StructRef"),
-			&DeclType::Struct(ref nm, ref decllist) => {
-				assert_eq!(*nm, "Entry".to_string());
-				assert_eq!(decllist.len(), 2);
-				let ref key: api::UDTDecl = decllist[0];
-				assert_eq!(key.name, "key");
-				match key.ty {
-					DeclType::Struct(_, _) => panic!("incorrect type UDT for 'key'"),
-					DeclType::Enum(_, _) => panic!("incorrect type Enum for 'key'"),
-					DeclType::EnumRef(_) => panic!("incorrect type for 'key'"),
-					DeclType::StructRef(_) => panic!("incorrect type for 'key'"),
-					DeclType::Basic(ref blt) => {
-						let ch = Type::Builtin(Native::Character);
-						assert_eq!(blt, &Type::Pointer(Box::new(ch)));
-					}
-				}
-				let ref value: api::UDTDecl = decllist[1];
-				assert_eq!(value.name, "value");
-				match value.ty {
-					DeclType::Struct(_, _) => panic!("incorrect type UDT for 'key'"),
-					DeclType::Enum(_, _) => panic!("incorrect type Enum for 'key'"),
-					DeclType::EnumRef(_) => panic!("incorrect type for 'key'"),
-					DeclType::StructRef(_) => panic!("incorrect type for 'key'"),
-					DeclType::Basic(ref blt) => {
-						let ch = Type::Builtin(Native::Void);
-						assert_eq!(blt, &Type::Pointer(Box::new(ch)));
-					}
-				}
-			},
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert!(!pgm.stats().completed);
+		loop {
+			let mut code: Vec<u8> = Vec::new();
+			pgm.codegen(&mut code).unwrap();
+			pgm.record(&code);
+			if pgm.done() {
+				break;
+			}
+			pgm.next();
+		}
+		let stats = pgm.stats();
+		assert!(stats.completed);
+		assert_eq!(stats.emitted, pgm.n_states());
+		assert_eq!(stats.total, pgm.n_states());
+		assert_eq!(stats.fraction(), 1.0);
+		assert_eq!(stats.ranges.len(), 2);
+		assert_eq!(stats.ranges[0], ("a".to_string(), 0, 1));
+		let nx = pgm.symlookup("x").unwrap().generator.n_state();
+		assert_eq!(stats.ranges[1], ("x".to_string(), 0, nx-1));
+
+		// sampling a single case covers only part of the space.
+		let mut sampled: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
 		};
+		sampled.analyze().unwrap();
+		let mut strm: Vec<u8> = Vec::new();
+		sampled.codegen_case(3, &vec!["stdlib.h"], &mut strm).unwrap();
+		let stats = sampled.stats();
+		assert!(!stats.completed);
+		assert_eq!(stats.emitted, 1);
+		assert_eq!(stats.fraction(), 1.0 / sampled.n_states() as f64);
 	}
 
 	#[test]
-	fn enum_single() {
-		let s = "enum Enumeration { BLAH = 0 , }";
-		match fuzz::parse_LDeclarations(s) {
-			Ok(_) => {},
-			Err(e) => panic!("{:?}", e),
-		};
-		let t = "enum Enumeration { BLA = 0 , }";
-		assert!(fuzz::parse_LDeclarations(t).is_ok());
-		assert_eq!(fuzz::parse_LDeclarations(t).unwrap().len(), 1);
+	fn distinct_count_bounded() {
+		use api::DistinctCount;
+		let mut direct = DistinctCount::direct(100);
+		for v in [3, 7, 3, 99].iter() {
+			direct.insert(*v);
+		}
+		assert_eq!(direct.count(), 3);
+
+		// exact up to the limit, then approximate in a fixed bitmap.
+		let mut cnt = DistinctCount::new();
+		for v in 0..api::MAX_EXACT_DISTINCT as u64 {
+			cnt.insert(v);
+			cnt.insert(v);
+		}
+		assert_eq!(cnt.count(), api::MAX_EXACT_DISTINCT);
+		let n = 4 * api::MAX_EXACT_DISTINCT;
+		for v in 0..n as u64 {
+			cnt.insert(v);
+		}
+		assert!(cnt.exact.is_empty());
+		assert_eq!(cnt.bitmap.len(), api::DISTINCT_BITS / 64);
+		let est = cnt.count() as f64;
+		assert!((est - n as f64).abs() < 0.02 * n as f64, "estimated {}", est);
 	}
 
 	#[test]
-	fn enum_multi() {
-		let s = "enum Enumeration { FOO = 0 , BAR = 1 , BAZ = 42 , }";
-		let decls = match fuzz::parse_LDeclarations(s) {
-			Ok(parsed) => parsed,
+	fn libfuzzer_harness() {
+		let s = "enum ACTION { FIND = 0, ENTER = 1, }\n".to_string() +
+			"var:free act gen:Enum enum ACTION\n" +
+			"var:free x gen:std:I32 i32\n" +
+			"constraint:new x > 0\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
 			Err(e) => panic!("{:?}", e),
 		};
-		assert_eq!(decls.len(), 1);
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_libfuzzer(&vec!["stdlib.h"], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("int LLVMFuzzerTestOneInput(const uint8_t* data, \
+		                       size_t size) {"));
+		assert!(code.contains("switch(st_act)"));
+		assert!(code.contains("switch(st_x)"));
+		assert!(code.contains("case 1: { ACTION act_1 = ENTER; act = act_1; \
+		                       break; }"));
+		assert!(code.contains("if(!(x > 0)) { return 0; }"));
+		assert!(!code.contains("exit("));
+
+		// a 64-element array has too many states to list.
+		let s = "struct big { i32 v[64]; }\n\
+			var:free b gen:Struct struct big\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert_eq!(pgm.n_states(), usize::max_value());
+		let mut strm: Vec<u8> = Vec::new();
+		let err = pgm.codegen_libfuzzer(&vec![], &mut strm).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+		assert!(err.to_string().contains("'b' has too many states"), "{}", err);
 	}
 
 	#[test]
-	fn struct_fvar_single() {
-		let s = "struct X { } var:free blah gen:I32 i32";
-		let decls = match fuzz::parse_LDeclarations(s) {
-			Ok(parsed) => parsed,
+	fn null_pointer_var() {
+		let s = "var:free p gen:null pointer void\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s) {
+			Ok(p) => p,
 			Err(e) => panic!("{:?}", e),
 		};
-		assert_eq!(decls.len(), 2);
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let p = pgm.symlookup("p").unwrap();
+		assert_eq!(p.generator.name(), "std:null");
+		assert_eq!(p.generator.n_state(), 1);
+		assert_eq!(p.generator.value(), "NULL");
+		assert_eq!(pgm.n_states(), 1);
 	}
 
 	#[test]
-	fn parse_function_new() {
-		let s = "function:decl hcreate_r int {usize, pointer struct hsearch_data,}";
-		let decls: Vec<api::Declaration> = match fuzz::parse_LDeclarations(s) {
-			Ok(parsed) => parsed,
-			Err(e) => panic!("{:?}", e),
+	fn heap_pointer_var() {
+		let s = "struct pt { i32 x; }\n".to_string() +
+			"var:free p gen:heap pointer struct pt\n" +
+			"function:decl draw void {pointer struct pt,}\n" +
+			"function:call draw { p }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		assert_eq!(pgm.n_states(), 2);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(1, &vec![], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("#include <stdlib.h>\n"), code);
+		assert!(code.contains("struct pt* p = (struct pt *)malloc(\
+		                       sizeof(struct pt));"), code);
+		assert!(code.contains("draw(p);\n\tfree(p);\n"), code);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_libfuzzer(&vec![], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("draw(p);\n\tfree(p);\n"), code);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(0, &vec!["stdlib.h"], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("struct pt* p = NULL;"), code);
+		assert_eq!(code.matches("#include <stdlib.h>").count(), 1);
+
+		// a destructor frees the memory itself.
+		let s = "struct pt { i32 x; }\n".to_string() +
+			"var:free p gen:heap pointer struct pt\n" +
+			"function:delete release void {pointer struct pt,}\n" +
+			"function:call release { p }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen_case(1, &vec![], &mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("release(p);"), code);
+		assert!(!code.contains("free(p)"), code);
+
+		let s = "var:free n gen:heap i32\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s).unwrap();
+		assert!(pgm.analyze().unwrap_err().contains("only pointers"));
+	}
+
+	#[test]
+	fn dsl_round_trip() {
+		let samples = vec![
+			"struct entry {\n char key; pointer char data; u32 flags:3; \
+			 i64 len @ 8; enum ACTION act; struct { u8 a; u8 b; }; }\n\
+			 enum ACTION { FIND, ENTER = 4, EXIT = 0x10, QUIT = 'q', }\n\
+			 struct hsearch_data {}",
+			"var:free tbl gen:opaque struct hsearch_data\n\
+			 var:free x gen:std:I32 i32\n\
+			 var:shared p gen:null restrict aligned(16) pointer pointer void\n\
+			 var:free e gen:enum sample(3) enum ACTION\n\
+			 var:free fl gen:values(O_RDONLY, O_CREAT|O_TRUNC) int\n\
+			 var:constrained r pointer struct entry",
+			"function:new hcreate_r int {usize, pointer struct hsearch_data,}\n\
+			 function:delete hdestroy_r void {pointer struct hsearch_data,}\n\
+			 function:decl getpid int {}\n\
+			 function:decl fill void {i32,} precondition x > 0; \
+			 precondition x < 8;",
+		];
+		for s in samples.iter() {
+			let decls = match fuzz::parse_LDeclarations(s) {
+				Ok(d) => d,
+				Err(e) => panic!("{}", util::parse_error(s, e)),
+			};
+			let printed: Vec<String> = decls.iter().map(|d| d.to_dsl()).collect();
+			let printed = printed.join("\n");
+			let reparsed = match fuzz::parse_LDeclarations(printed.as_str()) {
+				Ok(d) => d,
+				Err(e) => panic!("{}\n{}", util::parse_error(&printed, e), printed),
+			};
+			assert_eq!(decls, reparsed);
+			// printing is canonical: a second round trip changes nothing.
+			let again: Vec<String> = reparsed.iter().map(|d| d.to_dsl()).collect();
+			assert_eq!(printed, again.join("\n"));
+		}
+	}
+
+	#[test]
+	fn buffer_element_argument() {
+		let s = "var:free buf gen:buffer pointer i32\n".to_string() +
+			"function:decl get i32 {i32,}\n" +
+			"function:decl set void {pointer i32,}\n" +
+			"function:call get { buf[2] }\n" +
+			"function:call set { &buf[2] }\n" +
+			"function:call set { op:& buf[255] }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
 		};
-		assert_eq!(decls.len(), 1);
-		let fqn = match decls[0] {
-			api::Declaration::Function(ref f) => f,
-			_ => panic!("non function type {:?}", decls[0]),
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut code: Vec<u8> = Vec::new();
+		pgm.codegen(&mut code).unwrap();
+		let code = String::from_utf8(code).unwrap();
+		assert!(code.contains("get(buf[2]);"), code);
+		assert!(code.contains("set(&buf[2]);"), code);
+		assert!(code.contains("set(&buf[255]);"), code);
+
+		// ops can be listed, outermost first.
+		let s = "var:free buf gen:buffer pointer i32\n".to_string() +
+			"var:free n gen:std:I32 i32\n" +
+			"function:decl get i32 {i32,}\n" +
+			"function:decl set void {pointer i32,}\n" +
+			"function:call set { op:addressof,index(3) buf }\n" +
+			"function:call get { op:deref,addressof n }\n" +
+			"function:call get { op:*,addressof buf[1] }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
 		};
-		assert_eq!(fqn.name, "hcreate_r");
-		match fqn.retval {
-			api::DeclType::Basic(ref ty) => match ty {
-				&Type::Builtin(ref t) => assert_eq!(*t, Native::Integer),
-				_ => panic!("basic type, but {:?}, not integer", ty),
-			},
-			_ => panic!("retval should be a basic type, not {:?}", fqn.retval),
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut code: Vec<u8> = Vec::new();
+		pgm.codegen(&mut code).unwrap();
+		let code = String::from_utf8(code).unwrap();
+		assert!(code.contains("set(&buf[3]);"), code);
+		assert!(code.contains("get(n);"), code);
+		assert!(code.contains("get(buf[1]);"), code);
+
+		// ops that do not combine are kept, as in "**args".
+		let s = "var:free args gen:argv pointer pointer char\n".to_string() +
+			"var:free buf gen:buffer pointer i32\n" +
+			"function:decl put int {char,}\n" +
+			"function:decl fill void {usize,}\n" +
+			"function:call put { op:deref,deref args }\n" +
+			"function:call put { op:deref args[1] }\n" +
+			"function:call fill { op:sizeof,deref buf }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
 		};
-		assert_eq!(fqn.parameters.len(), 2);
-		match fqn.parameters[0] {
-			api::DeclType::Basic(ref ty) => match ty {
-				&Type::Builtin(ref t) => assert_eq!(*t, Native::Usize),
-				_ => panic!("basic type, but {:?} not usize", ty),
-			},
-			_ => panic!("arg0 should be a basic type, not {:?}", fqn.parameters[0]),
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut code: Vec<u8> = Vec::new();
+		pgm.codegen(&mut code).unwrap();
+		let code = String::from_utf8(code).unwrap();
+		assert!(code.contains("put(**args);"), code);
+		assert!(code.contains("put(*args[1]);"), code);
+		assert!(code.contains("fill(sizeof(*buf));"), code);
+
+		// ops that cannot be applied are parse errors.
+		for &(call, err) in [
+			("op:addressof,addressof n", "cannot be applied to the result of"),
+			("op:deref,lengthof buf", "cannot be applied to the result of"),
+			("op:deref,frob n", "unknown operation 'frob'"),
+			("op:deref,foo(2) n", "unknown operation 'foo(2)'"),
+			("op:deref,index(-1) n", "error parsing index '-1'"),
+		].iter() {
+			let s = "var:free n gen:std:I32 i32\n".to_string() +
+				"var:free buf gen:buffer pointer i32\n" +
+				"function:decl get i32 {i32,}\n" +
+				"function:call get { " + call + " }\n";
+			match fuzz::parse_LProgram(s.as_str()) {
+				Ok(_) => panic!("parsed '{}'", call),
+				Err(e) => {
+					let msg = util::parse_error(s.as_str(), e);
+					assert!(msg.contains(err), msg);
+				},
+			};
+		}
+
+		// the largest buffer has 256 elements.
+		let s = "var:free buf gen:buffer pointer i32\n".to_string() +
+			"function:decl get i32 {i32,}\n" +
+			"function:call get { buf[256] }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		let err = pgm.analyze().unwrap_err();
+		assert!(err.contains("index 256 is out of bounds of 'buf'"), err);
+
+		let s = "var:free n gen:std:I32 i32\n".to_string() +
+			"function:decl get i32 {i32,}\n" +
+			"function:call get { n[0] }\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		let err = pgm.analyze().unwrap_err();
+		assert!(err.contains("'n' is not a pointer"), err);
+	}
+
+	#[test]
+	fn parse_preconditions() {
+		let s = "var:free n gen:std:I32 i32\n".to_string() +
+			"function:decl fill void {i32,} precondition n > 0;\n" +
+			"function:call fill { n } precondition n < 1<<20; \
+			 precondition (n & 3) == 0;\n";
+		let pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
 		};
-		let ptr: &Type = match fqn.parameters[1] {
-			api::DeclType::Basic(ref ptr) => ptr,
-			_ => panic!("invalid arg1: {:?}", fqn.parameters[1]),
+		match pgm.declarations[1] {
+			api::Declaration::Function(ref f) =>
+				assert_eq!(f.preconditions, vec!["n>0"]),
+			ref d => panic!("not a function: {:?}", d),
 		};
-		let boxptr = match ptr {
-			&Type::Pointer(ref b) => b,
-			_ => panic!("invalid ptr type {:?}", ptr),
+		use opcode::UOp;
+		let call = api::Expr::Call("fill".to_string(), Box::new(vec![
+			api::Expr::VarRef(UOp::None, "n".to_string())]));
+		assert_eq!(pgm.ast, vec![
+			api::Stmt::Guarded(vec!["n<1<<20".to_string(), "(n&3)==0".to_string()],
+			                   Box::new(api::Stmt::Basic(call))),
+		]);
+
+		// adjacent operators are kept apart, or "- -1" would be a decrement.
+		let s = "var:free n gen:std:I32 i32\n".to_string() +
+			"function:decl fill void {i32,}\n" +
+			"function:call fill { n } precondition n - -1 > 0; \
+			 precondition n + +n > 0;\n";
+		let pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
 		};
-		use std::ops::Deref;
-		match boxptr.deref() {
-			&Type::Struct(ref nm, _) => assert_eq!(nm, "hsearch_data"),
-			_ => panic!("invalid box ptr {:?}", boxptr),
+		match pgm.ast[0] {
+			api::Stmt::Guarded(ref conds, _) =>
+				assert_eq!(*conds, vec!["n- -1>0", "n+ +n>0"]),
+			ref st => panic!("not guarded: {:?}", st),
 		};
 	}
 
 	#[test]
-	fn parse_two_function_decls() {
-		let s = "function:decl hcreate_r int {".to_string() +
-			"usize, pointer struct hsearch_data," +
-		"}" +
-		"function:decl hsearch_r int {" +
-			"int, int, pointer pointer int, pointer struct hsearch_data," +
-		"}";
-		let decls: Vec<api::Declaration> =
-			match fuzz::parse_LDeclarations(s.as_str()) {
-			Ok(parsed) => parsed,
-			Err(e) => panic!("{:?}", e),
-		};
-		assert_eq!(decls.len(), 2);
-		let fqn = match decls[0] {
-			api::Declaration::Function(ref f) => f,
-			_ => panic!("non function type {:?}", decls[0]),
+	fn precondition_codegen() {
+		// 'register' is a C keyword, so the variable gets another name.
+		let s = "var:free register gen:std:I32 i32\n".to_string() +
+			"var:free buf gen:std:Usize usize\n" +
+			"function:decl fill void {i32, usize,} \
+			 precondition register > 0;\n" +
+			"function:call fill { register buf } precondition buf != SIZE_MAX;\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
 		};
-		assert_eq!(fqn.name, "hcreate_r");
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let nm = pgm.symlookup("register").unwrap().codegen_name();
+		assert!(nm != "register");
+		let mut code: Vec<u8> = Vec::new();
+		pgm.codegen(&mut code).unwrap();
+		let code = util::indent_c(&String::from_utf8(code).unwrap());
+		let expected = format!("if(!({0}>0)) {{\n\treturn 0;\n}}\n\
+		                        if(!(buf!=SIZE_MAX)) {{\n\treturn 0;\n}}\n\
+		                        fill({0}, buf);\n", nm);
+		assert!(code.contains(&expected), "{}", code);
 	}
 
 	#[test]
-	fn opaque_struct_in_function() {
+	fn shared_variable() {
+		// the second declaration of 'tbl' refers to the first one.
 		let s = "struct hsearch_data {}\n".to_string() +
-		"var:free tbl gen:opaque struct hsearch_data\n" +
-		"function:decl hcreate_r int {" +
-			"usize, pointer struct hsearch_data,\n" +
-		"}\n";
-		let decls: Vec<api::Declaration> =
-			match fuzz::parse_LDeclarations(s.as_str()) {
-			Ok(parsed) => parsed,
-			Err(e) => panic!("{:?}", e),
+			"var:shared tbl gen:opaque struct hsearch_data\n" +
+			"var:free nel gen:std:Usize usize\n" +
+			"var:shared tbl gen:opaque struct hsearch_data\n" +
+			"function:new hcreate_r int {usize, pointer struct hsearch_data,}\n" +
+			"function:delete hdestroy_r void {pointer struct hsearch_data,}\n" +
+			"function:call hcreate_r { nel op:& tbl }\n" +
+			"function:call hdestroy_r { op:& tbl }\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+			Ok(p) => p,
+			Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
 		};
-		assert_eq!(decls.len(), 3);
-		// should assert that the hcreate_r's 2nd arg == types[0].
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut code: Vec<u8> = Vec::new();
+		pgm.codegen(&mut code).unwrap();
+		let code = String::from_utf8(code).unwrap();
+		let decls: Vec<&str> = code.lines()
+			.filter(|l| l.trim().starts_with("struct hsearch_data")).collect();
+		assert_eq!(decls.len(), 1, "{}", code);
+		assert!(decls[0].trim().starts_with("struct hsearch_data tbl ="));
+		assert!(code.contains("hcreate_r(nel, &tbl);"), code);
+		assert!(code.contains("hdestroy_r(&tbl);"), code);
+
+		// one struct can stand in for several parameters of the same call; it
+		// is declared once, even where it could be a temporary otherwise.
+		for gnu in [false, true].iter() {
+			let s = "struct ctx { i32 n; }\n".to_string() +
+				"var:shared c gen:std:ctx struct ctx\n" +
+				"function:decl swap void {pointer struct ctx, pointer struct ctx,}\n" +
+				"function:call swap { op:& c op:& c }\n";
+			let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+			match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+			pgm.set_gnu_extensions(*gnu);
+			let mut code: Vec<u8> = Vec::new();
+			pgm.codegen(&mut code).unwrap();
+			let code = String::from_utf8(code).unwrap();
+			assert_eq!(code.matches("struct ctx c = {").count(), 1, "{}", code);
+			assert!(code.contains("\tswap(&c, &c);\n"), code);
+		}
+
+		// all declarations of a shared variable must agree.
+		let s = "var:shared x gen:std:Usize usize\n".to_string() +
+			"var:shared x gen:std:I32 i32\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		let err = pgm.analyze().unwrap_err();
+		assert!(err.contains("'x' is redeclared"), err);
+		let s = "var:free x gen:std:Usize usize\n".to_string() +
+			"var:shared x gen:std:Usize usize\n";
+		let mut pgm: api::Program = fuzz::parse_LProgram(s.as_str()).unwrap();
+		assert!(pgm.analyze().is_err());
 	}
 
 	#[test]
-	fn compound_expr() {
-		let s = "var:free x gen:std:I32 i32\n".to_string() +
-			"var:free y gen:std:I32 i32\n" +
-			"constraint:new x > 0 && y < 0\n";
-		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+	fn fixed_value_set() {
+		let s = "var:free flags gen:values(O_RDONLY, O_RDWR, O_CREAT|O_TRUNC) \
+		         i32\n";
+		let pgm: api::Program = match fuzz::parse_LProgram(s) {
+			Ok(p) => p,
+			Err(e) => panic!("{:?}", e),
+		};
+		match pgm.declarations[0] {
+			api::Declaration::Free(ref fvd) =>
+				assert_eq!(fvd.genname, "values(O_RDONLY, O_RDWR, O_CREAT|O_TRUNC)"),
+			ref d => panic!("not a free variable: {:?}", d),
+		};
+		let mut pgm = pgm;
+		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let flags = pgm.symlookup("flags").unwrap();
+		assert_eq!(flags.generator.name(), "std:values");
+		assert_eq!(flags.generator.n_state(), 3);
+		assert_eq!(pgm.n_states(), 3);
+
+		// nested parentheses and casts are passed through as well.
+		let s = "var:free v gen:values(f(1, 2), (unsigned)-1, 'a'+0x10) i32\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s) {
 			Ok(p) => p,
 			Err(e) => panic!("{:?}", e),
 		};
 		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let mut gen = pgm.symlookup("v").unwrap().generator.clone_box();
+		let mut values: Vec<String> = vec![gen.value()];
+		while !gen.done() {
+			gen.next();
+			values.push(gen.value());
+		}
+		assert_eq!(values, vec!["f(1, 2)", "(unsigned)-1", "'a'+0x10"]);
 	}
 
 	#[test]
-	fn field_expr() {
-		let s = "struct Entry {\n".to_string() +
-				"pointer char key;\n" +
-				"pointer void value;\n" +
-			"}\n" +
-			"var:free e gen:opaque struct Entry\n" +
-			"e.value = 0\n";
-		let mut pgm: api::Program = match fuzz::parse_LProgram(s.as_str()) {
+	fn wide_string_var() {
+		let s = "var:free ws gen:wstring pointer wchar_t\n";
+		let mut pgm: api::Program = match fuzz::parse_LProgram(s) {
 			Ok(p) => p,
 			Err(e) => panic!("{:?}", e),
 		};
 		match pgm.analyze() { Err(e) => panic!(e), Ok(_) => () };
+		let ws = pgm.symlookup("ws").unwrap();
+		let wchar = Type::Builtin(Native::WideCharacter);
+		assert_eq!(ws.typ, Type::Pointer(Box::new(wchar)));
+		assert_eq!(ws.generator.name(), "std:wstring");
+	}
+
+	fn free_i32(name: &str) -> api::Stmt {
+		let i32ty = api::DeclType::Basic(Type::Builtin(Native::I32));
+		let fvd = api::FreeVarDecl{name: name.to_string(),
+		                           genname: "".to_string(), ty: i32ty,
+		                           shared: false};
+		api::Stmt::Declaration(api::Declaration::Free(fvd))
+	}
+
+	#[test]
+	fn codegen_name_keyword() {
+		let mut pgm = api::Program::new(&vec![], &vec![free_i32("int")]);
+		pgm.analyze().unwrap();
+		let sym = pgm.symlookup("int").unwrap();
+		use typ::Name;
+		assert_eq!(sym.name(), "int");
+		assert_eq!(sym.codegen_name(), "int_");
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("int32_t int_ = "));
+	}
+
+	#[test]
+	fn codegen_name_unique() {
+		let mut pgm = api::Program::new(&vec![], &vec![free_i32("x"),
+		                                               free_i32("x")]);
+		pgm.analyze().unwrap();
+		let cnames: Vec<String> = pgm.symtab.iter().map(|s| s.codegen_name())
+			.collect();
+		assert_eq!(cnames, vec!["x".to_string(), "x_2".to_string()]);
+		let mut strm: Vec<u8> = Vec::new();
+		pgm.codegen(&mut strm).unwrap();
+		let code = String::from_utf8(strm).unwrap();
+		assert!(code.contains("int32_t x = "));
+		assert!(code.contains("int32_t x_2 = "));
 	}
 }
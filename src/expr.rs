@@ -5,11 +5,14 @@ use api;
 use function::Function;
 use opcode::{UOp, BinOp};
 use stmt::Code;
+use tc::literal;
 use typ::{Native, Type};
 
 #[derive(Clone,Debug)]
 pub enum Expression {
 	Basic(UOp, api::Symbol),
+	// ops that do not combine into one, outermost first; see UOp::apply_all.
+	Ops(Vec<UOp>, api::Symbol),
 	FConstant(f64),
 	IConstant(i64),
 	UConstant(u64),
@@ -22,6 +25,8 @@ pub enum Expression {
 	FqnCall(Function, Vec<Expression>),
 	// Field expression is a field of a struct.
 	Field(api::Symbol, String),
+	// A field of the struct that the symbol points to.
+	ArrowField(api::Symbol, String),
 }
 
 impl Expression {
@@ -31,14 +36,20 @@ impl Expression {
 				match *op {
 					UOp::AddressOf => Type::Pointer(Box::new(src.typ.clone())),
 					UOp::Deref => src.typ.dereference(),
+					UOp::Index(_) | UOp::AddressOfIndex(_) =>
+						op.result_type(src.typ.clone()),
 					UOp::Negate => {
 						println!("FIXME negate of unsigned type should be signed.");
 						src.typ.clone()
 					},
 					UOp::None => src.typ.clone(),
 					UOp::Not => src.typ.clone(),
+					UOp::SizeOf | UOp::LengthOf => Type::Builtin(Native::Usize),
 				}
 			},
+			&Expression::Ops(ref ops, ref src) => {
+				ops.iter().rev().fold(src.typ.clone(), |ty, op| op.result_type(ty))
+			},
 			&Expression::FConstant(_) => Type::Builtin(Native::F64),
 			&Expression::IConstant(_) => Type::Builtin(Native::I64),
 			&Expression::UConstant(_) => Type::Builtin(Native::U64),
@@ -52,20 +63,22 @@ impl Expression {
 			},
 			&Expression::Field(ref sym, ref fld) => {
 				// "cast" to the Struct type from sym's type.
-				let fields = match sym.typ {
-					Type::Struct(_, ref flds) => flds,
+				match sym.typ {
+					Type::Struct(_, _, _) => (),
 					_ =>
 						panic!("Field expr {} references {:?} type; must be a struct.",
 						       fld, sym.typ),
 				};
-				use typ;
-				let find_field_name = |f: &typ::Field| { f.0 == *fld };
-				let idx = match fields.iter().position(find_field_name) {
+				match sym.typ.field_type(fld) {
 					None => panic!("Struct '{:?}' has no field '{}'", sym.typ, fld),
-					Some(i) => i,
-				};
-				use std::ops::Deref;
-				fields[idx].1.deref().clone()
+					Some(ty) => ty,
+				}
+			},
+			&Expression::ArrowField(ref sym, ref fld) => {
+				match sym.typ.dereference().field_type(fld) {
+					None => panic!("'{:?}' points to no field '{}'", sym.typ, fld),
+					Some(ty) => ty,
+				}
 			},
 		}
 	}
@@ -75,8 +88,64 @@ impl Code for Expression {
 	fn codegen(&self, strm: &mut std::io::Write, program: &api::Program)
 		-> Result<(),Error> {
 		match self {
+			// sizeof needs parentheses for anything but an expression, so
+			// always give it them.
+			&Expression::Basic(UOp::SizeOf, ref src) => {
+				write!(strm, "sizeof({})", src.codegen_name())
+			},
+			// The length of a buffer is whatever its generator currently gives.
+			// Our copy of the symbol is from analysis time, so ask the program.
+			&Expression::Basic(UOp::LengthOf, ref src) => {
+				let sym = program.cnamelookup(&src.codegen_name()).unwrap();
+				let len = match sym.generator.length() {
+					None => panic!("'{}' is not a buffer", src.name),
+					Some(n) => n,
+				};
+				write!(strm, "{}", literal(len as i128, &Type::Builtin(Native::Usize)))
+			},
+			&Expression::Basic(UOp::Index(i), ref src) => {
+				write!(strm, "{}[{}]", src.codegen_name(), i)
+			},
+			&Expression::Basic(UOp::AddressOfIndex(i), ref src) => {
+				write!(strm, "&{}[{}]", src.codegen_name(), i)
+			},
+			// a temporary is a compound literal right where its address is taken.
+			// Unlike a variable in a statement expression, it lives until the end
+			// of the enclosing block, so the callee may keep the pointer.
+			&Expression::Basic(UOp::AddressOf, ref src)
+				if program.is_temporary(&src.codegen_name()) => {
+				let sym = program.cnamelookup(&src.codegen_name()).unwrap();
+				let value = program.emitted_value(&sym.name).unwrap();
+				match sym.typ {
+					Type::Struct(ref nm, _, _) =>
+						write!(strm, "&(struct {}){}", nm, value),
+					_ => panic!("temporary '{}' is not a struct", sym.name),
+				}
+			},
 			&Expression::Basic(ref op, ref src) => {
-				write!(strm, "{}{}", op.to_string(), src.name)
+				let nm = src.codegen_name();
+				let nm = program.value_macro(&nm).unwrap_or(nm);
+				write!(strm, "{}{}", op.to_string(), nm)
+			},
+			// the ops are applied from the innermost one out, so "*" twice is
+			// "**pp".  Only the innermost one can be indexed without parentheses.
+			&Expression::Ops(ref ops, ref src) => {
+				let nm = src.codegen_name();
+				let mut code = nm.clone();
+				for op in ops.iter().rev() {
+					code = match *op {
+						UOp::SizeOf => format!("sizeof({})", code),
+						UOp::Index(i) if code == nm => format!("{}[{}]", code, i),
+						UOp::Index(i) => format!("({})[{}]", code, i),
+						UOp::AddressOfIndex(i) if code == nm =>
+							format!("&{}[{}]", code, i),
+						UOp::AddressOfIndex(i) => format!("&({})[{}]", code, i),
+						UOp::Negate if code.starts_with("-") => format!("-({})", code),
+						UOp::LengthOf => unreachable!(), // see UOp::apply_all.
+						_ => format!("{}{}", op.to_string(), code),
+					};
+				}
+				write!(strm, "{}", code)
 			},
 			&Expression::FConstant(fpval) => {
 				write!(strm, "{:.16}", fpval)
@@ -93,18 +162,22 @@ impl Code for Expression {
 				rhs.codegen(strm, program)
 			},
 			&Expression::FqnCall(ref fqn, ref args) => {
-				assert_eq!(fqn.parameters.len(), args.len());
+				assert!(fqn.parameters.len() == args.len() ||
+				        (fqn.variadic && fqn.parameters.len() < args.len()));
 				try!(write!(strm, "{}(", fqn.name));
 				for (a, arg) in args.iter().enumerate() {
 					try!(arg.codegen(strm, program));
-					if a != fqn.parameters.len()-1 {
+					if a != args.len()-1 {
 						try!(write!(strm, ", "));
 					}
 				}
 				write!(strm, ")")
 			},
 			&Expression::Field(ref sym, ref fld) => {
-				write!(strm, "{}.{}", sym.name, fld)
+				write!(strm, "{}.{}", sym.codegen_name(), fld)
+			},
+			&Expression::ArrowField(ref sym, ref fld) => {
+				write!(strm, "{}->{}", sym.codegen_name(), fld)
 			},
 		}
 	}
@@ -130,7 +203,8 @@ mod test {
 		($vname:expr, $vtype:expr) => ({
 			let dt = api::DeclType::Basic($vtype);
 			let fvd = api::FreeVarDecl{name: $vname.to_string(),
-			                           genname: "".to_string(), ty: dt};
+			                           genname: "".to_string(), ty: dt,
+			                           shared: false};
 			api::Stmt::Declaration(api::Declaration::Free(fvd))
 		})
 	}
@@ -165,6 +239,20 @@ mod test {
 		let v3 = pgm.symlookup("var3").unwrap();
 		let expr = Expression::Basic(addrof, v3.clone());
 		cg_expect!(expr, "*var3", pgm);
+		drop(expr);
+
+		// sizeof is a usize, and always parenthesized.
+		let expr = Expression::Basic(UOp::SizeOf, v3.clone());
+		assert_eq!(expr.extype(), Type::Builtin(Native::Usize));
+		cg_expect!(expr, "sizeof(var3)", pgm);
+
+		// an index goes after the variable, even when we take its address.
+		let expr = Expression::Basic(UOp::Index(2), v3.clone());
+		assert_eq!(expr.extype(), Type::Builtin(Native::I32));
+		cg_expect!(expr, "var3[2]", pgm);
+		let expr = Expression::Basic(UOp::AddressOfIndex(2), v3.clone());
+		assert_eq!(expr.extype(), v3.typ);
+		cg_expect!(expr, "&var3[2]", pgm);
 	}
 
 	#[test]
@@ -8,6 +8,10 @@ pub struct Function {
 	pub retval: ReturnType,
 	pub parameters: Vec<Parameter>,
 	pub name: String,
+	// takes more arguments after 'parameters', as with printf's "...".
+	pub variadic: bool,
+	// returns an int that only means true (non-zero) or false (zero).
+	pub bool_return: bool,
 }
 impl Function {
 	pub fn new(nm: &str, rettype: &ReturnType, pm: &Vec<Parameter>) -> Self {
@@ -15,6 +19,8 @@ impl Function {
 			name: nm.to_string(),
 			retval: rettype.clone(),
 			parameters: pm.clone(),
+			variadic: false,
+			bool_return: false,
 		}
 	}
 }
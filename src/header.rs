@@ -0,0 +1,251 @@
+// Imports declarations from C headers, so that structs, enums and function
+// signatures need not be transcribed into the DSL by hand.  Headers are
+// parsed with libclang, so this is only built with the "header-import"
+// feature.
+use std::path::Path;
+use clang::{Clang, Entity, EntityKind, Index, TypeKind};
+use api::{DeclType, Declaration, FuncDecl, FuncKind, UDTDecl};
+use typ::{Native, Qualifier, Type};
+
+// Parses the header at 'path' and gives the declarations of the structs,
+// enums and functions in 'names'.  The structs and enums come first, then
+// the functions, each in the order of 'names'.  A struct that the header only
+// declares is opaque, as "struct x {}" is in the DSL.  Anything that the DSL
+// cannot express, such as a union field or a function pointer, is skipped,
+// and a warning about it is given along with the declarations; a name that
+// the header does not declare is an error.
+pub fn import_header(path: &Path, names: &[&str])
+	-> Result<(Vec<Declaration>, Vec<String>), String> {
+	let clang = try!(Clang::new());
+	let index = Index::new(&clang, false, false);
+	let tu = try!(index.parser(path).parse().map_err(|e| {
+		format!("cannot parse '{}': {}", path.display(), e)
+	}));
+	let mut udts: Vec<Declaration> = Vec::new();
+	let mut funcs: Vec<Declaration> = Vec::new();
+	let mut warnings: Vec<String> = Vec::new();
+	for name in names.iter() {
+		let entities: Vec<Entity> = tu.get_entity().get_children().into_iter()
+			.filter(|e| e.get_name().map_or(false, |nm| nm == *name))
+			.collect();
+		// prefer the definition of a struct to its forward declarations.
+		let entity = match entities.iter().find(|e| e.is_definition()) {
+			Some(e) => *e,
+			None => match entities.first() {
+				None => return Err(format!("'{}' is not declared in '{}'", name,
+				                           path.display())),
+				Some(e) => *e,
+			},
+		};
+		let decl = match entity.get_kind() {
+			EntityKind::StructDecl => import_struct(&entity).map(Declaration::UDT),
+			EntityKind::EnumDecl => Ok(Declaration::UDT(import_enum(&entity))),
+			EntityKind::FunctionDecl =>
+				import_function(&entity).map(Declaration::Function),
+			kind => Err(format!("{:?} is not supported", kind)),
+		};
+		match decl {
+			Err(e) => warnings.push(format!("skipping '{}': {}", name, e)),
+			Ok(d @ Declaration::Function(_)) => funcs.push(d),
+			Ok(d) => udts.push(d),
+		};
+	}
+	udts.append(&mut funcs);
+	Ok((udts, warnings))
+}
+
+fn import_struct(entity: &Entity) -> Result<DeclType, String> {
+	let name = entity.get_name().unwrap();
+	let mut fields: Vec<UDTDecl> = Vec::new();
+	for fld in entity.get_children().iter()
+		.filter(|e| e.get_kind() == EntityKind::FieldDecl) {
+		let fname = match fld.get_name() {
+			None => return Err("anonymous members are not supported".to_string()),
+			Some(nm) => nm,
+		};
+		let ty = try!(decl_type(fld.get_type().unwrap()).map_err(|e| {
+			format!("field '{}': {}", fname, e)
+		}));
+		let bits = fld.get_bit_field_width().map(|w| w as u32);
+		fields.push(UDTDecl{name: fname, ty: ty, bits: bits, offset: None});
+	}
+	Ok(DeclType::Struct(name, fields, false))
+}
+
+fn import_enum(entity: &Entity) -> DeclType {
+	let values = entity.get_children().iter()
+		.filter(|e| e.get_kind() == EntityKind::EnumConstantDecl)
+		.map(|e| {
+			let (v, _) = e.get_enum_constant_value().unwrap();
+			(e.get_name().unwrap(), v, v.to_string())
+		}).collect();
+	DeclType::Enum(entity.get_name().unwrap(), values)
+}
+
+fn import_function(entity: &Entity) -> Result<FuncDecl, String> {
+	let name = entity.get_name().unwrap();
+	let rtype = entity.get_result_type().unwrap();
+	let retval = try!(decl_type(rtype).map_err(|e| {
+		format!("return type: {}", e)
+	}));
+	let mut params: Vec<DeclType> = Vec::new();
+	for (i, arg) in entity.get_arguments().unwrap_or(vec![]).iter().enumerate() {
+		params.push(try!(decl_type(arg.get_type().unwrap()).map_err(|e| {
+			format!("parameter {}: {}", i+1, e)
+		})));
+	}
+	let mut func = FuncDecl::new(&name, retval, params, FuncKind::Operation);
+	func.variadic = entity.is_variadic();
+	Ok(func)
+}
+
+// The fixed-width typedefs, which are nicer to generate than whatever they
+// happen to be on the host.
+fn fixed_typedef(name: &str) -> Option<Native> {
+	match name {
+		"int8_t" => Some(Native::I8), "uint8_t" => Some(Native::U8),
+		"int16_t" => Some(Native::I16), "uint16_t" => Some(Native::U16),
+		"int32_t" => Some(Native::I32), "uint32_t" => Some(Native::U32),
+		"int64_t" => Some(Native::I64), "uint64_t" => Some(Native::U64),
+		"size_t" => Some(Native::Usize),
+		_ => None,
+	}
+}
+
+// Converts a clang type to ours, as the grammar would give it for the same
+// type written in the DSL.  Only the const of types that the DSL can qualify
+// is kept, so a "const struct s" field is just a "struct s".
+fn decl_type(ty: ::clang::Type) -> Result<DeclType, String> {
+	match try!(unqualified_decl_type(ty)) {
+		DeclType::Basic(t) => Ok(DeclType::Basic(qualified(ty, t))),
+		decl => Ok(decl),
+	}
+}
+
+// 't' with the const of the clang type 'ty', if it has one.
+fn qualified(ty: ::clang::Type, t: Type) -> Type {
+	if !ty.is_const_qualified() {
+		return t;
+	}
+	match t {
+		Type::Qualified(inner, mut quals) => {
+			quals.insert(0, Qualifier::Const);
+			Type::Qualified(inner, quals)
+		},
+		t => Type::Qualified(Box::new(t), vec![Qualifier::Const]),
+	}
+}
+
+// As decl_type(), but ignoring whether 'ty' itself is const.
+fn unqualified_decl_type(ty: ::clang::Type) -> Result<DeclType, String> {
+	let native = |n: Native| Ok(DeclType::Basic(Type::Builtin(n)));
+	let named = |ty: ::clang::Type| match ty.get_declaration()
+		.and_then(|e| e.get_name()) {
+		None => Err(format!("'{}' has no name", ty.get_display_name())),
+		Some(nm) => Ok(nm),
+	};
+	match ty.get_kind() {
+		TypeKind::Typedef => match fixed_typedef(&ty.get_display_name()) {
+			Some(n) => native(n),
+			None => unqualified_decl_type(ty.get_canonical_type()),
+		},
+		TypeKind::Elaborated =>
+			unqualified_decl_type(ty.get_elaborated_type().unwrap()),
+		TypeKind::Void => native(Native::Void),
+		TypeKind::Bool => native(Native::Boolean),
+		TypeKind::CharS | TypeKind::CharU => native(Native::Character),
+		TypeKind::SChar => native(Native::I8),
+		TypeKind::UChar => native(Native::U8),
+		TypeKind::WChar => native(Native::WideCharacter),
+		TypeKind::Short => native(Native::Short),
+		TypeKind::UShort => native(Native::UShort),
+		TypeKind::Int => native(Native::Integer),
+		TypeKind::UInt => native(Native::Unsigned),
+		TypeKind::Long => native(Native::Long),
+		TypeKind::ULong => native(Native::ULong),
+		TypeKind::LongLong => native(Native::LongLong),
+		TypeKind::ULongLong => native(Native::ULongLong),
+		TypeKind::Int128 => native(Native::I128),
+		TypeKind::UInt128 => native(Native::U128),
+		TypeKind::Float => native(Native::F32),
+		TypeKind::Double => native(Native::F64),
+		TypeKind::Record => match ty.get_declaration().unwrap().get_kind() {
+			EntityKind::StructDecl => named(ty).map(DeclType::StructRef),
+			_ => Err(format!("'{}' is not a struct", ty.get_display_name())),
+		},
+		TypeKind::Enum => named(ty).map(DeclType::EnumRef),
+		TypeKind::Pointer => {
+			let pointee = ty.get_pointee_type().unwrap();
+			let sty = match try!(unqualified_decl_type(pointee)) {
+				DeclType::Basic(t) => t,
+				// a fake struct.
+				DeclType::StructRef(nm) => Type::Struct(nm, vec![], false),
+				DeclType::EnumRef(nm) => Type::Enum(nm, vec![]), // fake enum.
+				_ => unreachable!(),
+			};
+			let sty = qualified(pointee, sty);
+			Ok(DeclType::Basic(Type::Pointer(Box::new(sty))))
+		},
+		TypeKind::ConstantArray => {
+			match try!(decl_type(ty.get_element_type().unwrap())) {
+				DeclType::Basic(t) => {
+					let n = ty.get_size().unwrap();
+					Ok(DeclType::Basic(Type::Array(Box::new(t), n)))
+				},
+				_ => Err(format!("'{}' is an array of a struct or enum",
+				                 ty.get_display_name())),
+			}
+		},
+		_ => Err(format!("type '{}' is not supported", ty.get_display_name())),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std;
+	use tempdir;
+	use fuzz;
+	use header::import_header;
+
+	// libclang is loaded at runtime, and not every machine has it, so this
+	// only runs when asked for, with "cargo test -- --ignored".
+	#[test]
+	#[ignore]
+	fn import_fixture_header() {
+		use std::io::Write;
+		let dir = tempdir::TempDir::new("apifuzz").unwrap();
+		let path = dir.path().join("import.h");
+		{
+			let mut f = std::fs::File::create(&path).unwrap();
+			f.write_all(b"#include <stddef.h>\n\
+				#include <stdint.h>\n\
+				struct handle;\n\
+				enum mode { READ = 1, WRITE, APPEND = 8 };\n\
+				struct entry { const char* key; uint32_t len : 4; int m[2][3]; };\n\
+				struct entry* lookup(struct handle* h, size_t n, enum mode m);\n\
+				int log_msg(const char* fmt, ...);\n\
+				union u { int i; char c; };\n\
+				void visit(void (*cb)(int));\n").unwrap();
+		}
+		let names = vec!["lookup", "entry", "u", "handle", "mode", "visit",
+		                 "log_msg"];
+		let (decls, warnings) = import_header(&path, &names).unwrap();
+		let expected = fuzz::parse_LDeclarations(
+			"struct entry { pointer const char key; u32 len : 4; int m[2][3]; }\n\
+			 struct handle {}\n\
+			 enum mode { READ = 1, WRITE = 2, APPEND = 8, }\n\
+			 function:decl lookup pointer struct entry {\n\
+			   pointer struct handle, usize, enum mode,\n\
+			 }\n\
+			 function:decl log_msg int { pointer const char, ... }\n").unwrap();
+		assert_eq!(decls, expected);
+		// the union and the function that takes a function pointer.
+		assert_eq!(warnings.len(), 2, "{:?}", warnings);
+		assert!(warnings[0].starts_with("skipping 'u': "), "{}", warnings[0]);
+		assert!(warnings[1].starts_with("skipping 'visit': "),
+		        "{}", warnings[1]);
+
+		let err = import_header(&path, &vec!["missing"]).unwrap_err();
+		assert!(err.starts_with("'missing' is not declared in"), err);
+	}
+}
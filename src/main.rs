@@ -1,5 +1,8 @@
+extern crate lalrpop_util;
 extern crate rand;
 extern crate tempdir;
+#[cfg(feature = "header-import")]
+extern crate clang;
 use std::fs::File;
 use std::path::Path;
 use std::process::Command;
@@ -10,6 +13,8 @@ mod expr;
 mod function;
 mod fuzz;
 mod generator;
+#[cfg(feature = "header-import")]
+mod header;
 mod lexer;
 mod opcode;
 mod stmt;
@@ -99,7 +104,7 @@ fn compile(src: &str, dest: &str, flags: &Vec<&str>) -> Result<(), String> {
 	}
 }
 
-fn compile_and_test_program(program: &api::Program) -> Result<(),String> {
+fn compile_and_test_program(program: &mut api::Program) -> Result<(),String> {
 	use std::fmt;
 
 	let tmpdir = match TempDir::new("hotfuzz") {
@@ -116,18 +121,26 @@ fn compile_and_test_program(program: &api::Program) -> Result<(),String> {
 	};
 
 	let hdrs: Vec<&str> = vec!["stdlib.h", "search.h"];
-	match program.prologue(&mut newtest, &hdrs) {
+	let mut code: Vec<u8> = Vec::new();
+	match program.prologue(&mut code, &hdrs) {
 		Err(x) => return Err(fmt::format(format_args!("prologue: {}", x))),
 		_ => (),
 	};
-	match program.codegen(&mut newtest) {
+	match program.codegen(&mut code) {
 		Err(x) => return Err(fmt::format(format_args!("codegen: {}", x))),
 		_ => (),
 	}
-	match program.epilogue(&mut newtest) {
+	match program.epilogue(&mut code) {
 		Err(x) => return Err(fmt::format(format_args!("epilogue: {}", x))),
 		_ => (),
 	}
+	let code = util::indent_c(&String::from_utf8_lossy(&code)).into_bytes();
+	program.record(&code);
+	use std::io::Write;
+	match newtest.write_all(&code) {
+		Err(x) => return Err(fmt::format(format_args!("writing: {}", x))),
+		_ => (),
+	}
 	drop(newtest);
 
 	let outname = tmpdir.path().join(".fuzziter");
@@ -170,7 +183,7 @@ fn builtin_generators() -> Vec<Box<variable::Generator>> {
 	let cstype = Type::Pointer(Box::new(Type::Builtin(Native::Character)));
 	let cs = variable::GenCString::create(&cstype);
 	use variable::Generator;
-	let nncs = variable::GenIgnore::new(cs.clone(), 0, "std:cstring:nonnull");
+	let nncs = variable::GenIgnore::new(cs.clone_box(), 0, "std:cstring:nonnull");
 	rv.push(Box::new(nncs));
 
 	return rv;
@@ -180,21 +193,22 @@ fn builtin_generators() -> Vec<Box<variable::Generator>> {
 fn hash_generators() -> Vec<Box<variable::Generator>> {
 	let mut rv: Vec<Box<variable::Generator>> = Vec::new();
 
-	let hs_data = Type::Struct("struct hsearch_data".to_string(), vec![]);
+	let hs_data = Type::Struct("struct hsearch_data".to_string(), vec![], false);
 	let hs_data_ptr: Type = Type::Pointer(Box::new(hs_data.clone()));
 	rv.push(Box::new(variable::GenOpaque::create(&hs_data_ptr)));
 
 	let char_ptr = Type::Pointer(Box::new(Type::Builtin(Native::Character)));
 	let void_ptr = Type::Pointer(Box::new(Type::Builtin(Native::Void)));
 	let entry = Type::Struct("ENTRY".to_string(),
-		vec![("key".to_string(), Box::new(char_ptr)),
-		     ("data".to_string(), Box::new(void_ptr))]
+		vec![("key".to_string(), Box::new(char_ptr), None),
+		     ("data".to_string(), Box::new(void_ptr), None)],
+		false
 	);
 	rv.push(variable::generator(&entry));
 	rv.push(variable::generator(&Type::Pointer(Box::new(entry))));
 
-	let action_values = vec![("FIND".to_string(), 0),
-	                         ("ENTER".to_string(), 1)];
+	let action_values = vec![("FIND".to_string(), 0, "0".to_string()),
+	                         ("ENTER".to_string(), 1, "1".to_string())];
 	let action = Type::Enum("ACTION".to_string(), action_values);
 	rv.push(variable::generator(&action));
 	return rv;
@@ -218,7 +232,40 @@ fn parse_generators(fname: &str) -> Vec<Box<variable::Generator>> {
 	tobox(stdgen)
 }
 
+// Prints the DSL for the named declarations of a C header, so that they can
+// be pasted into a program, for "--import-header HEADER NAME...".  Warnings
+// about what was skipped go to stderr.
+#[cfg(feature = "header-import")]
+fn import_header(args: &[String]) -> Result<(), String> {
+	use std::io::Write;
+	if args.len() < 2 {
+		return Err("usage: --import-header HEADER NAME...".to_string());
+	}
+	let names: Vec<&str> = args[1..].iter().map(|a| a.as_str()).collect();
+	let (decls, warnings) = try!(header::import_header(Path::new(&args[0]),
+	                                                   &names));
+	for w in warnings.iter() {
+		writeln!(&mut std::io::stderr(), "WARNING: {}", w).unwrap();
+	}
+	for decl in decls.iter() {
+		println!("{}", decl.to_dsl());
+	}
+	Ok(())
+}
+
 fn main() {
+	#[cfg(feature = "header-import")]
+	{
+		let args: Vec<String> = std::env::args().collect();
+		if args.len() > 1 && args[1] == "--import-header" {
+			if let Err(e) = import_header(&args[2..]) {
+				use std::io::Write;
+				writeln!(&mut std::io::stderr(), "{}", e).unwrap();
+				std::process::exit(1);
+			}
+			return;
+		}
+	}
 	// todo: search path for hf files.
 	let mut generators = parse_generators("../share/stdgen.hf");
 	generators.append(&mut builtin_generators());
@@ -238,13 +285,14 @@ fn main() {
 			"usize, pointer struct hsearch_data,\n" +
 		"}\n" +
 		"function:decl hsearch_r int {\n" +
-			"int, int, pointer pointer int, pointer struct hsearch_data,\n" +
+			"struct entry, enum ACTION, pointer pointer struct entry,\n" +
+			"pointer struct hsearch_data,\n" +
 		"}\n" +
 		"constraint:new item.key != 0\n" +
 		"constraint:new function:call hcreate_r { nel op:& tbl } != 0\n" +
 		"function:call hsearch_r { item actvar op:& retval op:& tbl }\n";
 	let mut lprogram = match fuzz::parse_LProgram(s.as_str()) {
-		Err(e) => panic!("{:?}", e),
+		Err(e) => panic!("{}", util::parse_error(s.as_str(), e)),
 		Ok(x) => x,
 	};
 	assert!(lprogram.declarations.len() > 1);
@@ -253,11 +301,33 @@ fn main() {
 		Err(e) => panic!(e),
 		_ => (),
 	};
+	for w in lprogram.warnings().iter() {
+		println!("WARNING: {}", w);
+	}
 	assert_eq!(lprogram.statements.len(), 8);
 
+	// Given a case number, just print the program for that case.
+	match std::env::args().nth(1) {
+		None => (),
+		Some(arg) => {
+			let case = match arg.parse::<usize>() {
+				Err(e) => panic!("invalid case number '{}': {}", arg, e),
+				Ok(c) => c,
+			};
+			let hdrs: Vec<&str> = vec!["stdlib.h", "search.h"];
+			lprogram.codegen_case(case, &hdrs, &mut std::io::stdout()).unwrap();
+			return;
+		},
+	};
+
 	while !lprogram.done() {
-		match compile_and_test_program(&lprogram) {
+		match compile_and_test_program(&mut lprogram) {
 			Err(e) => {
+				// shrink the failing values for a cleaner reproduction.
+				lprogram.reduce(|pgm, case| {
+					pgm.seek(case);
+					compile_and_test_program(pgm).is_err()
+				});
 				let mut failed: Vec<u8> = Vec::new();
 				lprogram.codegen(&mut failed).unwrap();
 				let prog = String::from_utf8(failed).unwrap();
@@ -269,10 +339,13 @@ fn main() {
 	}
 	// We next()ed, but then our iteration finished() before we actually
 	// compile_and_test()ed the final state.  Test that last state.
-	match compile_and_test_program(&lprogram) {
+	match compile_and_test_program(&mut lprogram) {
 		Err(e) => panic!("compile/test error: {}", e),
 		Ok(_) => {},
 	};
+	let stats = lprogram.stats();
+	println!("{} programs ({} unique) for {} states; complete: {}",
+	         stats.emitted, stats.unique, stats.total, stats.completed);
 }
 
 #[cfg(test)]
@@ -320,7 +393,8 @@ mod test {
 				"usize, pointer struct hsearch_data,\n" +
 			"}\n" +
 			"function:decl hsearch_r int {\n" +
-				"int, int, pointer pointer int, pointer struct hsearch_data,\n" +
+				"struct entry, enum ACTION, pointer pointer struct entry,\n" +
+				"pointer struct hsearch_data,\n" +
 			"}\n" +
 			"function:call hcreate_r { nel op:& tbl }\n" +
 			"function:call hsearch_r { item actvar op:& retval op:& tbl }\n";
@@ -393,7 +467,7 @@ mod test {
 			_ => (),
 		};
 		assert_eq!(lprogram.statements.len(), 7);
-		assert_eq!(lprogram.n_states(), 256);
+		assert_eq!(lprogram.n_states(), 5488);
 	}
 
 	#[test]
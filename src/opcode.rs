@@ -7,6 +7,10 @@ pub enum UOp {
 	Deref, // dereference it once
 	Negate, // unary negation, i.e. the "-" in "-1.f"
 	Not, // relational not, the "!" in "!foo"
+	SizeOf, // the size of the object, e.g. for buffer length arguments
+	LengthOf, // the number of elements in a generated buffer
+	Index(usize), // one element of a buffer, as in "x[2]"
+	AddressOfIndex(usize), // the address of one element, as in "&x[2]"
 	None,
 }
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -23,21 +27,29 @@ impl UOp {
 		match *self {
 			UOp::AddressOf => unreachable!(), // retval not a Type, no Pointers avail
 			UOp::Deref => unreachable!(), // ditto 'retval not a type'.
+			UOp::Index(_) | UOp::AddressOfIndex(_) => unreachable!(), // ditto.
 			UOp::Negate => match affects {
 				Native::Boolean => Native::Boolean,
-				Native::U8 | Native::U16 | Native::U32 | Native::U64 |
-					Native::Unsigned | Native::Usize | Native::Character =>
+				Native::U8 | Native::U16 | Native::U32 | Native::U64 | Native::U128 |
+					Native::Unsigned | Native::Usize | Native::Character |
+					Native::WideCharacter | Native::UShort | Native::ULong |
+					Native::ULongLong =>
 						panic!("negating unary type!"),
 				Native::I8 => Native::I8,
 				Native::I16 => Native::I16,
 				Native::I32 => Native::I32,
 				Native::I64 => Native::I64,
+				Native::I128 => Native::I128,
 				Native::Integer => Native::Integer,
+				Native::Short => Native::Short,
+				Native::Long => Native::Long,
+				Native::LongLong => Native::LongLong,
 				Native::F32 => Native::F32,
 				Native::F64 => Native::F64,
 				Native::Void => panic!("negating void type!"),
 			},
 			UOp::Not => Native::Boolean,
+			UOp::SizeOf | UOp::LengthOf => Native::Usize,
 			UOp::None => affects,
 		}
 	}
@@ -47,14 +59,57 @@ impl UOp {
 		match *self {
 			UOp::AddressOf =>	Type::Pointer(Box::new(affects)),
 			UOp::Deref => affects.dereference(),
+			UOp::Index(_) => affects.dereference(),
+			UOp::AddressOfIndex(_) => Type::Pointer(Box::new(affects.dereference())),
 			UOp::Negate => match affects {
 				Type::Builtin(nat) => Type::Builtin(self.nat_result_type(nat)),
 				_ => unimplemented!(),
 			},
 			UOp::Not => Type::Builtin(Native::Boolean),
+			UOp::SizeOf | UOp::LengthOf => Type::Builtin(Native::Usize),
 			UOp::None => affects,
 		}
 	}
+
+	// The single op that has the effect of applying this one to the result of
+	// 'inner', as "&" on "x[2]" is "&x[2]".  Most pairs have none.
+	pub fn apply(&self, inner: UOp) -> Option<UOp> {
+		match (*self, inner) {
+			(op, UOp::None) | (UOp::None, op) => Some(op),
+			(UOp::AddressOf, UOp::Deref) | (UOp::Deref, UOp::AddressOf) =>
+				Some(UOp::None),
+			(UOp::AddressOf, UOp::Index(i)) => Some(UOp::AddressOfIndex(i)),
+			(UOp::Deref, UOp::AddressOfIndex(i)) => Some(UOp::Index(i)),
+			_ => None,
+		}
+	}
+
+	// Combines ops where they can be.  They are given outermost first, as they
+	// read in C, and are applied from the innermost one out; so are the ops
+	// that are left, as "**pp" is [Deref, Deref].  Nothing can be applied to a
+	// size or length, and only an lvalue has an address.
+	pub fn apply_all(ops: &[UOp]) -> Result<Vec<UOp>, String> {
+		let mut rv: Vec<UOp> = Vec::new(); // innermost first, until the end.
+		for op in ops.iter().rev() {
+			let inner = rv.pop().unwrap_or(UOp::None);
+			match op.apply(inner) {
+				Some(UOp::None) => (),
+				Some(o) => rv.push(o),
+				None => match (*op, inner) {
+					(UOp::AddressOf, _) | (UOp::LengthOf, _) | (_, UOp::SizeOf) |
+					(_, UOp::LengthOf) =>
+						return Err(format!("{:?} cannot be applied to the result of \
+						                    {:?}", op, inner)),
+					_ => {
+						rv.push(inner);
+						rv.push(*op);
+					},
+				},
+			};
+		}
+		rv.reverse();
+		Ok(rv)
+	}
 }
 
 impl BinOp {
@@ -98,6 +153,10 @@ impl fmt::Display for UOp {
 			UOp::Deref => write!(f, "*"),
 			UOp::Negate => write!(f, "-"),
 			UOp::Not => write!(f, "!"),
+			UOp::SizeOf => write!(f, "sizeof"),
+			UOp::LengthOf => write!(f, "lengthof"),
+			UOp::Index(i) => write!(f, "[{}]", i),
+			UOp::AddressOfIndex(i) => write!(f, "&[{}]", i),
 			UOp::None => write!(f, ""),
 		}
 	}
@@ -140,6 +199,37 @@ mod test {
 		assert_eq!(UOp::Not.nat_result_type(Native::Boolean), Native::Boolean);
 		assert_eq!(UOp::Not.result_type(Type::Builtin(Native::Boolean)),
 		           Type::Builtin(Native::Boolean));
+		assert_eq!(UOp::SizeOf.result_type(Type::Builtin(Native::U8)),
+		           Type::Builtin(Native::Usize));
+		let i32ptr = Type::Pointer(Box::new(Type::Builtin(Native::I32)));
+		assert_eq!(UOp::Index(2).result_type(i32ptr.clone()),
+		           Type::Builtin(Native::I32));
+		assert_eq!(UOp::AddressOfIndex(2).result_type(i32ptr.clone()), i32ptr);
+	}
+
+	#[test]
+	fn uop_composition() {
+		assert_eq!(UOp::apply_all(&[UOp::AddressOf, UOp::Index(2)]),
+		           Ok(vec![UOp::AddressOfIndex(2)]));
+		assert_eq!(UOp::apply_all(&[UOp::Deref, UOp::AddressOf]), Ok(vec![]));
+		assert_eq!(UOp::apply_all(&[UOp::Deref, UOp::AddressOf, UOp::Deref]),
+		           Ok(vec![UOp::Deref]));
+		assert_eq!(UOp::apply_all(&[UOp::Deref, UOp::AddressOf, UOp::Index(1)]),
+		           Ok(vec![UOp::Index(1)]));
+		assert_eq!(UOp::apply_all(&[UOp::SizeOf]), Ok(vec![UOp::SizeOf]));
+		assert_eq!(UOp::apply_all(&[UOp::None]), Ok(vec![]));
+		assert_eq!(UOp::apply_all(&[]), Ok(vec![]));
+		// what does not combine is kept.
+		assert_eq!(UOp::apply_all(&[UOp::Deref, UOp::Deref]),
+		           Ok(vec![UOp::Deref, UOp::Deref]));
+		assert_eq!(UOp::apply_all(&[UOp::SizeOf, UOp::Deref, UOp::Index(1)]),
+		           Ok(vec![UOp::SizeOf, UOp::Deref, UOp::Index(1)]));
+		assert_eq!(UOp::apply_all(&[UOp::AddressOf, UOp::Deref, UOp::Deref]),
+		           Ok(vec![UOp::Deref]));
+		let err = UOp::apply_all(&[UOp::AddressOf, UOp::AddressOf]).unwrap_err();
+		assert_eq!(err, "AddressOf cannot be applied to the result of AddressOf");
+		let err = UOp::apply_all(&[UOp::Deref, UOp::LengthOf]).unwrap_err();
+		assert_eq!(err, "Deref cannot be applied to the result of LengthOf");
 	}
 
 	macro_rules! result_nat_test {
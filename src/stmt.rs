@@ -10,6 +10,30 @@ pub trait Code {
 		-> Result<(),std::io::Error>;
 }
 
+// Where the code of a program goes, a piece at a time.  Any byte stream is a
+// sink that writes one line per declaration or statement; other sinks can
+// keep the pieces apart, e.g. to split them across files.
+pub trait CodeSink {
+	// the declaration of a variable, including its initial value.
+	fn declaration(&mut self, code: &str) -> Result<(),Error>;
+	// a statement, which may span several lines, as an "if" does.
+	fn statement(&mut self, code: &str) -> Result<(),Error>;
+	// anything else, such as the lines around the statements.
+	fn text(&mut self, code: &str) -> Result<(),Error>;
+}
+
+impl<W: std::io::Write + ?Sized> CodeSink for W {
+	fn declaration(&mut self, code: &str) -> Result<(),Error> {
+		write!(self, "\t{}\n", code)
+	}
+	fn statement(&mut self, code: &str) -> Result<(),Error> {
+		write!(self, "\t{}\n", code)
+	}
+	fn text(&mut self, code: &str) -> Result<(),Error> {
+		self.write_all(code.as_bytes())
+	}
+}
+
 #[derive(Clone, Debug)]
 pub enum Statement {
 	VariableDeclaration(String /* name */, Type),
@@ -19,7 +43,10 @@ pub enum Statement {
 	Constraint(Expression),
 	If(Expression, Box<Vec<Statement>> /* stmts if true. */),
 	While(Expression, Box<Vec<Statement>> /* stmts if true. */),
-	/* todo: 'loop' etc. */
+	Repeat(u64 /* count */, String /* loop variable */, Box<Vec<Statement>>),
+	// C conditions, using the generated names, that must hold to run the
+	// statement.
+	Guarded(Vec<String>, Box<Statement>),
 }
 
 impl Code for Statement {
@@ -27,19 +54,33 @@ impl Code for Statement {
 		-> Result<(),Error> {
 		match self {
 			&Statement::VariableDeclaration(ref nm, _) => {
-				let sym = pgm.symlookup(nm).unwrap();
-				assert_eq!(sym.name, *nm);
-				write!(strm, "{};", sym.generator.decl(nm))
+				let sym = pgm.cnamelookup(nm).unwrap();
+				assert_eq!(sym.codegen_name(), *nm);
+				pgm.note_declaration(sym);
+				let decl = sym.generator.decl(nm);
+				if pgm.is_temporary(nm) {
+					return Ok(());
+				}
+				write!(strm, "{};", decl)
 			},
 			&Statement::Expr(ref expr) => {
 				try!(expr.codegen(strm, pgm));
 				write!(strm, ";")
 			},
 			&Statement::Assignment(ref lhs, ref rhs) => {
+				// A void function has no result to assign; just make the call.
+				if rhs.extype() == Type::Builtin(Native::Void) {
+					try!(rhs.codegen(strm, pgm));
+					return write!(strm, ";");
+				}
 				try!(lhs.codegen(strm, pgm));
 				try!(write!(strm, " = "));
 				try!(rhs.codegen(strm, pgm));
-				write!(strm, ";")
+				try!(write!(strm, ";"));
+				match *rhs {
+					Expression::FqnCall(ref fqn, _) => pgm.log_call(strm, lhs, fqn),
+					_ => Ok(()),
+				}
 			},
 			&Statement::Verify(ref expr) => {
 				try!(write!(strm, "assert("));
@@ -80,6 +121,26 @@ impl Code for Statement {
 				}
 				writeln!(strm, "}}")
 			},
+			&Statement::Repeat(count, ref var, ref stlist) => {
+				use std::ops::Deref;
+				try!(writeln!(strm, "for(size_t {0} = 0; {0} < {1}; ++{0}) {{", var,
+				              count));
+				for stmt in stlist.deref() {
+					try!(write!(strm, "\t"));
+					try!(stmt.codegen(strm, pgm));
+					try!(write!(strm, "\n"));
+				}
+				writeln!(strm, "}}")
+			},
+			&Statement::Guarded(ref conds, ref stmt) => {
+				// like a constraint: the inputs are no good, but that is not a bug.
+				for cond in conds.iter() {
+					try!(writeln!(strm, "if(!({})) {{", cond));
+					try!(writeln!(strm, "\treturn 0;"));
+					try!(writeln!(strm, "}}"));
+				}
+				stmt.codegen(strm, pgm)
+			},
 		}
 	}
 }
@@ -106,7 +167,8 @@ mod test {
 		($vname:expr, $vtype:expr) => ({
 			let dt = api::DeclType::Basic($vtype);
 			let fvd = api::FreeVarDecl{name: $vname.to_string(),
-			                           genname: "".to_string(), ty: dt};
+			                           genname: "".to_string(), ty: dt,
+			                           shared: false};
 			api::Stmt::Declaration(api::Declaration::Free(fvd))
 		})
 	}
@@ -176,6 +238,20 @@ mod test {
 		cg_expect!(fexpr, "h(Va, Vb)", pgm);
 	}
 
+	#[test]
+	fn void_assignment() {
+		let mut pgm = api::Program::new(&vec![], &vec![
+			vardecl!("a", Type::Builtin(Native::I32)),
+		]);
+		pgm.analyze().unwrap();
+		let null = UOp::None;
+		let lhs = Expression::Basic(null, pgm.symlookup("a").unwrap().clone());
+		let fqn = Function::new("reset", &Type::Builtin(Native::Void), &vec![]);
+		let rhs = Expression::FqnCall(fqn, vec![]);
+		let asgn = Statement::Assignment(lhs, rhs);
+		cg_expect!(asgn, "reset();", pgm);
+	}
+
 	#[test]
 	fn expr_statement() {
 		let mut pgm = api::Program::new(&vec![], &vec![
@@ -249,11 +325,10 @@ mod test {
 		let char_ptr = Type::Pointer(Box::new(Type::Builtin(Native::Character)));
 		let void_ptr = Type::Pointer(Box::new(Type::Builtin(Native::Void)));
 		let entry = Type::Struct("ENTRY".to_string(), vec![
-			("key".to_string(), Box::new(char_ptr.clone())),
-			("data".to_string(), Box::new(void_ptr.clone())),
-		]);
-		let foo = api::Symbol{name: "foo".to_string(),
-		                      generator: variable::generator(&entry), typ: entry};
+			("key".to_string(), Box::new(char_ptr.clone()), None),
+			("data".to_string(), Box::new(void_ptr.clone()), None),
+		], false);
+		let foo = api::Symbol::new("foo", variable::generator(&entry), entry);
 		let keyexpr = Expression::Field(foo.clone(), "key".to_string());
 		let dataexpr = Expression::Field(foo, "data".to_string());
 		assert_eq!(keyexpr.extype(), char_ptr);
@@ -288,4 +363,19 @@ mod test {
 		let ifst = Statement::While(simple, Box::new(vec![]));
 		cg_expect!(ifst, "while(foo) {\n}\n", pgm);
 	}
+
+	#[test]
+	fn repeat_statement() {
+		let mut pgm = api::Program::new(&vec![], &vec![
+			vardecl!("foo", Type::Builtin(Native::I32)),
+		]);
+		pgm.analyze().unwrap();
+		let varfoo = pgm.symlookup("foo").unwrap();
+		let null = UOp::None;
+		let simple = Expression::Basic(null, varfoo.clone());
+		let body = vec![Statement::Verify(simple)];
+		let rep = Statement::Repeat(10, "j".to_string(), Box::new(body));
+		cg_expect!(rep, "for(size_t j = 0; j < 10; ++j) {\n\tassert(foo);\n}\n",
+		           pgm);
+	}
 }
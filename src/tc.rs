@@ -25,8 +25,12 @@
 // utilize randomness.  Thus subsequent runs with the exact same inputs may
 // find different sets of bugs.
 extern crate rand;
+use std::cell::{Cell, RefCell};
+use std::fmt::Write;
+use std::sync::Arc;
 use rand::distributions::{IndependentSample, Range};
 use typ::*;
+use util;
 
 // A class of types.
 pub trait TypeClass<T> {
@@ -34,6 +38,106 @@ pub trait TypeClass<T> {
 	fn value(&self, class: usize) -> T;
 }
 
+// Spells the given value as a C literal of the given integer (or pointer)
+// type.  Unsigned values get a 'u' suffix and 64-bit values an 'll' suffix, so
+// the literal has the type we intend.  The minimum of a signed type is written
+// as an expression: "-2147483648" is the negation of 2147483648, which does not
+// fit into an int.
+pub fn literal(value: i128, ty: &Type) -> String {
+	let mut rv = String::new();
+	literal_into(&mut rv, value, ty);
+	rv
+}
+
+// Appends literal(value, ty) to 'out'.
+pub fn literal_into(out: &mut String, value: i128, ty: &Type) {
+	match *ty {
+		Type::Builtin(Native::I128) =>
+			return literal_u128_into(out, value as u128, ty),
+		Type::Builtin(Native::U128) => {
+			assert!(value >= 0, "{} does not fit in an unsigned __int128", value);
+			return literal_u128_into(out, value as u128, ty);
+		},
+		_ => (),
+	};
+	let nat = match *ty {
+		Type::Builtin(ref n) => *n,
+		Type::Pointer(_) => {
+			assert!(value >= 0 && value <= u64::max_value() as i128);
+			return write!(out, "({}){}ull", ty.without_attributes().cast_name(),
+			              value).unwrap();
+		},
+		// attributes are not allowed in a cast, so cast to the plain pointer.
+		// Pointers to qualified pointers drop just the attributes, above.
+		Type::Qualified(ref ptr, _) => return literal_into(out, value, ptr),
+		_ => panic!("no literals of type {:?}", ty),
+	};
+	let (min, max, suffix): (i128, i128, &str) =
+		match nat.fixed(DataModel::default()) {
+		Native::Boolean => (0, 1, ""),
+		Native::U8 => (0, u8::max_value() as i128, "u"),
+		Native::U16 => (0, u16::max_value() as i128, "u"),
+		Native::U32 | Native::Unsigned => (0, u32::max_value() as i128, "u"),
+		Native::U64 | Native::Usize => (0, u64::max_value() as i128, "ull"),
+		Native::I8 => (i8::min_value() as i128, i8::max_value() as i128, ""),
+		Native::I16 => (i16::min_value() as i128, i16::max_value() as i128, ""),
+		Native::I32 | Native::Integer =>
+			(i32::min_value() as i128, i32::max_value() as i128, ""),
+		Native::I64 => (i64::min_value() as i128, i64::max_value() as i128, "ll"),
+		_ => panic!("no integer literals of type {:?}", nat),
+	};
+	// C's own types have their own suffixes, whatever their width.
+	let suffix = match nat {
+		Native::Long => "l", Native::ULong => "ul",
+		Native::LongLong => "ll", Native::ULongLong => "ull",
+		_ => suffix,
+	};
+	assert!(min <= value && value <= max,
+	        "{} does not fit in a {}", value, nat.name());
+	// int8_t and int16_t promote to int, so only the larger minimums need care.
+	if value == min && min < i16::min_value() as i128 {
+		return write!(out, "({}{}-1)", value+1, suffix).unwrap();
+	}
+	write!(out, "{}{}", value, suffix).unwrap()
+}
+
+// Spells the given bits as a C literal of a 128-bit type.  C has no 128-bit
+// literals, so values that do not fit into 64 bits are assembled from their
+// two halves.  For __int128, 'bits' is the two's complement representation.
+pub fn literal_u128(bits: u128, ty: &Type) -> String {
+	let mut rv = String::new();
+	literal_u128_into(&mut rv, bits, ty);
+	rv
+}
+
+// Appends literal_u128(bits, ty) to 'out'.
+pub fn literal_u128_into(out: &mut String, bits: u128, ty: &Type) {
+	let signed = match *ty {
+		Type::Builtin(Native::I128) => true,
+		Type::Builtin(Native::U128) => false,
+		_ => panic!("{:?} is not a 128-bit type", ty),
+	};
+	let value = bits as i128;
+	if signed && value >= i64::min_value() as i128 &&
+	   value <= i64::max_value() as i128 {
+		out.push_str("((__int128)");
+		literal_into(out, value, &Type::Builtin(Native::I64));
+		out.push(')');
+		return;
+	}
+	if !signed && bits <= u64::max_value() as u128 {
+		return write!(out, "((unsigned __int128){}ull)", bits).unwrap();
+	}
+	if signed {
+		out.push_str("((__int128)");
+	}
+	write!(out, "(((unsigned __int128){}ull << 64) | {}ull)", (bits >> 64) as u64,
+	       bits as u64).unwrap();
+	if signed {
+		out.push(')');
+	}
+}
+
 // Specialization is not yet stable in rust.  Thus the types are not type
 // parameters but baked into the type name.  Sigh.
 
@@ -52,13 +156,33 @@ pub struct TC_Usize {}
 pub struct TC_I32 {}
 #[allow(non_camel_case_types)]
 #[derive(Clone, Debug)]
+pub struct TC_I128 {}
+#[allow(non_camel_case_types)]
+#[derive(Clone, Debug)]
+pub struct TC_U128 {}
+#[allow(non_camel_case_types)]
+#[derive(Clone, Debug)]
 pub struct TC_Pointer {}
 #[allow(non_camel_case_types)]
 #[derive(Clone, Debug)]
 pub struct TC_Enum {
-	values: Vec<u32>
+	shared: Arc<EnumValues>,
+}
+// The values of an enum never change, so every TC_Enum of the same type shares
+// them instead of copying them out of the type again.
+#[derive(Debug)]
+struct EnumValues {
+	values: Vec<u32>,
+	spellings: Vec<String>,
+	names: Vec<String>,
 }
 
+// The values of every enum type that a TC_Enum was made for on this thread,
+// and the number of times they had to be built; see TC_Enum::new().
+thread_local!(static ENUM_VALUES: RefCell<Vec<(Type, Arc<EnumValues>)>> =
+              RefCell::new(Vec::new()));
+thread_local!(static ENUM_VALUES_BUILT: Cell<usize> = Cell::new(0));
+
 #[allow(non_camel_case_types)]
 #[derive(Clone)]
 pub struct TC_Char_Printable {
@@ -77,7 +201,7 @@ impl TypeClass<u8> for TC_U8 {
 	fn value(&self, class: usize) -> u8 {
 		// UGH.  Getting a static Range<x> is a nightmare.  For now we'll just
 		// reallocate every damn call.
-		let mut rng: rand::ThreadRng = rand::thread_rng();
+		let mut rng: util::FuzzRng = util::rng();
 		let du8_1_128 = Range::new(1, 128);
 		let du8_129_254 = Range::new(129, 254);
 		match class {
@@ -93,7 +217,7 @@ impl TypeClass<u8> for TC_U8 {
 impl TypeClass<u16> for TC_U16 {
 	fn n(&self) -> usize { return 4; }
 	fn value(&self, class: usize) -> u16 {
-		let mut rng: rand::ThreadRng = rand::thread_rng();
+		let mut rng: util::FuzzRng = util::rng();
 		let du16_1_32767 = Range::new(1, 128);
 		let du16_32768_65534 = Range::new(129, 254);
 		match class {
@@ -108,12 +232,17 @@ impl TypeClass<u16> for TC_U16 {
 
 impl TC_Usize {
 	pub fn new() -> Self { TC_Usize{} }
+
+	// zero and the maximum.
+	pub fn boundaries(&self) -> Vec<usize> {
+		vec![0, 3]
+	}
 }
 impl TypeClass<usize> for TC_Usize {
 	fn n(&self) -> usize { return 4; }
 	fn value(&self, class: usize) -> usize {
 		//let du_pos_small = Range::new(1, i32::max_value()/2);
-		let mut rng: rand::ThreadRng = rand::thread_rng();
+		let mut rng: util::FuzzRng = util::rng();
 		let du_small = Range::new(1, usize::max_value()/2);
 		let du_large = Range::new(usize::max_value()/2+1, usize::max_value()-1);
 		match class {
@@ -130,11 +259,17 @@ impl TC_I32 {
 	pub fn new() -> Self {
 		TC_I32 {}
 	}
+
+	// The classes that always have the same value, as opposed to a random
+	// value from a range: the minimum, zero and the maximum.
+	pub fn boundaries(&self) -> Vec<usize> {
+		vec![0, 3, 6]
+	}
 }
 impl TypeClass<i32> for TC_I32 {
 	fn n(&self) -> usize { return 7; }
 	fn value(&self, class: usize) -> i32 {
-		let mut rng: rand::ThreadRng = rand::thread_rng();
+		let mut rng: util::FuzzRng = util::rng();
 		let du_neg_large = Range::new(i32::min_value()+1, i32::min_value()/2);
 		let du_neg_small = Range::new(i32::min_value()/2+1, -1);
 		let du_pos_small = Range::new(1, i32::max_value()/2);
@@ -155,24 +290,106 @@ impl TypeClass<i32> for TC_I32 {
 impl TC_Enum {
 	// An enum maps strings to their actual values.  But we don't actually care
 	// about the strings, so just pull out all the values and keep those.
+	// Generators of the same type share the values.
 	pub fn new(ty: &Type) -> Self {
-		match ty {
-			&Type::Enum(_, ref values) => {
-				let val = values.iter().map(|val| val.1.clone() as u32).collect();
-				TC_Enum{values: val}
-			},
+		let values = match ty {
+			&Type::Enum(_, ref values) => values,
 			_ => panic!("gave non-enum type {:?} to Enum::new", ty),
-		}
+		};
+		ENUM_VALUES.with(|cache| {
+			let mut cache = cache.borrow_mut();
+			if let Some(&(_, ref shared)) = cache.iter().find(|e| e.0 == *ty) {
+				return TC_Enum{shared: shared.clone()};
+			}
+			let val = values.iter().map(|val| val.1.clone() as u32).collect();
+			let spell = values.iter().map(|val| val.2.clone()).collect();
+			let names = values.iter().map(|val| val.0.clone()).collect();
+			let shared = Arc::new(EnumValues{values: val, spellings: spell,
+			                                 names: names});
+			ENUM_VALUES_BUILT.with(|n| n.set(n.get()+1));
+			cache.push((ty.clone(), shared.clone()));
+			TC_Enum{shared: shared}
+		})
+	}
+
+	// How many times this thread had to build the values of an enum, rather
+	// than sharing the values that were built before.
+	#[allow(dead_code)]
+	pub fn n_built() -> usize {
+		ENUM_VALUES_BUILT.with(|n| n.get())
+	}
+}
+
+impl TC_Enum {
+	// The value of the given class as the user spelled it in the declaration.
+	pub fn spelling(&self, class: usize) -> String {
+		assert!(class < self.shared.spellings.len());
+		return self.shared.spellings[class].clone();
+	}
+
+	// The name of the enumerator of the given class.
+	pub fn name(&self, class: usize) -> String {
+		assert!(class < self.shared.names.len());
+		return self.shared.names[class].clone();
 	}
 }
 
 impl TypeClass<i32> for TC_Enum {
-	fn n(&self) -> usize { self.values.len() }
+	fn n(&self) -> usize { self.shared.values.len() }
 	// Because we already pulled out the values, we can just use the class as an
 	// index into that list.
 	fn value(&self, class: usize) -> i32 {
-		assert!(class < self.values.len());
-		return self.values[class] as i32;
+		assert!(class < self.shared.values.len());
+		return self.shared.values[class] as i32;
+	}
+}
+
+// 128-bit integers are mostly used for wide arithmetic (e.g. in crypto code),
+// so besides the extremes, zero, and one, we include the values just around
+// the boundary between the two 64-bit halves, where carries go wrong.
+impl TC_I128 {
+	pub fn new() -> Self { TC_I128{} }
+
+	// the minimum, zero and the maximum.
+	pub fn boundaries(&self) -> Vec<usize> {
+		vec![0, 2, 6]
+	}
+}
+impl TypeClass<i128> for TC_I128 {
+	fn n(&self) -> usize { return 7; }
+	fn value(&self, class: usize) -> i128 {
+		match class {
+			0 => i128::min_value(),
+			1 => -1,
+			2 => 0,
+			3 => 1,
+			4 => u64::max_value() as i128,
+			5 => u64::max_value() as i128 + 1,
+			6 => i128::max_value(),
+			_ => panic!("invalid type class {} given for i128!", class),
+		}
+	}
+}
+
+impl TC_U128 {
+	pub fn new() -> Self { TC_U128{} }
+
+	// zero and the maximum.
+	pub fn boundaries(&self) -> Vec<usize> {
+		vec![0, 4]
+	}
+}
+impl TypeClass<u128> for TC_U128 {
+	fn n(&self) -> usize { return 5; }
+	fn value(&self, class: usize) -> u128 {
+		match class {
+			0 => 0,
+			1 => 1,
+			2 => u64::max_value() as u128,
+			3 => u64::max_value() as u128 + 1,
+			4 => u128::max_value(),
+			_ => panic!("invalid type class {} given for u128!", class),
+		}
 	}
 }
 
@@ -180,17 +397,30 @@ impl TC_Pointer {
 	pub fn new() -> Self {
 		TC_Pointer{}
 	}
+
+	// NULL and the very last address.
+	pub fn boundaries(&self) -> Vec<usize> {
+		vec![0, 6]
+	}
 }
 
-// Pointers are pretty simple: null-initialized or not.
+// Pointers are null-initialized or arbitrary, or one of the addresses that
+// classically fault: unaligned, on either side of the end of the first page,
+// the start of the kernel's half of the address space on x86-64, and the very
+// last address.
 impl TypeClass<usize> for TC_Pointer {
-	fn n(&self) -> usize { 2 }
+	fn n(&self) -> usize { 7 }
 	fn value(&self, class: usize) -> usize {
-		let mut rng: rand::ThreadRng = rand::thread_rng();
+		let mut rng: util::FuzzRng = util::rng();
 		let arb = Range::new(1, usize::max_value()-1);
 		match class {
 			0 => 0,
 			1 => arb.ind_sample(&mut rng),
+			2 => 0x1,
+			3 => 0xfff,
+			4 => 0x1000,
+			5 => 0xffff_8000_0000_0000u64 as usize,
+			6 => usize::max_value(),
 			_ => panic!("invalid class {} given for TC_Pointer", class),
 		}
 	}
@@ -206,7 +436,7 @@ impl TypeClass<char> for TC_Char_Printable {
 	fn value(&self, class: usize) -> char {
 		assert!(class == 0);
 		let dchar_print = Range::new(32, 126);
-		return dchar_print.ind_sample(&mut rand::thread_rng()) as u8 as char;
+		return dchar_print.ind_sample(&mut util::rng()) as u8 as char;
 	}
 }
 impl ::std::fmt::Debug for TC_Char_Printable {
@@ -225,7 +455,7 @@ impl TypeClass<char> for TC_Char_Special {
 	fn value(&self, class: usize) -> char {
 		assert!(class == 0);
 		let dchar_special = Range::new(0, 31);
-		return dchar_special.ind_sample(&mut rand::thread_rng()) as u8 as char;
+		return dchar_special.ind_sample(&mut util::rng()) as u8 as char;
 	}
 }
 impl ::std::fmt::Debug for TC_Char_Special {
@@ -233,3 +463,93 @@ impl ::std::fmt::Debug for TC_Char_Special {
 		write!(f, "specialchar{{0 of 1}}")
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use tc::literal;
+	use typ::{Native, Type};
+
+	#[test]
+	fn pointer_fault_addresses() {
+		use tc::{TC_Pointer, TypeClass};
+		let cls = TC_Pointer::new();
+		let values: Vec<usize> = (0..cls.n()).map(|c| cls.value(c)).collect();
+		for addr in [0, 0x1, 0xfff, 0x1000, usize::max_value()].iter() {
+			assert!(values.contains(addr), "{:x} missing from {:?}", addr, values);
+		}
+		let ptr = Type::Pointer(Box::new(Type::Builtin(Native::Void)));
+		assert_eq!(literal(cls.value(6) as i128, &ptr),
+		           "(void *)18446744073709551615ull");
+	}
+
+	#[test]
+	fn literal_u64_max() {
+		let u64t = Type::Builtin(Native::U64);
+		assert_eq!(literal(u64::max_value() as i128, &u64t),
+		           "18446744073709551615ull");
+	}
+
+	#[test]
+	fn literal_i32_min() {
+		let i32t = Type::Builtin(Native::I32);
+		assert_eq!(literal(i32::min_value() as i128, &i32t), "(-2147483647-1)");
+		assert_eq!(literal(-5, &i32t), "-5");
+		let i64t = Type::Builtin(Native::I64);
+		assert_eq!(literal(i64::min_value() as i128, &i64t),
+		           "(-9223372036854775807ll-1)");
+		assert_eq!(literal(-128, &Type::Builtin(Native::I8)), "-128");
+	}
+
+	#[test]
+	fn literal_c_integer_types() {
+		assert_eq!(literal(-3, &Type::Builtin(Native::Short)), "-3");
+		assert_eq!(literal(65535, &Type::Builtin(Native::UShort)), "65535u");
+		assert_eq!(literal(1 << 40, &Type::Builtin(Native::Long)),
+		           "1099511627776l");
+		assert_eq!(literal(i64::min_value() as i128,
+		                   &Type::Builtin(Native::LongLong)),
+		           "(-9223372036854775807ll-1)");
+		assert_eq!(literal(8, &Type::Builtin(Native::ULong)), "8ul");
+		assert_eq!(literal(9, &Type::Builtin(Native::ULongLong)), "9ull");
+	}
+
+	#[test]
+	fn literal_usize() {
+		let usz = Type::Builtin(Native::Usize);
+		assert_eq!(literal(42, &usz), "42ull");
+		assert_eq!(literal(7, &Type::Builtin(Native::Unsigned)), "7u");
+		let vptr = Type::Pointer(Box::new(Type::Builtin(Native::Void)));
+		assert_eq!(literal(4096, &vptr), "(void *)4096ull");
+	}
+
+	#[test]
+	fn enum_values_shared() {
+		use std::sync::Arc;
+		use tc::{TC_Enum, TypeClass};
+		use variable::{Generator, GenEnum};
+		let values = vec![("RED".to_string(), 0, "RED".to_string()),
+		                  ("BLUE".to_string(), 4, "BLUE".to_string())];
+		let color = Type::Enum("color".to_string(), values.clone());
+		let before = TC_Enum::n_built();
+		let mut gens: Vec<GenEnum> = (0..1000).map(|_| GenEnum::create(&color))
+			.collect();
+		assert_eq!(TC_Enum::n_built(), before+1);
+		let (a, b) = (TC_Enum::new(&color), TC_Enum::new(&color));
+		assert!(Arc::ptr_eq(&a.shared, &b.shared));
+		assert_eq!(b.value(1), 4);
+		// the state is still per generator.
+		gens[0].next();
+		assert_eq!(gens[0].value(), "BLUE");
+		assert_eq!(gens[1].value(), "RED");
+
+		let shade = Type::Enum("shade".to_string(), values);
+		TC_Enum::new(&shade);
+		assert_eq!(TC_Enum::n_built(), before+2);
+	}
+
+	#[test]
+	#[should_panic]
+	fn literal_out_of_range() {
+		literal(256, &Type::Builtin(Native::U8));
+	}
+}
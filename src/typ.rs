@@ -3,15 +3,52 @@ use function;
 // A Native type is a type that is builtin to the language.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Native {
-	U8, U16, U32, U64, Unsigned, Usize,
-	I8, I16, I32, I64, Integer,
+	U8, U16, U32, U64, U128, Unsigned, Usize,
+	I8, I16, I32, I64, I128, Integer,
+	// C's own integer types, whose widths depend on the target; see fixed().
+	Short, UShort, Long, ULong, LongLong, ULongLong,
 	F32, F64,
 	Boolean,
 	Character,
+	WideCharacter,
 	Void,
 }
 
+// How wide the integer types of the target are, which C leaves open.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataModel {
+	ILP32, // 32bit Unix and Windows: int, long and pointers are 32 bits.
+	LP64, // 64bit Unix: long and pointers are 64 bits.
+	LLP64, // 64bit Windows: only long long and pointers are 64 bits.
+}
+
+impl Default for DataModel {
+	fn default() -> Self { DataModel::LP64 }
+}
+
 impl Native {
+	// The fixed-width type that this type is on the given target.  Fixed-width
+	// and non-integer types are what they are everywhere.
+	pub fn fixed(&self, model: DataModel) -> Native {
+		let wide = model != DataModel::ILP32;
+		match *self {
+			Native::Short => Native::I16,
+			Native::UShort => Native::U16,
+			Native::Integer => Native::I32,
+			Native::Unsigned => Native::U32,
+			Native::Long if model == DataModel::LP64 => Native::I64,
+			Native::Long => Native::I32,
+			Native::ULong if model == DataModel::LP64 => Native::U64,
+			Native::ULong => Native::U32,
+			Native::LongLong => Native::I64,
+			Native::ULongLong => Native::U64,
+			Native::Usize if wide => Native::U64,
+			Native::Usize => Native::U32,
+			_ => *self,
+		}
+	}
+
 	// True if this type is "wider" than the given Native type.  Wider means that
 	// it is always safe to assign a narrower-type to the wider-type, and almost
 	// always unsafe to assign the other way.
@@ -53,6 +90,8 @@ impl RTTI for u8 { fn type_name(&self) -> String { "u8".to_string() } }
 impl RTTI for u16 { fn type_name(&self) -> String { "u16".to_string() } }
 impl RTTI for u32 { fn type_name(&self) -> String { "u32".to_string() } }
 impl RTTI for u64 { fn type_name(&self) -> String { "u64".to_string() } }
+impl RTTI for i128 { fn type_name(&self) -> String { "i128".to_string() } }
+impl RTTI for u128 { fn type_name(&self) -> String { "u128".to_string() } }
 impl RTTI for usize { fn type_name(&self) -> String { "usize".to_string() } }
 impl RTTI for f32 { fn type_name(&self) -> String { "f32".to_string() } }
 impl RTTI for f64 { fn type_name(&self) -> String { "f64".to_string() } }
@@ -65,28 +104,67 @@ impl RTTI for Native {
 			Native::U32 => "u32".to_string(), Native::U64 => "u64".to_string(),
 			Native::I8 => "u8".to_string(), Native::I16 => "u16".to_string(),
 			Native::I32 => "u32".to_string(), Native::I64 => "u64".to_string(),
+			Native::U128 => "u128".to_string(), Native::I128 => "i128".to_string(),
 			Native::Unsigned => "unsigned".to_string(),
 			Native::Usize => "usize".to_string(),
 			Native::Integer => "i32".to_string(),
+			Native::Short => "short".to_string(),
+			Native::UShort => "ushort".to_string(),
+			Native::Long => "long".to_string(),
+			Native::ULong => "ulong".to_string(),
+			Native::LongLong => "longlong".to_string(),
+			Native::ULongLong => "ulonglong".to_string(),
 			Native::F32 => "f32".to_string(), Native::F64 => "f64".to_string(),
 			Native::Boolean => "bool".to_string(),
 			Native::Character => "char".to_string(),
+			Native::WideCharacter => "wchar".to_string(),
 			Native::Void => "void".to_string(),
 		}
 	}
 }
 
-pub type EnumValue = (String, i64);
-pub type Field = (String, Box<Type>);
+// An enumerator: its name, its numeric value, and the value as it was spelled
+// in the declaration (e.g. "0x10" or "'z'"), so that we can emit it verbatim.
+pub type EnumValue = (String, i64, String);
+// A struct field: its name, its type, and its width in bits if it is a
+// bitfield.
+pub type Field = (String, Box<Type>, Option<u32>);
+
+// Qualifiers that can be attached to a pointer.  They change how the pointer
+// is spelled in C, but not the values it can take on.  Only const can also
+// qualify a type that is not a pointer, as the char of "const char*" is.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Qualifier {
+	Const,
+	Restrict,
+	Aligned(u32),
+}
+
+impl Name for Qualifier {
+	fn name(&self) -> String {
+		match self {
+			&Qualifier::Const => "const".to_string(),
+			&Qualifier::Restrict => "restrict".to_string(),
+			&Qualifier::Aligned(n) => format!("__attribute__((aligned({})))", n),
+		}
+	}
+}
 
 // A Type holds the basic immutable type information of the object.
 #[derive(Clone, Debug)]
 pub enum Type {
 	Builtin(Native),
 	Pointer(Box<Type>),
-	Struct(String, Vec<Field>),
+	// the name, the fields and whether it is packed, i.e. has no padding.
+	Struct(String, Vec<Field>, bool),
 	Enum(String, Vec<EnumValue>),
-	Function(Box<function::Function>)
+	Function(Box<function::Function>),
+	// A type with qualifiers.  Always a Type::Pointer, unless the only
+	// qualifier is const.
+	Qualified(Box<Type>, Vec<Qualifier>),
+	// An array of a fixed number of elements, as in the field "int m[3]".  An
+	// array of arrays has more dimensions.
+	Array(Box<Type>, usize),
 }
 
 impl PartialEq for Type {
@@ -98,8 +176,9 @@ impl PartialEq for Type {
 			&Type::Pointer(ref x) => match other {
 				&Type::Pointer(ref y) => x == y, _ => false
 			},
-			&Type::Struct(ref s, ref flds) => match other {
-				&Type::Struct(ref t, ref oflds) => s==t && flds==oflds,
+			&Type::Struct(ref s, ref flds, packed) => match other {
+				&Type::Struct(ref t, ref oflds, opacked) =>
+					s==t && flds==oflds && packed==opacked,
 				_ => false,
 			},
 			&Type::Enum(ref s, ref vals) => match other {
@@ -110,6 +189,12 @@ impl PartialEq for Type {
 			&Type::Function(ref fqn) => match other {
 				&Type::Function(ref ofqn) => fqn.name == ofqn.name, _ => false,
 			},
+			&Type::Qualified(ref x, ref q) => match other {
+				&Type::Qualified(ref y, ref r) => x == y && q == r, _ => false,
+			},
+			&Type::Array(ref x, n) => match other {
+				&Type::Array(ref y, m) => x == y && n == m, _ => false,
+			},
 		}
 	}
 	fn ne(&self, other: &Type) -> bool {
@@ -123,9 +208,151 @@ impl Type {
 		use std::ops::Deref;
 		match self {
 			&Type::Pointer(ref inner) => inner.deref().clone(),
+			&Type::Qualified(ref ptr, _) => ptr.dereference(),
 			_ => panic!("Can't deref a non-pointer type!"),
 		}
 	}
+
+	// Whether variable::generator() can create a generator for values of this
+	// type.  If not, gives the first part of the type that it cannot generate,
+	// such as the type of one of the fields of a struct.
+	#[allow(dead_code)]
+	pub fn is_generatable(&self) -> Result<(), Type> {
+		match self.ungeneratable("") {
+			None => Ok(()),
+			Some((_, leaf)) => Err(leaf),
+		}
+	}
+
+	// As is_generatable(), but also gives the path to the part that cannot be
+	// generated, such as "S.inner.x" for a field of a struct in a struct S.
+	// 'path' is how to refer to this type; it is empty for the type itself.
+	pub fn ungeneratable(&self, path: &str) -> Option<(String, Type)> {
+		match *self {
+			Type::Builtin(nat) => match nat {
+				Native::I32 | Native::Usize | Native::I128 | Native::U128 |
+				Native::Integer | Native::Boolean => None,
+				_ => Some((path.to_string(), self.clone())),
+			},
+			// generic pointers never look at what they point to.
+			Type::Pointer(_) => None,
+			Type::Qualified(ref ty, _) => ty.ungeneratable(path),
+			// a struct without fields is opaque, and generated as a whole.
+			Type::Struct(ref nm, ref flds, _) => {
+				for &(ref fname, ref fty, bits) in flds.iter() {
+					let base = if path.is_empty() { nm.as_str() } else { path };
+					let fpath = format!("{}.{}", base, fname);
+					let found = match bits {
+						Some(_) => match **fty {
+							Type::Builtin(Native::I8) | Type::Builtin(Native::I16) |
+							Type::Builtin(Native::I32) | Type::Builtin(Native::I64) |
+							Type::Builtin(Native::Integer) | Type::Builtin(Native::U8) |
+							Type::Builtin(Native::U16) | Type::Builtin(Native::U32) |
+							Type::Builtin(Native::U64) | Type::Builtin(Native::Unsigned) |
+							Type::Builtin(Native::Usize) |
+							Type::Builtin(Native::Boolean) => None,
+							_ => Some((fpath, (**fty).clone())),
+						},
+						None => fty.ungeneratable(&fpath),
+					};
+					if found.is_some() {
+						return found;
+					}
+				}
+				None
+			},
+			// an enum without values was never declared.
+			Type::Enum(_, ref values) if values.is_empty() =>
+				Some((path.to_string(), self.clone())),
+			Type::Enum(_, _) => None,
+			Type::Function(_) => Some((path.to_string(), self.clone())),
+			Type::Array(ref elem, _) => elem.ungeneratable(path),
+		}
+	}
+
+	// The type of the elements of a (possibly multi-dimensional) array, and
+	// its sizes as they are written after a name: outermost first, as in
+	// "[2][3]".  Other types are their own elements, without sizes.
+	pub fn array_parts(&self) -> (&Type, String) {
+		match *self {
+			Type::Array(ref elem, n) => {
+				let (base, dims) = elem.array_parts();
+				(base, format!("[{}]{}", n, dims))
+			},
+			_ => (self, String::new()),
+		}
+	}
+
+	// Declares 'name' to be of this type, as in "int32_t m[2][3]".
+	pub fn declarator(&self, name: &str) -> String {
+		let (base, dims) = self.array_parts();
+		format!("{} {}{}", base.name(), name, dims)
+	}
+
+	// The type without any qualifiers.
+	pub fn unqualified(&self) -> &Type {
+		match self {
+			&Type::Qualified(ref ptr, _) => ptr,
+			_ => self,
+		}
+	}
+
+	// The type without attributes anywhere in it, such as the alignment of the
+	// pointer that a pointer points to.  Qualifiers that C allows in a cast
+	// are kept.
+	pub fn without_attributes(&self) -> Type {
+		match self {
+			&Type::Qualified(ref ptr, ref quals) => {
+				let quals: Vec<Qualifier> = quals.iter().filter(|q| match **q {
+					Qualifier::Aligned(_) => false,
+					Qualifier::Const | Qualifier::Restrict => true,
+				}).cloned().collect();
+				if quals.is_empty() {
+					ptr.without_attributes()
+				} else {
+					Type::Qualified(Box::new(ptr.without_attributes()), quals)
+				}
+			},
+			&Type::Pointer(ref inner) =>
+				Type::Pointer(Box::new(inner.without_attributes())),
+			_ => self.clone(),
+		}
+	}
+
+	// How the type is spelled in a cast, e.g. "(void *)": the '*'s of a pointer
+	// are set apart from the type that they point to.
+	pub fn cast_name(&self) -> String {
+		match self {
+			&Type::Pointer(ref base) => match **base {
+				Type::Pointer(_) => base.cast_name() + "*",
+				_ => base.cast_name() + " *",
+			},
+			&Type::Qualified(ref ptr, ref quals) => {
+				quals.iter().fold(ptr.cast_name(), |nm, q| nm + " " + &q.name())
+			},
+			_ => self.name(),
+		}
+	}
+
+	// The type of the named field of this struct.  The fields of anonymous
+	// members (those with an empty name) are fields of this struct, as in C11.
+	pub fn field_type(&self, name: &str) -> Option<Type> {
+		let fields = match *self {
+			Type::Struct(_, ref flds, _) => flds,
+			_ => return None,
+		};
+		for &(ref fname, ref fty, _) in fields.iter() {
+			if fname == name {
+				return Some((**fty).clone());
+			}
+			if fname.is_empty() {
+				if let Some(ty) = fty.field_type(name) {
+					return Some(ty);
+				}
+			}
+		}
+		None
+	}
 }
 
 impl RTTI for Type {
@@ -133,7 +360,7 @@ impl RTTI for Type {
 		match self {
 			&Type::Builtin(ref nat) => nat.type_name(),
 			&Type::Pointer(ref base) => base.type_name() + "*",
-			&Type::Struct(ref nm, ref flds) => {
+			&Type::Struct(ref nm, ref flds, _) => {
 				use std::fmt::Write;
 				let mut rv = String::new();
 				write!(&mut rv, "struct {} {{", nm).unwrap();
@@ -146,6 +373,13 @@ impl RTTI for Type {
 			},
 			&Type::Enum(ref nm, _) => "enum ".to_string() + &nm,
 			&Type::Function(ref fqn) => "func ".to_string() + &fqn.name,
+			&Type::Qualified(ref ptr, ref quals) => {
+				quals.iter().fold(ptr.type_name(), |nm, q| nm + " " + &q.name())
+			},
+			&Type::Array(_, _) => {
+				let (base, dims) = self.array_parts();
+				base.type_name() + &dims
+			},
 		}
 	}
 }
@@ -166,11 +400,18 @@ impl Name for Native {
 			&Native::U16 => "uint16_t", &Native::I16 => "int16_t",
 			&Native::U32 => "uint32_t", &Native::I32 => "int32_t",
 			&Native::U64 => "uint64_t", &Native::I64 => "int64_t",
+			// a GCC/clang extension; there is no int128_t.
+			&Native::U128 => "unsigned __int128", &Native::I128 => "__int128",
 			&Native::F32 => "float", &Native::F64 => "double",
 			&Native::Usize => "size_t", &Native::Integer => "int",
 			&Native::Unsigned => "unsigned",
+			&Native::Short => "short", &Native::UShort => "unsigned short",
+			&Native::Long => "long", &Native::ULong => "unsigned long",
+			&Native::LongLong => "long long",
+			&Native::ULongLong => "unsigned long long",
 			&Native::Boolean => "bool",
 			&Native::Character => "char",
+			&Native::WideCharacter => "wchar_t",
 			&Native::Void => "void",
 		}.to_string()
 	}
@@ -186,9 +427,17 @@ impl Name for Type {
 				tryp!(write!(&mut res, "{}*", t.name()));
 				res
 			},
-			&Type::Struct(ref udt, _) => "struct ".to_string() + &udt.clone(),
+			&Type::Struct(ref udt, _, _) => "struct ".to_string() + &udt.clone(),
 			&Type::Enum(ref enm, _) => enm.clone(),
 			&Type::Function(ref fqn) => fqn.name.clone(),
+			// qualifiers go after the '*': "int* restrict", "char const*"
+			&Type::Qualified(ref ptr, ref quals) => {
+				quals.iter().fold(ptr.name(), |nm, q| nm + " " + &q.name())
+			},
+			&Type::Array(_, _) => {
+				let (base, dims) = self.array_parts();
+				base.name() + &dims
+			},
 		}
 	}
 }
@@ -5,6 +5,7 @@ use rand::distributions::{IndependentSample, Range};
 
 use opcode::BinOp;
 use typ::*;
+use util;
 use variable::Generator;
 
 #[derive(Clone)]
@@ -25,7 +26,7 @@ impl UserGen {
 			&Type::Builtin(ref blt) => blt.clone(),
 			_ => panic!("need native type for typed_min: {:?}", t),
 		};
-		match ty {
+		match ty.fixed(DataModel::default()) {
 			Native::I8 => i8::min_value().to_string(),
 			Native::U8 => u8::min_value().to_string(),
 			Native::I16 => i16::min_value().to_string(),
@@ -44,7 +45,7 @@ impl UserGen {
 			&Type::Builtin(ref blt) => blt.clone(),
 			_ => panic!("need native type for typed_max: {:?}", t),
 		};
-		match ty {
+		match ty.fixed(DataModel::default()) {
 			Native::I8 => i8::max_value().to_string(),
 			Native::U8 => u8::max_value().to_string(),
 			Native::I16 => i16::max_value().to_string(),
@@ -95,7 +96,7 @@ impl UserGen {
 				let lo = l.parse::<i64>().unwrap();
 				let hi = h.parse::<i64>().unwrap();
 				let range = Range::new(lo, hi);
-				range.ind_sample(&mut rand::thread_rng()).to_string()
+				range.ind_sample(&mut util::rng()).to_string()
 			}
 		}
 	}
@@ -210,7 +211,7 @@ impl ::variable::Generator for UserGen {
 		}
 		write!(f, "}}}}")
 	}
-	fn clone(&self) -> Box<Generator> {
+	fn clone_box(&self) -> Box<Generator> {
 		Box::new(UserGen{ty: self.ty.clone(), name: self.name.clone(),
 		                 states: self.states.clone(),
 		                 idx: self.idx})
@@ -1,5 +1,461 @@
+use std::cell::RefCell;
+use std::fmt;
+use lalrpop_util::ParseError;
+use rand;
+use rand::{Rng, SeedableRng, XorShiftRng};
+use rand::distributions::{IndependentSample, Range};
 use typ::*;
 
+// All of the randomness used in generating values comes from this per-thread
+// RNG instead of rand::thread_rng, so that a case can be reproduced exactly by
+// seeding it.  It starts out randomly seeded.
+thread_local!(static RNG: RefCell<XorShiftRng> =
+              RefCell::new(rand::thread_rng().gen::<XorShiftRng>()));
+
+// A handle to the current thread's generation RNG.
+pub struct FuzzRng;
+impl Rng for FuzzRng {
+	fn next_u32(&mut self) -> u32 {
+		RNG.with(|r| r.borrow_mut().next_u32())
+	}
+}
+
+pub fn rng() -> FuzzRng { FuzzRng }
+
+// Picks an index into 'weights' at random, where the chance of each index is
+// proportional to its weight.
+pub fn weighted_index(weights: &[u32]) -> usize {
+	let total: u64 = weights.iter().map(|&w| w as u64).sum();
+	assert!(total > 0, "no index has any weight");
+	let mut pick = Range::new(0, total).ind_sample(&mut rng());
+	for (i, &w) in weights.iter().enumerate() {
+		if pick < w as u64 {
+			return i;
+		}
+		pick -= w as u64;
+	}
+	unreachable!()
+}
+
+// Reseeds the current thread's RNG.  The same (seed, stream) pair always gives
+// the same sequence of random values; we use the stream to give every case of
+// a program its own sequence.
+pub fn seed_rng(seed: u64, stream: u64) {
+	// the leading constant ensures the seed is never all zeros, which
+	// XorShiftRng does not allow.
+	let s: [u32; 4] = [0x9e3779b9, (seed >> 32) as u32, seed as u32,
+	                   (stream as u32) ^ ((stream >> 32) as u32)];
+	RNG.with(|r| *r.borrow_mut() = XorShiftRng::from_seed(s));
+}
+
+// Runs 'f', then puts the RNG back the way it was, so that whatever 'f' drew
+// is drawn again by the next caller.
+pub fn replaying<T, F: FnOnce() -> T>(f: F) -> T {
+	let saved = RNG.with(|r| r.borrow().clone());
+	let rv = f();
+	RNG.with(|r| *r.borrow_mut() = saved);
+	rv
+}
+
+// Spells the given byte such that it can appear inside a C string literal.
+// Non-printable bytes use octal escapes, because those end after three digits;
+// a hex escape would swallow any hex digits that happen to follow it.
+pub fn c_escape(byte: u8) -> String {
+	match byte {
+		b'"' => "\\\"".to_string(),
+		b'\\' => "\\\\".to_string(),
+		// a '?' could otherwise form a trigraph with the characters after it.
+		b'?' => "\\?".to_string(),
+		0x20 ... 0x7e => (byte as char).to_string(),
+		_ => format!("\\{:03o}", byte),
+	}
+}
+
+// The bytes that a C string literal, as generators spell them, stands for:
+// without the quotes, and without the NUL that terminates it.  The universal
+// character names of wide literals, such as \u00e9, become UTF-8.  Gives None
+// for anything that is not a single string literal, such as "NULL".
+pub fn c_unescape(literal: &str) -> Option<Vec<u8>> {
+	let b = if literal.starts_with("L\"") {
+		literal[1..].as_bytes()
+	} else {
+		literal.as_bytes()
+	};
+	if b.len() < 2 || b[0] != b'"' || b[b.len()-1] != b'"' {
+		return None;
+	}
+	let b = &b[1..b.len()-1];
+	let mut rv: Vec<u8> = Vec::with_capacity(b.len());
+	let mut i = 0;
+	while i < b.len() {
+		if b[i] != b'\\' {
+			rv.push(b[i]);
+			i += 1;
+			continue;
+		}
+		i += 1;
+		if i == b.len() {
+			return None;
+		}
+		match b[i] {
+			b'0' ... b'7' => {
+				let mut v: u32 = 0;
+				let mut ndigits = 0;
+				while ndigits < 3 && i < b.len() && b'0' <= b[i] && b[i] <= b'7' {
+					v = v*8 + (b[i] - b'0') as u32;
+					i += 1;
+					ndigits += 1;
+				}
+				rv.push(v as u8);
+			},
+			// a hex escape takes every hex digit after it; a universal character
+			// name takes exactly 4 or 8.
+			esc @ b'x' | esc @ b'u' | esc @ b'U' => {
+				let width = match esc { b'u' => 4, b'U' => 8, _ => b.len() };
+				i += 1;
+				let start = i;
+				while i < b.len() && i-start < width && (b[i] as char).is_digit(16) {
+					i += 1;
+				}
+				let digits = std::str::from_utf8(&b[start..i]).unwrap();
+				let v = match u32::from_str_radix(digits, 16) {
+					Err(_) => return None,
+					Ok(v) => v,
+				};
+				if esc == b'x' {
+					if v > 0xff {
+						return None;
+					}
+					rv.push(v as u8);
+					continue;
+				}
+				if i-start != width {
+					return None;
+				}
+				match std::char::from_u32(v) {
+					None => return None,
+					Some(c) => {
+						let mut buf = [0u8; 4];
+						rv.extend(c.encode_utf8(&mut buf).as_bytes());
+					},
+				};
+			},
+			b'n' => { rv.push(b'\n'); i += 1; },
+			b't' => { rv.push(b'\t'); i += 1; },
+			b'r' => { rv.push(b'\r'); i += 1; },
+			b'a' => { rv.push(0x07); i += 1; },
+			b'b' => { rv.push(0x08); i += 1; },
+			b'f' => { rv.push(0x0c); i += 1; },
+			b'v' => { rv.push(0x0b); i += 1; },
+			// \", \\, \' and \? all just mean the character itself.
+			c => { rv.push(c); i += 1; },
+		}
+	}
+	Some(rv)
+}
+
+// Escapes the given text for use in XML character data or attribute values.
+pub fn xml_escape(text: &str) -> String {
+	let mut rv = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'&' => rv.push_str("&amp;"),
+			'<' => rv.push_str("&lt;"),
+			'>' => rv.push_str("&gt;"),
+			'"' => rv.push_str("&quot;"),
+			'\'' => rv.push_str("&apos;"),
+			_ => rv.push(c),
+		};
+	}
+	rv
+}
+
+// Escapes the given text for use inside a JSON string.
+pub fn json_escape(text: &str) -> String {
+	let mut rv = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'"' => rv.push_str("\\\""),
+			'\\' => rv.push_str("\\\\"),
+			'\n' => rv.push_str("\\n"),
+			'\r' => rv.push_str("\\r"),
+			'\t' => rv.push_str("\\t"),
+			c if (c as u32) < 0x20 => rv.push_str(&format!("\\u{:04x}", c as u32)),
+			_ => rv.push(c),
+		};
+	}
+	rv
+}
+
+// The 64-bit FNV-1a hash of the given bytes.  Unlike std's hashers, its
+// values are the same on every platform and with every version of Rust.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for b in bytes.iter() {
+		hash ^= *b as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+	}
+	hash
+}
+
+// Words that can't be used as identifiers in C (through C11).
+const C_KEYWORDS: [&'static str; 44] = [
+	"auto", "break", "case", "char", "const", "continue", "default", "do",
+	"double", "else", "enum", "extern", "float", "for", "goto", "if", "inline",
+	"int", "long", "register", "restrict", "return", "short", "signed",
+	"sizeof", "static", "struct", "switch", "typedef", "union", "unsigned",
+	"void", "volatile", "while", "_Alignas", "_Alignof", "_Atomic", "_Bool",
+	"_Complex", "_Generic", "_Imaginary", "_Noreturn", "_Static_assert",
+	"_Thread_local",
+];
+
+// Turns the given name into a valid C identifier that is not in 'taken'.
+// Invalid characters become '_', names starting with a digit get a leading
+// '_', keywords get a trailing '_', and collisions get a numeric suffix.
+pub fn c_identifier(name: &str, taken: &Vec<String>) -> String {
+	let mut ident: String = name.chars().map(|c| match c {
+		'a' ... 'z' | 'A' ... 'Z' | '0' ... '9' | '_' => c,
+		_ => '_',
+	}).collect();
+	if ident.is_empty() || ident.chars().next().unwrap().is_digit(10) {
+		ident.insert(0, '_');
+	}
+	if C_KEYWORDS.contains(&ident.as_str()) {
+		ident.push('_');
+	}
+	if !taken.contains(&ident) {
+		return ident;
+	}
+	let mut n: usize = 2;
+	while taken.contains(&format!("{}_{}", ident, n)) {
+		n += 1;
+	}
+	format!("{}_{}", ident, n)
+}
+
+// The value of a (possibly negative) hexadecimal C literal, such as "0x1F".
+pub fn hex_value(lit: &str) -> Result<i64, String> {
+	// the sign is parsed along with the digits, so that the most negative i64
+	// is not taken for an overflow.
+	let digits = match lit.starts_with("-") {
+		true => format!("-{}", &lit[3..]),
+		false => lit[2..].to_string(),
+	};
+	i64::from_str_radix(&digits, 16).map_err(|e| {
+		format!("error parsing '{}' as a hex i64: {}", lit, e)
+	})
+}
+
+// The value of a C character constant, such as 'A' or '\n'.  The given string
+// includes the surrounding single quotes.
+pub fn char_value(lit: &str) -> Result<i64, String> {
+	let inner: Vec<char> = lit[1..lit.len()-1].chars().collect();
+	if inner[0] != '\\' {
+		return Ok(inner[0] as i64);
+	}
+	match inner[1] {
+		// an octal escape of a single digit, as in '\0'.
+		d @ '0' ... '7' => Ok(d as i64 - '0' as i64),
+		'a' => Ok(7), 'b' => Ok(8), 't' => Ok(9), 'n' => Ok(10), 'v' => Ok(11),
+		'f' => Ok(12), 'r' => Ok(13), '"' => Ok(34), '\'' => Ok(39),
+		'?' => Ok(63), '\\' => Ok(92),
+		_ => Err(format!("unknown escape sequence in character constant {}",
+		                 lit)),
+	}
+}
+
+// The values of the enumerators of an enum declaration.  As in C, an
+// enumerator without a value is one more than the previous enumerator, or 0 if
+// it is the first.  Enumerators that refer to a constant are spelled as such,
+// along with those that follow them; their values are filled in when the
+// constants are resolved.  It is an error for an implicit value to be one more
+// than the largest i64.
+pub fn enum_values(constants: Vec<(String, Option<(i64, String)>)>)
+	-> Result<Vec<EnumValue>, String> {
+	let mut values: Vec<EnumValue> = Vec::new();
+	// the previous enumerator, and its value or the constant it refers to.
+	let mut prev: Option<String> = None;
+	let mut next: Option<i64> = Some(0);
+	let mut alias: Option<(String, i64)> = None;
+	for (name, explicit) in constants {
+		let overflow = || format!("enumerator '{}' overflows after '{}'", name,
+		                          prev.clone().unwrap_or_default());
+		let (v, spelling) = match (explicit, alias) {
+			(Some(val), _) => val,
+			(None, Some((nm, off))) => match off.checked_add(1) {
+				Some(o) => (0, format!("{} + {}", nm, o)),
+				None => return Err(overflow()),
+			},
+			(None, None) => match next {
+				Some(n) => (n, n.to_string()),
+				None => return Err(overflow()),
+			},
+		};
+		alias = constant_reference(&spelling);
+		next = v.checked_add(1);
+		values.push((name.clone(), v, spelling));
+		prev = Some(name);
+	}
+	Ok(values)
+}
+
+// The constant and offset of a value spelled as a reference to a constant,
+// such as "BUFSIZ" or "BUFSIZ + 2".
+pub fn constant_reference(spelling: &str) -> Option<(String, i64)> {
+	let mut parts = spelling.splitn(2, " + ");
+	let nm = parts.next().unwrap_or("");
+	match nm.chars().next() {
+		Some(c) if c.is_alphabetic() || c == '_' => (),
+		_ => return None,
+	};
+	match parts.next().map(|off| off.parse::<i64>()) {
+		None => Some((nm.to_string(), 0)),
+		Some(Ok(off)) => Some((nm.to_string(), off)),
+		Some(Err(_)) => None,
+	}
+}
+
+// Re-indents generated C code with one tab per level of brace nesting.
+// Generators build their values without knowing how deeply they will be
+// nested, so this is easier than threading the depth through them.  Braces
+// inside string and character literals and comments are not counted.
+pub fn indent_c(code: &str) -> String {
+	let mut rv = String::with_capacity(code.len());
+	let mut depth: usize = 0;
+	for line in code.lines() {
+		let text = line.trim();
+		// 'lowest' is the lowest depth we reach on this line, relative to the
+		// start of the line: a line which closes a block is part of the outer
+		// block, even if it also opens another one, as in "} else {".
+		let (mut delta, mut lowest): (isize, isize) = (0, 0);
+		let mut quote: Option<char> = None;
+		let mut chars = text.chars().peekable();
+		while let Some(c) = chars.next() {
+			match (quote, c) {
+				(Some(_), '\\') => { chars.next(); },
+				(Some(q), _) if c == q => quote = None,
+				(Some(_), _) => (),
+				(None, '"') | (None, '\'') => quote = Some(c),
+				(None, '/') if chars.peek() == Some(&'/') => break,
+				(None, '{') => delta += 1,
+				(None, '}') => {
+					delta -= 1;
+					lowest = lowest.min(delta);
+				},
+				(None, _) => (),
+			};
+		}
+		if !text.is_empty() {
+			let level = (depth as isize + lowest).max(0) as usize;
+			for _ in 0..level {
+				rv.push('\t');
+			}
+			rv.push_str(text);
+		}
+		rv.push('\n');
+		depth = (depth as isize + delta).max(0) as usize;
+	}
+	rv
+}
+
+// Glues the tokens of an expression back together.  Whitespace is not
+// preserved by the lexer, so we only put a space between two tokens that would
+// otherwise run into each other, e.g. "unsigned" and "x", or "-" and "-1",
+// which would be a decrement.
+pub fn join_tokens(tokens: &Vec<String>) -> String {
+	let word = |c: char| c.is_alphanumeric() || c == '_' || c == '\'';
+	let operator = |c: char| "+-*/%&|^!~<>=".contains(c);
+	let mut rv = String::new();
+	for tok in tokens.iter() {
+		let sep = match (rv.chars().last(), tok.chars().next()) {
+			(Some(a), Some(b)) =>
+				(word(a) && word(b)) || (operator(a) && operator(b)),
+			_ => false,
+		};
+		if sep {
+			rv.push(' ');
+		}
+		rv.push_str(tok);
+	}
+	rv
+}
+
+// Splits a comma-separated list of expressions, ignoring commas that are
+// nested in parentheses or quoted, as in "f(a, b), c, \",\"".
+pub fn split_args(text: &str) -> Vec<String> {
+	let mut rv: Vec<String> = Vec::new();
+	let mut depth: usize = 0;
+	let mut cur = String::new();
+	// the quote of the literal we are in, and whether the last char escaped.
+	let mut quote: Option<char> = None;
+	let mut escaped = false;
+	for c in text.chars() {
+		if let Some(q) = quote {
+			if !escaped && c == q {
+				quote = None;
+			}
+			escaped = !escaped && c == '\\';
+			cur.push(c);
+			continue;
+		}
+		match c {
+			'"' | '\'' => quote = Some(c),
+			'(' => depth += 1,
+			')' => depth = depth.saturating_sub(1),
+			',' if depth == 0 => {
+				rv.push(cur.trim().to_string());
+				cur.clear();
+				continue;
+			},
+			_ => (),
+		};
+		cur.push(c);
+	}
+	if !cur.trim().is_empty() || !rv.is_empty() {
+		rv.push(cur.trim().to_string());
+	}
+	rv
+}
+
+// The 1-based line and column of the given byte offset into 'text'.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+	let before = &text[..offset.min(text.len())];
+	let line = before.matches('\n').count() + 1;
+	let column = match before.rfind('\n') {
+		None => before.chars().count() + 1,
+		Some(nl) => before[nl+1..].chars().count() + 1,
+	};
+	(line, column)
+}
+
+// Describes an error from parsing 'text', including where it happened.
+pub fn parse_error<T, E: fmt::Display>(text: &str, err: ParseError<usize, T, E>)
+	-> String {
+	let at = |loc: usize| {
+		let (line, column) = line_column(text, loc);
+		format!("line {}, column {}", line, column)
+	};
+	let expecting = |expected: &Vec<String>| {
+		if expected.is_empty() {
+			return String::new();
+		}
+		format!("; expected one of {}", expected.join(", "))
+	};
+	match err {
+		ParseError::InvalidToken{location} =>
+			format!("{}: invalid token", at(location)),
+		ParseError::UnrecognizedToken{token: Some((start, _, end)), expected} =>
+			format!("{}: unexpected '{}'{}", at(start), &text[start..end],
+			        expecting(&expected)),
+		ParseError::UnrecognizedToken{token: None, expected} =>
+			format!("{}: unexpected end of input{}", at(text.len()),
+			        expecting(&expected)),
+		ParseError::ExtraToken{token: (start, _, end)} =>
+			format!("{}: extra '{}'", at(start), &text[start..end]),
+		ParseError::User{error} => format!("{}", error),
+	}
+}
+
 pub fn type_from_str(typename: &str) -> Type {
 	Type::Builtin(match typename {
 		"I8" | "i8" => Native::I8,